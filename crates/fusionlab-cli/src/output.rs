@@ -0,0 +1,81 @@
+//! Result files written by `--output-file`
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// A file that only appears at its path once it is complete
+///
+/// Writes go to a temporary file next to the target, which `commit` renames
+/// into place. If the file is dropped without `commit`, e.g. because the
+/// query failed halfway, the temporary file is removed and an existing file
+/// at the target path is left untouched.
+pub struct AtomicFile {
+    path: PathBuf,
+    temp_path: PathBuf,
+    writer: Option<BufWriter<File>>,
+    committed: bool,
+}
+
+impl AtomicFile {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let name = path
+            .file_name()
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{:?} is not a file path", path),
+                )
+            })?
+            .to_string_lossy();
+        // Same directory, so the rename cannot cross file systems
+        let temp_path = path.with_file_name(format!(".{}.tmp-{}", name, std::process::id()));
+        let file = File::create(&temp_path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            temp_path,
+            writer: Some(BufWriter::new(file)),
+            committed: false,
+        })
+    }
+
+    /// Flush the data to disk and move the file to its final path
+    pub fn commit(mut self) -> io::Result<()> {
+        let file = self
+            .writer
+            .take()
+            .expect("writer is only taken by commit")
+            .into_inner()
+            .map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        drop(file);
+        std::fs::rename(&self.temp_path, &self.path)?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer
+            .as_mut()
+            .expect("writer is only taken by commit")
+            .write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer
+            .as_mut()
+            .expect("writer is only taken by commit")
+            .flush()
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if !self.committed {
+            drop(self.writer.take());
+            let _ = std::fs::remove_file(&self.temp_path);
+        }
+    }
+}
@@ -0,0 +1,238 @@
+//! Interactive SQL shell for the `repl` command
+//!
+//! Both runners live for the whole session, so tables registered with
+//! DataFusion and the MySQL connection pool are reused by every statement.
+//! A statement may span several lines and ends with `;`, or the terminator
+//! set by a `DELIMITER` line; input is split with `split_terminated`, so
+//! semicolons in literals and comments do not end it. Lines starting with
+//! `\` are meta-commands and run immediately.
+
+use fusionlab_core::{
+    split_terminated, DataFusionRunner, DfQueryResult, MySQLRunner, QueryOptions,
+    TableFormatOptions,
+};
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use std::path::PathBuf;
+
+use crate::{print_rows, Backend};
+
+/// History file in the home directory, shared by all sessions
+const HISTORY_FILE: &str = ".fusionlab_history";
+
+const HELP: &str = "\
+Statements end with ';' and may span several lines;
+DELIMITER <terminator> changes the terminator.
+  \\use mysql|df   run the following statements on MySQL or DataFusion
+  \\help          show this help
+  \\q             quit (also Ctrl-D)";
+
+/// A parsed meta-command
+enum Command {
+    Use(Backend),
+    Help,
+    Quit,
+}
+
+impl Command {
+    fn parse(line: &str) -> Result<Self, String> {
+        let mut words = line.split_whitespace();
+        let name = words.next().unwrap_or_default();
+        let arg = words.next();
+        match (name, arg) {
+            ("\\q" | "\\quit", None) => Ok(Command::Quit),
+            ("\\help" | "\\?", None) => Ok(Command::Help),
+            ("\\use", Some("mysql")) => Ok(Command::Use(Backend::Mysql)),
+            ("\\use", Some("df")) => Ok(Command::Use(Backend::Df)),
+            ("\\use", _) => Err("usage: \\use mysql|df".to_string()),
+            _ => Err(format!("unknown command {:?}; try \\help", line)),
+        }
+    }
+}
+
+/// The interactive session
+pub struct Repl {
+    mysql: MySQLRunner,
+    df: DataFusionRunner,
+    backend: Backend,
+    show_rows: usize,
+    null_marker: String,
+    table: TableFormatOptions,
+}
+
+impl Repl {
+    pub fn new(
+        mysql: MySQLRunner,
+        df: DataFusionRunner,
+        backend: Backend,
+        show_rows: usize,
+        null_marker: String,
+        table: TableFormatOptions,
+    ) -> Self {
+        Self {
+            mysql,
+            df,
+            backend,
+            show_rows,
+            null_marker,
+            table,
+        }
+    }
+
+    /// Read and run statements until `\q` or end of input
+    pub async fn run(mut self) -> anyhow::Result<()> {
+        let mut editor = DefaultEditor::new()?;
+        let history = history_path();
+        if let Some(path) = &history {
+            // Missing on the first run
+            let _ = editor.load_history(path);
+        }
+        println!("FusionLab shell; \\help for help, \\q to quit");
+
+        let mut buffer = String::new();
+        let mut delimiter = ";".to_string();
+        loop {
+            let prompt = match (buffer.is_empty(), self.backend) {
+                (false, _) => "    -> ",
+                (true, Backend::Mysql) => " mysql> ",
+                (true, Backend::Df) => "    df> ",
+            };
+            // Reading blocks; keep the other runtime workers free
+            let line = match tokio::task::block_in_place(|| editor.readline(prompt)) {
+                Ok(line) => line,
+                // Ctrl-C drops the statement being typed
+                Err(ReadlineError::Interrupted) => {
+                    buffer.clear();
+                    continue;
+                }
+                Err(ReadlineError::Eof) => break,
+                Err(e) => return Err(e.into()),
+            };
+
+            let trimmed = line.trim();
+            if buffer.is_empty() && trimmed.starts_with('\\') {
+                let _ = editor.add_history_entry(trimmed);
+                match Command::parse(trimmed) {
+                    Ok(Command::Quit) => break,
+                    Ok(Command::Help) => println!("{}", HELP),
+                    Ok(Command::Use(backend)) => {
+                        self.backend = backend;
+                        println!("Using {}", backend_name(backend));
+                    }
+                    Err(message) => eprintln!("{}", message),
+                }
+                continue;
+            }
+
+            if !buffer.is_empty() {
+                buffer.push('\n');
+            }
+            buffer.push_str(&line);
+            // Scanned from the start each time, so an open literal or
+            // comment spanning lines is recognized
+            let split = split_terminated(&buffer, &delimiter);
+            if split.statements.is_empty() && split.rest.is_some() {
+                continue;
+            }
+            let statements: Vec<String> =
+                split.statements.iter().map(ToString::to_string).collect();
+            let rest = split.rest.map(str::to_string);
+            delimiter = split.delimiter;
+
+            // The unfinished rest goes into history with its own statement
+            let entered = buffer[..buffer.len() - rest.as_ref().map_or(0, String::len)].trim();
+            if !entered.is_empty() {
+                let _ = editor.add_history_entry(entered);
+            }
+            buffer = rest.unwrap_or_default();
+            for sql in &statements {
+                let outcome = match self.backend {
+                    Backend::Mysql => self.run_mysql(sql).await,
+                    Backend::Df => self.run_df(sql).await,
+                };
+                if let Err(e) = outcome {
+                    eprintln!("Error: {}", e);
+                }
+            }
+        }
+
+        if let Some(path) = &history {
+            if let Err(e) = editor.save_history(path) {
+                eprintln!("Warning: could not save history to {:?}: {}", path, e);
+            }
+        }
+        self.mysql.close().await;
+        Ok(())
+    }
+
+    async fn run_mysql(&self, sql: &str) -> anyhow::Result<()> {
+        // Only the displayed rows are kept
+        let result = self
+            .mysql
+            .run_query_streamed(sql, Some(self.show_rows), &QueryOptions::default(), |_| {})
+            .await?;
+        if result.columns.is_empty() {
+            println!(
+                "{} rows affected ({:.2}ms)",
+                result.affected_rows, result.duration_ms
+            );
+            return Ok(());
+        }
+        print_rows(&result, self.show_rows, &self.null_marker, &self.table)?;
+        println!();
+        println!(
+            "{} rows ({:.2}ms: exec {:.2}ms, fetch {:.2}ms)",
+            result.row_count, result.duration_ms, result.exec_ms, result.fetch_ms
+        );
+        Ok(())
+    }
+
+    async fn run_df(&self, sql: &str) -> anyhow::Result<()> {
+        let result = self
+            .df
+            .run_query_collect(sql)
+            .await
+            .map_err(|e| anyhow::anyhow!("Query failed: {}", e))?;
+        if self.show_rows > 0 && result.row_count > 0 {
+            let head = head(&result, self.show_rows);
+            println!(
+                "{}",
+                head.to_table_with_options(&self.null_marker, &self.table)
+            );
+        }
+        println!("{} rows ({:.2}ms)", result.row_count, result.duration_ms);
+        Ok(())
+    }
+}
+
+fn backend_name(backend: Backend) -> &'static str {
+    match backend {
+        Backend::Mysql => "MySQL",
+        Backend::Df => "DataFusion",
+    }
+}
+
+/// `~/.fusionlab_history`, or no history without a home directory
+fn history_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE))
+}
+
+/// The first `limit` rows of `result`
+fn head(result: &DfQueryResult, limit: usize) -> DfQueryResult {
+    let mut remaining = limit;
+    let batches = result
+        .batches
+        .iter()
+        .filter_map(|batch| {
+            let rows = batch.num_rows().min(remaining);
+            remaining -= rows;
+            (rows > 0).then(|| batch.slice(0, rows))
+        })
+        .collect();
+    DfQueryResult {
+        row_count: result.row_count.min(limit),
+        duration_ms: result.duration_ms,
+        batches,
+        schema: result.schema.clone(),
+    }
+}
@@ -0,0 +1,69 @@
+//! Example: Compare the text and binary protocols over point queries
+//!
+//! Runs the same point query for keys 1..=N over each protocol and prints
+//! latency statistics for both.
+//!
+//! Usage:
+//!   cargo run --release --example protocol_bench -- [iterations] [query]
+//!
+//! Example:
+//!   cargo run --release --example protocol_bench -- 1000 "SELECT * FROM part WHERE p_partkey = {}"
+
+use fusionlab_core::{MySQLConfig, MySQLRunner, Protocol, QueryOptions, QueryStats};
+
+const DEFAULT_QUERY: &str = "SELECT c_custkey, c_name FROM customer WHERE c_custkey = {}";
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().collect();
+
+    let iterations = match args.get(1).map(|s| s.parse::<usize>()) {
+        None => 1000,
+        Some(Ok(n)) if n > 0 => n,
+        Some(_) => {
+            eprintln!("Usage: {} [iterations] [query]", args[0]);
+            eprintln!();
+            eprintln!("`{{}}` in the query is replaced by the key, 1 to iterations");
+            std::process::exit(1);
+        }
+    };
+    let query = args.get(2).map(|s| s.as_str()).unwrap_or(DEFAULT_QUERY);
+
+    let runner = match MySQLRunner::new(&MySQLConfig::default()) {
+        Ok(runner) => runner,
+        Err(e) => {
+            eprintln!("✗ Failed to create MySQL runner: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    println!("Query: {}", query);
+    println!("Iterations: {}", iterations);
+    println!();
+
+    for protocol in [Protocol::Text, Protocol::Binary] {
+        let options = QueryOptions {
+            protocol,
+            ..QueryOptions::default()
+        };
+        let mut samples = Vec::with_capacity(iterations);
+        let mut rows = 0;
+        for key in 1..=iterations {
+            let sql = query.replace("{}", &key.to_string());
+            match runner.run_query_with_options(&sql, &options).await {
+                Ok(result) => {
+                    samples.push(result.duration_ms);
+                    rows += result.row_count;
+                }
+                Err(e) => {
+                    eprintln!("✗ {:?} query failed: {}", protocol, e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        let stats = QueryStats::from_samples(samples, 0, rows);
+        println!("{:?}: {} ({} rows)", protocol, stats, rows);
+    }
+
+    runner.close().await;
+}
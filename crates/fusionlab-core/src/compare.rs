@@ -0,0 +1,598 @@
+//! Comparison of MySQL and DataFusion results for the same query
+//!
+//! `compare_results` converts the DataFusion result and diffs both with
+//! `verify::diff_results`. Checksums hash rows instead, with cells
+//! normalized by column type to a textual form both engines share (decimal
+//! scale, timestamp separators, float digits).
+
+use std::iter;
+
+use crate::verify::{diff_results, DiffOptions, DiffReport};
+use crate::{CellValue, ColumnType, DfQueryResult, QueryResult, Result};
+
+/// Decimal places checksums keep of non-integer numbers by default
+const FLOAT_DECIMALS: usize = 6;
+
+/// Compare the rows of a MySQL result (`a`) with those of a DataFusion
+/// result (`b`)
+///
+/// The DataFusion result is converted with `DfQueryResult::to_query_result`
+/// and both go through `verify::diff_results`, so `opts` sets the
+/// tolerances; set `ignore_order` unless the query has an `ORDER BY`. The
+/// MySQL result must hold all of its rows (not a streamed prefix).
+pub fn compare_results(
+    mysql: &QueryResult,
+    datafusion: &DfQueryResult,
+    opts: DiffOptions,
+) -> Result<DiffReport> {
+    Ok(diff_results(mysql, &datafusion.to_query_result()?, opts))
+}
+
+/// Float `text` rounded to `decimals` places, or `None` if it is not a
+/// finite number
+fn round_float(text: &str, decimals: usize) -> Option<String> {
+    let float = text.trim().parse::<f64>().ok().filter(|f| f.is_finite())?;
+    Some(trim_fraction(&format!("{:.*}", decimals, float)))
+}
+
+/// Decimal `text` rounded half away from zero to `decimals` places, or
+/// `None` if it is not a plain decimal
+///
+/// Rounding works on the digits, so no precision is lost to a float.
+fn round_decimal(text: &str, decimals: usize) -> Option<String> {
+    let text = text.trim();
+    let (negative, unsigned) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let (int_part, frac_part) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+    if unsigned.is_empty()
+        || !int_part
+            .bytes()
+            .chain(frac_part.bytes())
+            .all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+
+    // The kept digits; the last `decimals` of them are the fraction
+    let mut digits: Vec<u8> = int_part
+        .bytes()
+        .chain(frac_part.bytes().chain(iter::repeat(b'0')).take(decimals))
+        .collect();
+    if frac_part
+        .as_bytes()
+        .get(decimals)
+        .is_some_and(|&d| d >= b'5')
+    {
+        match digits.iter().rposition(|&d| d != b'9') {
+            Some(i) => {
+                digits[i] += 1;
+                digits[i + 1..].fill(b'0');
+            }
+            None => {
+                digits.fill(b'0');
+                digits.insert(0, b'1');
+            }
+        }
+    }
+    let (int_digits, frac_digits) = digits.split_at(digits.len() - decimals);
+    let int_digits = String::from_utf8_lossy(int_digits);
+    let int_digits = int_digits.trim_start_matches('0');
+
+    let mut fixed = String::new();
+    if negative {
+        fixed.push('-');
+    }
+    fixed.push_str(if int_digits.is_empty() {
+        "0"
+    } else {
+        int_digits
+    });
+    if decimals > 0 {
+        fixed.push('.');
+        fixed.push_str(&String::from_utf8_lossy(frac_digits));
+    }
+    Some(trim_fraction(&fixed))
+}
+
+/// Drop trailing fractional zeros from a fixed-point number
+fn trim_fraction(fixed: &str) -> String {
+    let trimmed = if fixed.contains('.') {
+        fixed.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        fixed
+    };
+    // Avoid "-0" for tiny negative values
+    if trimmed == "-0" { "0" } else { trimmed }.to_string()
+}
+
+/// Normalize `DATETIME` and `TIME` text, or `None` for other values
+///
+/// Arrow renders timestamps as `YYYY-MM-DDTHH:MM:SS` and drops trailing
+/// zeros from fractional seconds, MySQL pads them to the column's precision.
+pub(crate) fn normalize_temporal(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let is_datetime = bytes.len() >= 19
+        && bytes[4] == b'-'
+        && bytes[7] == b'-'
+        && matches!(bytes[10], b' ' | b'T')
+        && bytes[13] == b':';
+    let is_time = text.contains(':')
+        && text
+            .bytes()
+            .all(|b| b.is_ascii_digit() || matches!(b, b':' | b'.' | b'-'));
+    if !is_datetime && !is_time {
+        return None;
+    }
+    let mut normalized = if is_datetime {
+        format!("{} {}", &text[..10], &text[11..])
+    } else {
+        text.to_string()
+    };
+    if normalized.contains('.') {
+        normalized.truncate(normalized.trim_end_matches('0').trim_end_matches('.').len());
+    }
+    Some(normalized)
+}
+
+/// Normalization applied before hashing rows into a checksum
+///
+/// Cells are normalized by their value and column type, so text columns
+/// keep `'007'` apart from `'7'`:
+///
+/// - integers and text are hashed as they are
+/// - floats are rounded to `float_decimals` places, with trailing zeros
+///   dropped
+/// - decimals are rounded the same way on their digits, never through a
+///   float, so `12.30` and `12.3` agree and long decimals stay exact
+/// - dates and times use a space separator and drop trailing fractional
+///   zeros
+/// - with `trim_trailing_whitespace`, trailing spaces, tabs and newlines
+///   are removed from every value
+/// - NULL hashes differently from every value, including the string `NULL`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChecksumOptions {
+    /// Decimal places kept for non-integer numbers
+    pub float_decimals: usize,
+    /// Ignore trailing whitespace in values
+    pub trim_trailing_whitespace: bool,
+}
+
+impl Default for ChecksumOptions {
+    fn default() -> Self {
+        Self {
+            float_decimals: FLOAT_DECIMALS,
+            trim_trailing_whitespace: true,
+        }
+    }
+}
+
+impl ChecksumOptions {
+    /// The text hashed for `cell` of a `col_type` column, or `None` for NULL
+    fn normalize(&self, cell: &CellValue, col_type: ColumnType) -> Option<String> {
+        if cell.is_null() {
+            return None;
+        }
+        let text = cell.format_value();
+        let text = if self.trim_trailing_whitespace {
+            text.trim_end()
+        } else {
+            &text
+        };
+        let normalized = match (cell, col_type) {
+            (CellValue::Float(_) | CellValue::Double(_), _)
+            | (CellValue::Str(_), ColumnType::Float | ColumnType::Double) => {
+                round_float(text, self.float_decimals)
+            }
+            (CellValue::Decimal(_), _) | (CellValue::Str(_), ColumnType::Decimal) => {
+                round_decimal(text, self.float_decimals)
+            }
+            (CellValue::Date { .. } | CellValue::DateTime { .. } | CellValue::Time { .. }, _)
+            | (
+                CellValue::Str(_),
+                ColumnType::Date | ColumnType::Time | ColumnType::DateTime | ColumnType::Timestamp,
+            ) => normalize_temporal(text),
+            _ => None,
+        };
+        Some(normalized.unwrap_or_else(|| text.to_string()))
+    }
+}
+
+impl QueryResult {
+    /// Order-insensitive checksum of the rows, to check that two results
+    /// hold the same rows without comparing them cell by cell
+    ///
+    /// Each normalized row (see `ChecksumOptions`) is hashed and the hashes
+    /// are summed, so duplicate rows count. Column names and types are not
+    /// part of the checksum. Matches `DfQueryResult::checksum` for the same
+    /// rows.
+    pub fn checksum(&self, opts: ChecksumOptions) -> u64 {
+        self.rows
+            .iter()
+            .map(|row| {
+                hash_row(
+                    &row.iter()
+                        .enumerate()
+                        .map(|(i, cell)| {
+                            let col_type = self.column_types.get(i).copied();
+                            opts.normalize(cell, col_type.unwrap_or(ColumnType::Unknown))
+                        })
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .fold(0, u64::wrapping_add)
+    }
+}
+
+impl DfQueryResult {
+    /// Order-insensitive checksum of the rows; see `QueryResult::checksum`
+    ///
+    /// The rows are converted with `to_query_result` first, so they are
+    /// normalized by the column types MySQL would report.
+    pub fn checksum(&self, opts: ChecksumOptions) -> Result<u64> {
+        Ok(self.to_query_result()?.checksum(opts))
+    }
+}
+
+/// Hash a normalized row with FNV-1a, stable across Rust releases
+///
+/// Each cell is tagged so NULL, the empty string and `NULL` differ, and
+/// values are terminated with 0xff, which never occurs in UTF-8, so cell
+/// boundaries cannot shift. The result is mixed (SplitMix64 finalizer) so
+/// that summing hashes of similar rows does not cancel out.
+fn hash_row(row: &[Option<String>]) -> u64 {
+    const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+    let mut hash = FNV_OFFSET;
+    let mut write = |bytes: &[u8]| {
+        for &b in bytes {
+            hash ^= u64::from(b);
+            hash = hash.wrapping_mul(FNV_PRIME);
+        }
+    };
+    for cell in row {
+        match cell {
+            None => write(&[0]),
+            Some(value) => {
+                write(&[1]);
+                write(value.as_bytes());
+                write(&[0xff]);
+            }
+        }
+    }
+
+    let mut z = hash;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::verify::RowDiffKind;
+    use crate::{CellValue, ColumnType};
+    use datafusion::arrow::array::{
+        Decimal128Array, Float64Array, Int64Array, StringArray, TimestampMicrosecondArray,
+    };
+    use datafusion::arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+    use datafusion::arrow::record_batch::RecordBatch;
+    use std::sync::Arc;
+
+    fn mysql_result(rows: Vec<Vec<CellValue>>) -> QueryResult {
+        QueryResult {
+            row_count: rows.len(),
+            columns: vec!["id".into(), "amount".into(), "ratio".into(), "ts".into()],
+            column_types: vec![
+                ColumnType::Int,
+                ColumnType::Decimal,
+                ColumnType::Double,
+                ColumnType::DateTime,
+            ],
+            rows,
+            ..QueryResult::default()
+        }
+    }
+
+    fn df_result(ids: Vec<i64>, amounts: Vec<Option<i128>>) -> DfQueryResult {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, true),
+            Field::new("amount", DataType::Decimal128(10, 2), true),
+            Field::new("ratio", DataType::Float64, true),
+            Field::new("ts", DataType::Timestamp(TimeUnit::Microsecond, None), true),
+        ]));
+        let n = ids.len();
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(ids)),
+                Arc::new(
+                    Decimal128Array::from(amounts)
+                        .with_precision_and_scale(10, 2)
+                        .unwrap(),
+                ),
+                Arc::new(Float64Array::from(vec![1.0 / 3.0; n])),
+                Arc::new(TimestampMicrosecondArray::from(vec![86_401_000_000; n])),
+            ],
+        )
+        .unwrap();
+        DfQueryResult {
+            row_count: n,
+            duration_ms: 0.0,
+            batches: vec![batch],
+            schema,
+        }
+    }
+
+    fn mysql_row(id: i64, amount: Option<&str>) -> Vec<CellValue> {
+        vec![
+            CellValue::Int(id),
+            amount.map_or(CellValue::Null, |a| CellValue::Decimal(a.to_string())),
+            CellValue::Double(0.3333333333333333),
+            CellValue::DateTime {
+                year: 1970,
+                month: 1,
+                day: 2,
+                hour: 0,
+                minute: 0,
+                second: 1,
+                micros: 0,
+            },
+        ]
+    }
+
+    fn unordered() -> DiffOptions {
+        DiffOptions {
+            ignore_order: true,
+            ..DiffOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_compare_matching_ignores_order() {
+        let mysql = mysql_result(vec![mysql_row(1, Some("12.30")), mysql_row(2, None)]);
+        let df = df_result(vec![2, 1], vec![None, Some(1230)]);
+        let report = compare_results(&mysql, &df, unordered()).unwrap();
+        assert!(report.is_match(), "{}", report);
+        assert_eq!(report.a_row_count, 2);
+        assert_eq!(report.b_row_count, 2);
+    }
+
+    #[test]
+    fn test_compare_reports_differences() {
+        let mysql = mysql_result(vec![
+            mysql_row(1, Some("12.30")),
+            mysql_row(1, Some("12.30")),
+            mysql_row(2, Some("5.00")),
+        ]);
+        let df = df_result(vec![1, 2], vec![Some(1230), Some(501)]);
+        let report = compare_results(&mysql, &df, unordered()).unwrap();
+        assert!(!report.is_match());
+        // The duplicate row counts as a difference
+        assert_eq!(report.missing_in_b, 2);
+        assert_eq!(report.missing_in_a, 1);
+        let values = |row: &Option<Vec<String>>| row.as_ref().unwrap()[..2].to_vec();
+        assert_eq!(values(&report.rows[0].a), ["1", "12.30"]);
+        assert_eq!(values(&report.rows[1].a), ["2", "5.00"]);
+        assert_eq!(report.rows[2].kind, RowDiffKind::MissingInA);
+        assert_eq!(values(&report.rows[2].b), ["2", "5.01"]);
+    }
+
+    #[test]
+    fn test_compare_null_differs_from_string() {
+        let mysql = mysql_result(vec![mysql_row(1, None)]);
+        let df = df_result(vec![1], vec![None]);
+        assert!(compare_results(&mysql, &df, unordered())
+            .unwrap()
+            .is_match());
+
+        let strings = |value: Option<&str>| DfQueryResult {
+            row_count: 1,
+            duration_ms: 0.0,
+            schema: Arc::new(Schema::new(vec![Field::new("s", DataType::Utf8, true)])),
+            batches: vec![RecordBatch::try_new(
+                Arc::new(Schema::new(vec![Field::new("s", DataType::Utf8, true)])),
+                vec![Arc::new(StringArray::from(vec![value]))],
+            )
+            .unwrap()],
+        };
+        let mut mysql = mysql_result(vec![vec![CellValue::Null]]);
+        mysql.columns = vec!["s".into()];
+        assert!(
+            !compare_results(&mysql, &strings(Some("NULL")), unordered())
+                .unwrap()
+                .is_match()
+        );
+        assert!(compare_results(&mysql, &strings(None), unordered())
+            .unwrap()
+            .is_match());
+    }
+
+    #[test]
+    fn test_checksum_matches_across_engines() {
+        let opts = ChecksumOptions::default();
+        let mysql = mysql_result(vec![mysql_row(1, Some("12.30")), mysql_row(2, None)]);
+        let df = df_result(vec![2, 1], vec![None, Some(1230)]);
+        assert_eq!(mysql.checksum(opts), df.checksum(opts).unwrap());
+
+        // One changed value changes the checksum
+        let changed = df_result(vec![2, 1], vec![None, Some(1231)]);
+        assert_ne!(mysql.checksum(opts), changed.checksum(opts).unwrap());
+
+        // Duplicates count, and NULL differs from the string "NULL"
+        let doubled = mysql_result(vec![
+            mysql_row(1, Some("12.30")),
+            mysql_row(1, Some("12.30")),
+            mysql_row(2, None),
+        ]);
+        assert_ne!(doubled.checksum(opts), mysql.checksum(opts));
+        let null_string = mysql_result(vec![
+            mysql_row(1, Some("12.30")),
+            mysql_row(2, Some("NULL")),
+        ]);
+        assert_ne!(null_string.checksum(opts), mysql.checksum(opts));
+
+        assert_eq!(mysql_result(vec![]).checksum(opts), 0);
+    }
+
+    #[test]
+    fn test_checksum_options() {
+        let typed = |col_type, values: &[&str]| {
+            let mut result = mysql_result(
+                values
+                    .iter()
+                    .map(|v| vec![CellValue::Str(v.to_string())])
+                    .collect(),
+            );
+            result.columns.truncate(1);
+            result.column_types = vec![col_type];
+            result
+        };
+        let strings = |values: &[&str]| typed(ColumnType::String, values);
+        let decimals = |values: &[&str]| typed(ColumnType::Decimal, values);
+        let opts = ChecksumOptions::default();
+        assert_eq!(
+            strings(&["ASIA  ", "EUROPE"]).checksum(opts),
+            strings(&["ASIA", "EUROPE\n"]).checksum(opts)
+        );
+        let exact = ChecksumOptions {
+            trim_trailing_whitespace: false,
+            ..opts
+        };
+        assert_ne!(
+            strings(&["ASIA  "]).checksum(exact),
+            strings(&["ASIA"]).checksum(exact)
+        );
+
+        // Text columns are not read as numbers
+        assert_ne!(
+            strings(&["007"]).checksum(opts),
+            strings(&["7"]).checksum(opts)
+        );
+        assert_eq!(
+            decimals(&["7.0"]).checksum(opts),
+            decimals(&["7"]).checksum(opts)
+        );
+        // Beyond the precision of a double
+        assert_ne!(
+            decimals(&["12345678901234567890.1"]).checksum(opts),
+            decimals(&["12345678901234567890.2"]).checksum(opts)
+        );
+
+        let coarse = ChecksumOptions {
+            float_decimals: 2,
+            ..opts
+        };
+        assert_eq!(
+            decimals(&["0.33333"]).checksum(coarse),
+            decimals(&["0.334"]).checksum(coarse)
+        );
+        assert_ne!(
+            decimals(&["0.33333"]).checksum(opts),
+            decimals(&["0.334"]).checksum(opts)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_checksum_ssb_query_on_both_engines() {
+        if std::env::var("FUSIONLAB_TEST_MYSQL").is_err() {
+            return;
+        }
+        use crate::{DataFusionRunner, MySQLConfig, MySQLRunner};
+
+        let mysql = MySQLRunner::new(&MySQLConfig::default()).unwrap();
+        mysql
+            .run_query("DROP TABLE IF EXISTS fusionlab_checksum_customer")
+            .await
+            .unwrap();
+        mysql
+            .run_query(
+                "CREATE TABLE fusionlab_checksum_customer \
+                 (c_custkey INT, c_nation VARCHAR(15), c_region VARCHAR(12), c_acctbal DOUBLE)",
+            )
+            .await
+            .unwrap();
+        mysql
+            .run_query(
+                "INSERT INTO fusionlab_checksum_customer VALUES \
+                 (1, 'CHINA', 'ASIA', 10.5), (2, 'JAPAN', 'ASIA', 0.1), \
+                 (3, 'FRANCE', 'EUROPE', NULL), (4, 'CHINA', 'ASIA', 7.25)",
+            )
+            .await
+            .unwrap();
+
+        // Copy the table into DataFusion, then run the same SSB-style query
+        let df = DataFusionRunner::new();
+        let table = mysql
+            .run_query("SELECT * FROM fusionlab_checksum_customer")
+            .await
+            .unwrap();
+        df.register_query_result("fusionlab_checksum_customer", &table)
+            .unwrap();
+
+        let sql = "SELECT c_region, c_nation, COUNT(*) AS customers, SUM(c_acctbal) AS balance \
+                   FROM fusionlab_checksum_customer GROUP BY c_region, c_nation";
+        let mysql_result = mysql.run_query(sql).await.unwrap();
+        let df_result = df.run_query_collect(sql).await.unwrap();
+        let opts = ChecksumOptions::default();
+        assert_eq!(
+            mysql_result.checksum(opts),
+            df_result.checksum(opts).unwrap()
+        );
+
+        mysql
+            .run_query("UPDATE fusionlab_checksum_customer SET c_acctbal = 7.5 WHERE c_custkey = 4")
+            .await
+            .unwrap();
+        let changed = mysql.run_query(sql).await.unwrap();
+        assert_ne!(changed.checksum(opts), df_result.checksum(opts).unwrap());
+
+        mysql
+            .run_query("DROP TABLE fusionlab_checksum_customer")
+            .await
+            .unwrap();
+        mysql.close().await;
+    }
+
+    #[test]
+    fn test_round_numbers() {
+        assert_eq!(round_decimal("12.30", 6).as_deref(), Some("12.3"));
+        assert_eq!(round_decimal("12.00", 6).as_deref(), Some("12"));
+        assert_eq!(round_decimal("-0.0000001", 6).as_deref(), Some("0"));
+        assert_eq!(round_decimal("0.3333335", 6).as_deref(), Some("0.333334"));
+        assert_eq!(round_decimal("-9.9999995", 6).as_deref(), Some("-10"));
+        assert_eq!(round_decimal(".5", 0).as_deref(), Some("1"));
+        assert_eq!(
+            round_decimal("9007199254740993.0000001", 6).as_deref(),
+            Some("9007199254740993")
+        );
+        assert_eq!(round_decimal("1e3", 6), None);
+        assert_eq!(round_decimal("-", 6), None);
+
+        assert_eq!(round_float("0.3333333333", 6).as_deref(), Some("0.333333"));
+        assert_eq!(round_float("-0.0000001", 6).as_deref(), Some("0"));
+        assert_eq!(round_float("1e3", 6).as_deref(), Some("1000"));
+    }
+
+    #[test]
+    fn test_normalize_temporal() {
+        assert_eq!(
+            normalize_temporal("2024-01-15T12:34:56.5").as_deref(),
+            Some("2024-01-15 12:34:56.5")
+        );
+        assert_eq!(
+            normalize_temporal("2024-01-15 12:34:56.500000").as_deref(),
+            Some("2024-01-15 12:34:56.5")
+        );
+        assert_eq!(
+            normalize_temporal("2024-01-15 12:34:56").as_deref(),
+            Some("2024-01-15 12:34:56")
+        );
+        assert_eq!(
+            normalize_temporal("-838:59:59.000000").as_deref(),
+            Some("-838:59:59")
+        );
+        assert_eq!(normalize_temporal("ASIA"), None);
+    }
+}
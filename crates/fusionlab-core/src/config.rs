@@ -0,0 +1,425 @@
+//! Settings loaded from a `fusionlab.toml` file
+//!
+//! Every field is optional so that the CLI can layer its settings: flags
+//! override the environment (`MySQLSection::from_env`), which overrides the
+//! file, which overrides the built-in defaults.
+//!
+//! ```toml
+//! [mysql]
+//! host = "db.internal"
+//! user = "bench"
+//! database = "ssb"
+//! ssl_mode = "required"
+//! pool_max = 32
+//!
+//! [datafusion]
+//! csv_dir = "data/ssb"
+//! batch_size = 16384
+//! target_partitions = 8
+//! ```
+
+use datafusion::prelude::SessionConfig;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+use crate::{FusionLabError, MySQLConfig, PoolConfig, Result, SslConfig, SslMode};
+
+/// File name looked up in the current directory
+pub const CONFIG_FILE_NAME: &str = "fusionlab.toml";
+
+/// Environment variable that supplies the MySQL password, taking precedence
+/// over `MYSQL_PWD` and the config file, so it need not be passed on the
+/// command line
+pub const PASSWORD_ENV: &str = "FUSIONLAB_MYSQL_PASSWORD";
+
+/// Contents of a config file
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    pub mysql: MySQLSection,
+    pub datafusion: DataFusionSection,
+    /// File the settings were read from, `None` when no file was found
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
+}
+
+/// `[mysql]` section, mirroring the fields of `MySQLConfig`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MySQLSection {
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub user: Option<String>,
+    /// May be left out and supplied through the environment instead
+    pub password: Option<String>,
+    pub database: Option<String>,
+    pub socket: Option<PathBuf>,
+    /// `disabled`, `preferred`, `required`, `verify-ca` or `verify-identity`;
+    /// defaults to `verify-identity` when `ssl_ca` or `ssl_cert` is set
+    pub ssl_mode: Option<SslMode>,
+    pub ssl_ca: Option<PathBuf>,
+    pub ssl_cert: Option<PathBuf>,
+    pub ssl_key: Option<PathBuf>,
+    pub connect_timeout_ms: Option<u64>,
+    pub connect_retries: Option<u32>,
+    pub pool_min: Option<usize>,
+    pub pool_max: Option<usize>,
+    pub stmt_cache_size: Option<usize>,
+    pub retry_on_disconnect: Option<bool>,
+    /// Connection character set, e.g. `latin1` for a latin1 dump
+    pub charset: Option<String>,
+    pub collation: Option<String>,
+    /// Use the compressed protocol, e.g. over a slow link
+    pub compress: Option<bool>,
+}
+
+/// `[datafusion]` section
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DataFusionSection {
+    /// Directory with the SSB CSV files, for `--source csv`
+    pub csv_dir: Option<PathBuf>,
+    /// Rows per record batch
+    pub batch_size: Option<usize>,
+    /// Partitions used to parallelize scans, joins and aggregations
+    pub target_partitions: Option<usize>,
+}
+
+impl ConfigFile {
+    /// Read and parse `path`; a missing file is an error
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            FusionLabError::Config(format!("cannot read {}: {}", path.display(), e))
+        })?;
+        let mut config = Self::parse(&text)
+            .map_err(|e| FusionLabError::Config(format!("{}: {}", path.display(), e)))?;
+        config.source = Some(path.to_path_buf());
+        Ok(config)
+    }
+
+    /// Load the first existing file of `search_paths()`, or the defaults if
+    /// there is none
+    pub fn discover() -> Result<Self> {
+        match Self::search_paths().into_iter().find(|p| p.is_file()) {
+            Some(path) => Self::load(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// `./fusionlab.toml`, then `$XDG_CONFIG_HOME/fusionlab/config.toml`
+    /// (`~/.config` when `XDG_CONFIG_HOME` is unset)
+    pub fn search_paths() -> Vec<PathBuf> {
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")));
+
+        let mut paths = vec![PathBuf::from(CONFIG_FILE_NAME)];
+        if let Some(dir) = config_home {
+            paths.push(dir.join("fusionlab").join("config.toml"));
+        }
+        paths
+    }
+
+    fn parse(text: &str) -> std::result::Result<Self, toml::de::Error> {
+        toml::from_str(text)
+    }
+}
+
+impl MySQLSection {
+    /// Connection settings from the environment, using the `mysql` client's
+    /// variables: `MYSQL_HOST`, `MYSQL_TCP_PORT`, `MYSQL_UNIX_PORT` (socket),
+    /// `MYSQL_PWD`, plus `MYSQL_USER` and `MYSQL_DATABASE`
+    ///
+    /// `FUSIONLAB_MYSQL_PASSWORD` takes precedence over `MYSQL_PWD`. Empty
+    /// variables count as unset.
+    pub fn from_env() -> Result<Self> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let var = |name: &str| var(name).filter(|value| !value.is_empty());
+        let port = var("MYSQL_TCP_PORT")
+            .map(|port| {
+                port.parse().map_err(|_| {
+                    FusionLabError::Config(format!("MYSQL_TCP_PORT: invalid port {:?}", port))
+                })
+            })
+            .transpose()?;
+        Ok(MySQLSection {
+            host: var("MYSQL_HOST"),
+            port,
+            user: var("MYSQL_USER"),
+            password: var(PASSWORD_ENV).or_else(|| var("MYSQL_PWD")),
+            database: var("MYSQL_DATABASE"),
+            socket: var("MYSQL_UNIX_PORT").map(PathBuf::from),
+            ..MySQLSection::default()
+        })
+    }
+
+    /// Fill the fields missing here from `fallback`
+    ///
+    /// A host set here also keeps the socket of `fallback` out, since a
+    /// socket wins over the host when connecting: `--host` must not end up
+    /// on the socket named by `MYSQL_UNIX_PORT`.
+    pub fn or(self, fallback: MySQLSection) -> MySQLSection {
+        let fallback_socket = match self.host {
+            Some(_) => None,
+            None => fallback.socket,
+        };
+        MySQLSection {
+            host: self.host.or(fallback.host),
+            port: self.port.or(fallback.port),
+            user: self.user.or(fallback.user),
+            password: self.password.or(fallback.password),
+            database: self.database.or(fallback.database),
+            socket: self.socket.or(fallback_socket),
+            ssl_mode: self.ssl_mode.or(fallback.ssl_mode),
+            ssl_ca: self.ssl_ca.or(fallback.ssl_ca),
+            ssl_cert: self.ssl_cert.or(fallback.ssl_cert),
+            ssl_key: self.ssl_key.or(fallback.ssl_key),
+            connect_timeout_ms: self.connect_timeout_ms.or(fallback.connect_timeout_ms),
+            connect_retries: self.connect_retries.or(fallback.connect_retries),
+            pool_min: self.pool_min.or(fallback.pool_min),
+            pool_max: self.pool_max.or(fallback.pool_max),
+            stmt_cache_size: self.stmt_cache_size.or(fallback.stmt_cache_size),
+            retry_on_disconnect: self.retry_on_disconnect.or(fallback.retry_on_disconnect),
+            charset: self.charset.or(fallback.charset),
+            collation: self.collation.or(fallback.collation),
+            compress: self.compress.or(fallback.compress),
+        }
+    }
+
+    /// Build a `MySQLConfig`, taking unset fields from `MySQLConfig::default()`
+    pub fn to_config(&self) -> MySQLConfig {
+        self.apply(MySQLConfig::default())
+    }
+
+    /// Override the fields of `base` that are set here
+    ///
+    /// TLS settings replace those of `base` as a whole when any `ssl_*`
+    /// field is set.
+    pub fn apply(&self, base: MySQLConfig) -> MySQLConfig {
+        let has_certs = self.ssl_ca.is_some() || self.ssl_cert.is_some();
+        let ssl = match self.ssl_mode {
+            None if !has_certs && self.ssl_key.is_none() => base.ssl,
+            Some(SslMode::Disabled) => None,
+            mode => Some(SslConfig {
+                mode: mode.unwrap_or(SslMode::VerifyIdentity),
+                ca_cert_path: self.ssl_ca.clone(),
+                client_cert_path: self.ssl_cert.clone(),
+                client_key_path: self.ssl_key.clone(),
+                accept_invalid_certs: false,
+            }),
+        };
+
+        MySQLConfig {
+            host: self.host.clone().unwrap_or(base.host),
+            port: self.port.unwrap_or(base.port),
+            user: self.user.clone().unwrap_or(base.user),
+            password: self.password.clone().or(base.password),
+            database: self.database.clone().unwrap_or(base.database),
+            socket: self.socket.clone().or(base.socket),
+            ssl,
+            connect_timeout_ms: self.connect_timeout_ms.or(base.connect_timeout_ms),
+            connect_retries: self.connect_retries.unwrap_or(base.connect_retries),
+            pool: PoolConfig {
+                min: self.pool_min.unwrap_or(base.pool.min),
+                max: self.pool_max.unwrap_or(base.pool.max),
+                ..base.pool
+            },
+            stmt_cache_size: self.stmt_cache_size.unwrap_or(base.stmt_cache_size),
+            retry_on_disconnect: self.retry_on_disconnect.unwrap_or(base.retry_on_disconnect),
+            charset: self.charset.clone().or(base.charset),
+            collation: self.collation.clone().or(base.collation),
+            compress: self.compress.unwrap_or(base.compress),
+        }
+    }
+}
+
+impl MySQLConfig {
+    /// Connection settings from the environment (see
+    /// `MySQLSection::from_env`), defaults for the rest
+    pub fn from_env() -> Result<Self> {
+        Ok(MySQLSection::from_env()?.to_config())
+    }
+
+    /// The `[mysql]` section of the config file at `path`, defaults for the
+    /// rest
+    pub fn from_toml(path: &Path) -> Result<Self> {
+        Ok(ConfigFile::load(path)?.mysql.to_config())
+    }
+}
+
+impl DataFusionSection {
+    /// Session settings for `DataFusionRunner::with_config`
+    pub fn session_config(&self) -> SessionConfig {
+        let mut config = SessionConfig::new();
+        if let Some(batch_size) = self.batch_size {
+            config = config.with_batch_size(batch_size);
+        }
+        if let Some(partitions) = self.target_partitions {
+            config = config.with_target_partitions(partitions);
+        }
+        config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sections() {
+        let config = ConfigFile::parse(
+            r#"
+            [mysql]
+            host = "db.internal"
+            port = 3307
+            password = "from-file"
+            ssl_mode = "verify-ca"
+            ssl_ca = "/etc/ca.pem"
+            pool_max = 32
+
+            [datafusion]
+            csv_dir = "data/ssb"
+            batch_size = 1024
+            target_partitions = 3
+            "#,
+        )
+        .unwrap();
+
+        let mysql = config.mysql.to_config();
+        assert_eq!(mysql.host, "db.internal");
+        assert_eq!(mysql.port, 3307);
+        assert_eq!(mysql.user, MySQLConfig::default().user);
+        assert_eq!(mysql.password.as_deref(), Some("from-file"));
+        assert_eq!(mysql.pool.max, 32);
+        assert_eq!(mysql.pool.min, PoolConfig::default().min);
+        let ssl = mysql.ssl.unwrap();
+        assert_eq!(ssl.mode, SslMode::VerifyCa);
+        assert_eq!(ssl.ca_cert_path, Some(PathBuf::from("/etc/ca.pem")));
+
+        assert_eq!(config.datafusion.csv_dir, Some(PathBuf::from("data/ssb")));
+        let session = config.datafusion.session_config();
+        assert_eq!(session.batch_size(), 1024);
+        assert_eq!(session.target_partitions(), 3);
+
+        let empty = ConfigFile::parse("").unwrap();
+        assert_eq!(empty.mysql.to_config().host, MySQLConfig::default().host);
+        assert!(empty.mysql.to_config().ssl.is_none());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = ConfigFile::parse("[mysql]\nhots = \"x\"\n").unwrap_err();
+        assert!(err.to_string().contains("unknown field `hots`"));
+        assert!(ConfigFile::parse("[mysql]\nport = \"abc\"\n").is_err());
+        assert!(ConfigFile::parse("[mysql]\nssl_mode = \"on\"\n").is_err());
+
+        let err = ConfigFile::load(Path::new("/nonexistent/fusionlab.toml")).unwrap_err();
+        assert!(matches!(err, FusionLabError::Config(_)));
+    }
+
+    fn env_section(vars: &[(&str, &str)]) -> Result<MySQLSection> {
+        let vars: std::collections::HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        MySQLSection::from_vars(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn test_precedence() {
+        let file = ConfigFile::parse(
+            "[mysql]\nhost = \"file-host\"\nuser = \"file-user\"\npassword = \"file\"\n",
+        )
+        .unwrap();
+        let env = env_section(&[
+            ("MYSQL_HOST", "env-host"),
+            ("MYSQL_TCP_PORT", "3311"),
+            ("MYSQL_PWD", "mysql-pwd"),
+            (PASSWORD_ENV, "env"),
+        ])
+        .unwrap();
+        assert_eq!(env.password.as_deref(), Some("env"));
+
+        let flags = MySQLSection {
+            port: Some(3310),
+            ..MySQLSection::default()
+        };
+        let config = flags.or(env).or(file.mysql).to_config();
+        assert_eq!(config.host, "env-host");
+        assert_eq!(config.port, 3310);
+        assert_eq!(config.user, "file-user");
+        assert_eq!(config.password.as_deref(), Some("env"));
+        assert_eq!(config.database, MySQLConfig::default().database);
+
+        // MYSQL_UNIX_PORT applies only when no host is given
+        let env = || env_section(&[("MYSQL_UNIX_PORT", "/run/mysqld/mysqld.sock")]).unwrap();
+        let host = MySQLSection {
+            host: Some("db.internal".to_string()),
+            ..MySQLSection::default()
+        };
+        let config = host.or(env()).to_config();
+        assert_eq!(config.host, "db.internal");
+        assert_eq!(config.socket, None);
+        let socket = MySQLSection {
+            socket: Some(PathBuf::from("/tmp/mysql.sock")),
+            ..MySQLSection::default()
+        };
+        assert_eq!(
+            socket.or(env()).to_config().socket,
+            Some(PathBuf::from("/tmp/mysql.sock"))
+        );
+        assert_eq!(
+            MySQLSection::default().or(env()).to_config().socket,
+            Some(PathBuf::from("/run/mysqld/mysqld.sock"))
+        );
+    }
+
+    #[test]
+    fn test_from_env() {
+        let section = env_section(&[
+            ("MYSQL_USER", "bench"),
+            ("MYSQL_PWD", "secret"),
+            ("MYSQL_DATABASE", ""),
+            ("MYSQL_UNIX_PORT", "/run/mysqld/mysqld.sock"),
+        ])
+        .unwrap();
+        assert_eq!(section.user.as_deref(), Some("bench"));
+        assert_eq!(section.password.as_deref(), Some("secret"));
+        assert_eq!(section.database, None);
+        assert_eq!(
+            section.socket,
+            Some(PathBuf::from("/run/mysqld/mysqld.sock"))
+        );
+        assert_eq!(section.host, None);
+
+        let err = env_section(&[("MYSQL_TCP_PORT", "33o6")]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Config error: MYSQL_TCP_PORT: invalid port \"33o6\""
+        );
+        assert!(env_section(&[]).unwrap().password.is_none());
+    }
+
+    #[test]
+    fn test_from_toml() {
+        let path =
+            std::env::temp_dir().join(format!("fusionlab_test_{}_config.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            "[mysql]\nhost = \"db.internal\"\ndatabase = \"tpch\"\n",
+        )
+        .unwrap();
+        let config = MySQLConfig::from_toml(&path);
+        std::fs::remove_file(&path).ok();
+
+        let config = config.unwrap();
+        assert_eq!(config.host, "db.internal");
+        assert_eq!(config.database, "tpch");
+        assert_eq!(config.port, MySQLConfig::default().port);
+        assert!(MySQLConfig::from_toml(Path::new("/nonexistent/fusionlab.toml")).is_err());
+    }
+}
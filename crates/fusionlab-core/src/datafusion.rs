@@ -5,17 +5,35 @@
 use datafusion::arrow::array::{
     ArrayRef, Float64Array, Int32Array, Int64Array, StringArray,
 };
-use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::csv::Writer as CsvWriter;
+use datafusion::arrow::csv::WriterBuilder as CsvWriterBuilder;
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use datafusion::arrow::error::ArrowError;
+use datafusion::arrow::ipc::writer::FileWriter;
+use datafusion::arrow::json::writer::JsonArray;
+use datafusion::arrow::json::WriterBuilder;
 use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::arrow::util::display::{ArrayFormatter, FormatOptions};
 use datafusion::arrow::util::pretty::pretty_format_batches;
+use datafusion::datasource::MemTable;
+use datafusion::error::DataFusionError;
 use datafusion::prelude::*;
+pub use datafusion::prelude::{CsvReadOptions, NdJsonReadOptions};
+use datafusion::sql::sqlparser::ast::Statement;
+use datafusion::sql::sqlparser::dialect::GenericDialect;
+use datafusion::sql::sqlparser::parser::Parser;
 use futures::StreamExt;
-use std::path::Path;
+use serde::ser::{Error as _, Serialize, SerializeStruct, Serializer};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use crate::ibd_provider::IbdTableProvider;
-use crate::FusionLabError;
+use crate::table::format_table_with_kinds;
+use crate::{ColumnKind, FusionLabError, MySQLRunner, QueryResult, QueryStats, TableFormatOptions};
 
 /// Result of running a DataFusion query
 #[derive(Debug)]
@@ -26,9 +44,52 @@ pub struct DfQueryResult {
     pub duration_ms: f64,
     /// Record batches (Arrow format)
     pub batches: Vec<RecordBatch>,
+    /// Output schema (available even when no batches were produced)
+    pub schema: SchemaRef,
 }
 
 impl DfQueryResult {
+    /// Column names, in schema order
+    pub fn columns(&self) -> Vec<String> {
+        self.schema
+            .fields()
+            .iter()
+            .map(|f| f.name().clone())
+            .collect()
+    }
+
+    /// Rows of all batches formatted as display strings (NULL as `NULL`),
+    /// in the shape of `QueryResult::to_display_rows`
+    ///
+    /// Columns of a type Arrow cannot display are shown as `<type>`.
+    pub fn rows(&self) -> impl Iterator<Item = Vec<String>> + '_ {
+        self.rows_with_null("NULL")
+    }
+
+    /// Rows formatted as display strings, with SQL NULL shown as `marker`
+    pub fn rows_with_null<'a>(&'a self, marker: &'a str) -> impl Iterator<Item = Vec<String>> + 'a {
+        self.batches.iter().flat_map(move |batch| {
+            let options = FormatOptions::new().with_null(marker);
+            let formatters: Vec<_> = batch
+                .columns()
+                .iter()
+                .map(|array| {
+                    ArrayFormatter::try_new(array.as_ref(), &options)
+                        .map_err(|_| format!("<{}>", array.data_type()))
+                })
+                .collect();
+            (0..batch.num_rows()).map(move |i| {
+                formatters
+                    .iter()
+                    .map(|formatter| match formatter {
+                        Ok(formatter) => formatter.value(i).to_string(),
+                        Err(placeholder) => placeholder.clone(),
+                    })
+                    .collect()
+            })
+        })
+    }
+
     /// Format results as a pretty table
     pub fn to_table(&self) -> String {
         if self.batches.is_empty() {
@@ -38,6 +99,371 @@ impl DfQueryResult {
             .map(|t| t.to_string())
             .unwrap_or_else(|e| format!("Error formatting: {}", e))
     }
+
+    /// Format results as a table with SQL NULL shown as `null_marker`,
+    /// cutting cells to the widths in `options`
+    ///
+    /// Integer, float and decimal columns are right-aligned.
+    pub fn to_table_with_options(&self, null_marker: &str, options: &TableFormatOptions) -> String {
+        if self.batches.is_empty() {
+            return "Empty result".to_string();
+        }
+        let kinds: Vec<ColumnKind> = self
+            .schema
+            .fields()
+            .iter()
+            .map(|field| match field.data_type() {
+                t if t.is_integer() => ColumnKind::Integer,
+                t if t.is_numeric() => ColumnKind::Number,
+                _ => ColumnKind::Text,
+            })
+            .collect();
+        let rows: Vec<Vec<String>> = self.rows_with_null(null_marker).collect();
+        format_table_with_kinds(&self.columns(), &rows, &kinds, options)
+    }
+
+    /// Serialize all batches as RFC 4180 CSV with a header row
+    ///
+    /// Fields containing delimiters, quotes or newlines are quoted; NULLs are
+    /// written as empty fields.
+    pub fn to_csv(&self) -> Result<String, FusionLabError> {
+        self.to_csv_with_null("")
+    }
+
+    /// Serialize all batches as CSV like `to_csv`, writing NULLs as `marker`
+    /// (e.g. `\N` for `LOAD DATA INFILE`)
+    pub fn to_csv_with_null(&self, marker: &str) -> Result<String, FusionLabError> {
+        let mut buf = Vec::new();
+        {
+            let mut writer = CsvWriterBuilder::new()
+                .with_header(true)
+                .with_null(marker.to_string())
+                .build(&mut buf);
+            if self.batches.is_empty() {
+                // Still emit the header for empty results
+                writer
+                    .write(&RecordBatch::new_empty(self.schema.clone()))
+                    .map_err(|e| FusionLabError::DataFusion(e.to_string()))?;
+            }
+            for batch in &self.batches {
+                writer
+                    .write(batch)
+                    .map_err(|e| FusionLabError::DataFusion(e.to_string()))?;
+            }
+        }
+        String::from_utf8(buf).map_err(|e| FusionLabError::DataFusion(e.to_string()))
+    }
+
+    /// Serialize all batches as a JSON array of objects keyed by column name
+    ///
+    /// NULLs are written as JSON `null`; an empty result produces `[]`. A
+    /// repeated column name keeps only one value per object; the rows of
+    /// `to_json_document` keep them all.
+    pub fn to_json(&self) -> Result<String, FusionLabError> {
+        let mut buf = Vec::new();
+        let mut writer = WriterBuilder::new()
+            .with_explicit_nulls(true)
+            .build::<_, JsonArray>(&mut buf);
+        for batch in &self.batches {
+            writer
+                .write(batch)
+                .map_err(|e| FusionLabError::DataFusion(e.to_string()))?;
+        }
+        writer
+            .finish()
+            .map_err(|e| FusionLabError::DataFusion(e.to_string()))?;
+        String::from_utf8(buf).map_err(|e| FusionLabError::DataFusion(e.to_string()))
+    }
+
+    /// Serialize the result as one JSON document with `columns`, `row_count`,
+    /// `duration_ms` and `rows`
+    ///
+    /// Each row is an array parallel to `columns`, as in
+    /// `QueryResult::to_json_document`, with numbers kept as numbers.
+    pub fn to_json_document(&self) -> Result<String, FusionLabError> {
+        serde_json::to_string(self).map_err(|e| FusionLabError::DataFusion(e.to_string()))
+    }
+
+    /// Write all batches as an Arrow IPC file (Feather v2), which keeps the
+    /// column types exactly
+    ///
+    /// Batches are written under `schema`; an empty result produces a file
+    /// with the schema and no batches.
+    pub fn to_ipc(&self, writer: impl Write) -> Result<(), FusionLabError> {
+        let mut writer = FileWriter::try_new(writer, &self.schema)
+            .map_err(|e| FusionLabError::DataFusion(e.to_string()))?;
+        for batch in &self.batches {
+            let batch = RecordBatch::try_new(self.schema.clone(), batch.columns().to_vec())
+                .map_err(|e| FusionLabError::DataFusion(e.to_string()))?;
+            writer
+                .write(&batch)
+                .map_err(|e| FusionLabError::DataFusion(e.to_string()))?;
+        }
+        writer
+            .finish()
+            .map_err(|e| FusionLabError::DataFusion(e.to_string()))
+    }
+}
+
+impl Serialize for DfQueryResult {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let columns: Vec<&str> = self
+            .schema
+            .fields()
+            .iter()
+            .map(|f| f.name().as_str())
+            .collect();
+        let mut rows = Vec::with_capacity(self.row_count);
+        for batch in &self.batches {
+            rows.extend(json_rows(batch).map_err(S::Error::custom)?);
+        }
+
+        let mut doc = serializer.serialize_struct("DfQueryResult", 4)?;
+        doc.serialize_field("columns", &columns)?;
+        doc.serialize_field("row_count", &self.row_count)?;
+        doc.serialize_field("duration_ms", &self.duration_ms)?;
+        doc.serialize_field("rows", &rows)?;
+        doc.end()
+    }
+}
+
+/// Rows of `batch` as JSON arrays parallel to its columns
+///
+/// The arrow writer only produces objects, so the columns are renamed to
+/// their positions first; otherwise repeated names would collapse.
+fn json_rows(batch: &RecordBatch) -> Result<Vec<Vec<serde_json::Value>>, FusionLabError> {
+    let fields: Vec<Field> = batch
+        .schema()
+        .fields()
+        .iter()
+        .enumerate()
+        .map(|(i, f)| f.as_ref().clone().with_name(i.to_string()))
+        .collect();
+    let positional = RecordBatch::try_new(Arc::new(Schema::new(fields)), batch.columns().to_vec())
+        .map_err(|e| FusionLabError::DataFusion(e.to_string()))?;
+
+    let mut buf = Vec::new();
+    let mut writer = WriterBuilder::new()
+        .with_explicit_nulls(true)
+        .build::<_, JsonArray>(&mut buf);
+    writer
+        .write(&positional)
+        .map_err(|e| FusionLabError::DataFusion(e.to_string()))?;
+    writer
+        .finish()
+        .map_err(|e| FusionLabError::DataFusion(e.to_string()))?;
+    let objects: Vec<serde_json::Map<String, serde_json::Value>> =
+        serde_json::from_slice(&buf).map_err(|e| FusionLabError::DataFusion(e.to_string()))?;
+
+    Ok(objects
+        .into_iter()
+        .map(|mut object| {
+            (0..batch.num_columns())
+                .map(|i| object.remove(&i.to_string()).unwrap_or_default())
+                .collect()
+        })
+        .collect())
+}
+
+/// Format of a `BatchWriter`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchFormat {
+    /// As `DfQueryResult::to_csv`
+    Csv,
+    /// As `DfQueryResult::to_json_document`, but with `rows` before
+    /// `row_count` and `duration_ms`, which are only known at the end
+    Json,
+    /// As `DfQueryResult::to_ipc`, under the schema of the first batch
+    Arrow,
+}
+
+/// Serializes batches as they arrive, so results need not fit in memory
+///
+/// Feed it from `DataFusionRunner::run_query_stream_with` and pass the
+/// returned result to `finish`. Output starts with the first batch, whose
+/// schema provides the CSV header and the JSON `columns`.
+pub struct BatchWriter<W: Write> {
+    format: BatchFormat,
+    csv_null: String,
+    state: Option<EncoderState<W>>,
+}
+
+enum EncoderState<W: Write> {
+    /// Nothing written yet
+    Pending(W),
+    Csv(Box<CsvWriter<W>>),
+    Arrow(Box<FileWriter<W>>),
+    Json {
+        out: W,
+        first_row: bool,
+    },
+}
+
+impl<W: Write> BatchWriter<W> {
+    pub fn new(out: W, format: BatchFormat) -> Self {
+        Self {
+            format,
+            csv_null: String::new(),
+            state: Some(EncoderState::Pending(out)),
+        }
+    }
+
+    /// Write CSV NULLs as `marker` instead of empty fields, as
+    /// `DfQueryResult::to_csv_with_null` does
+    pub fn with_csv_null(mut self, marker: &str) -> Self {
+        self.csv_null = marker.to_string();
+        self
+    }
+
+    /// Append the rows of `batch`
+    pub fn write(&mut self, batch: &RecordBatch) -> Result<(), FusionLabError> {
+        self.start(&batch.schema())?;
+        match self.state.as_mut().expect("state is restored after start") {
+            EncoderState::Pending(_) => unreachable!("started above"),
+            EncoderState::Csv(writer) => writer
+                .write(batch)
+                .map_err(|e| FusionLabError::DataFusion(e.to_string())),
+            EncoderState::Arrow(writer) => writer
+                .write(batch)
+                .map_err(|e| FusionLabError::DataFusion(e.to_string())),
+            EncoderState::Json { out, first_row } => {
+                for row in json_rows(batch)? {
+                    if !*first_row {
+                        out.write_all(b",")?;
+                    }
+                    *first_row = false;
+                    serde_json::to_writer(&mut *out, &row)
+                        .map_err(|e| FusionLabError::DataFusion(e.to_string()))?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Complete the output for `result`, the summary returned by the
+    /// streaming query, and return the underlying writer
+    pub fn finish(mut self, result: &DfQueryResult) -> Result<W, FusionLabError> {
+        let empty = matches!(self.state, Some(EncoderState::Pending(_)));
+        self.start(&result.schema)?;
+        match self.state.take().expect("state is restored after start") {
+            EncoderState::Pending(_) => unreachable!("started above"),
+            EncoderState::Csv(mut writer) => {
+                if empty {
+                    // Still emit the header for empty results
+                    writer
+                        .write(&RecordBatch::new_empty(result.schema.clone()))
+                        .map_err(|e| FusionLabError::DataFusion(e.to_string()))?;
+                }
+                Ok(writer.into_inner())
+            }
+            EncoderState::Arrow(mut writer) => {
+                writer
+                    .finish()
+                    .map_err(|e| FusionLabError::DataFusion(e.to_string()))?;
+                writer
+                    .into_inner()
+                    .map_err(|e| FusionLabError::DataFusion(e.to_string()))
+            }
+            EncoderState::Json { mut out, .. } => {
+                let duration = serde_json::to_string(&result.duration_ms)
+                    .map_err(|e| FusionLabError::DataFusion(e.to_string()))?;
+                write!(
+                    out,
+                    "],\"row_count\":{},\"duration_ms\":{}}}",
+                    result.row_count, duration
+                )?;
+                Ok(out)
+            }
+        }
+    }
+
+    /// Write the header on first use
+    fn start(&mut self, schema: &SchemaRef) -> Result<(), FusionLabError> {
+        let state = match self.state.take() {
+            Some(EncoderState::Pending(mut out)) => match self.format {
+                BatchFormat::Csv => EncoderState::Csv(Box::new(
+                    CsvWriterBuilder::new()
+                        .with_header(true)
+                        .with_null(self.csv_null.clone())
+                        .build(out),
+                )),
+                BatchFormat::Arrow => EncoderState::Arrow(Box::new(
+                    FileWriter::try_new(out, schema)
+                        .map_err(|e| FusionLabError::DataFusion(e.to_string()))?,
+                )),
+                BatchFormat::Json => {
+                    let columns: Vec<&str> =
+                        schema.fields().iter().map(|f| f.name().as_str()).collect();
+                    let columns = serde_json::to_string(&columns)
+                        .map_err(|e| FusionLabError::DataFusion(e.to_string()))?;
+                    write!(out, "{{\"columns\":{},\"rows\":[", columns)?;
+                    EncoderState::Json {
+                        out,
+                        first_row: true,
+                    }
+                }
+            },
+            Some(state) => state,
+            None => unreachable!("state is only taken by finish"),
+        };
+        self.state = Some(state);
+        Ok(())
+    }
+}
+
+/// Format of CSV files for `DataFusionRunner::register_csv_with_options`
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+    pub delimiter: u8,
+    /// The first line holds column names
+    pub has_header: bool,
+    pub quote: u8,
+    /// Column names and types; inferred from the data when `None`
+    pub schema: Option<SchemaRef>,
+    /// Extension of the files read from a directory; a single file is read
+    /// whatever its name
+    pub file_extension: String,
+    /// Field values read as NULL, e.g. `\N` or `NULL`
+    pub null_markers: Vec<String>,
+    /// Every line ends in a delimiter, as in dbgen's `.tbl` files; the empty
+    /// field after it is dropped
+    pub trailing_delimiter: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            has_header: true,
+            quote: b'"',
+            schema: None,
+            file_extension: ".csv".to_string(),
+            null_markers: Vec::new(),
+            trailing_delimiter: false,
+        }
+    }
+}
+
+impl CsvOptions {
+    /// The `.tbl` format of SSB dbgen: no header, `|` after every field,
+    /// including the last
+    pub fn dbgen() -> Self {
+        Self {
+            delimiter: b'|',
+            has_header: false,
+            file_extension: ".tbl".to_string(),
+            trailing_delimiter: true,
+            ..Self::default()
+        }
+    }
+
+    /// Same options with an explicit schema
+    pub fn with_schema(self, schema: SchemaRef) -> Self {
+        Self {
+            schema: Some(schema),
+            ..self
+        }
+    }
 }
 
 /// DataFusion query runner with in-memory data support
@@ -52,6 +478,13 @@ impl DataFusionRunner {
         Self { ctx }
     }
 
+    /// Create a runner whose context uses `config`, e.g. to set the batch
+    /// size or the number of target partitions
+    pub fn with_config(config: SessionConfig) -> Self {
+        let ctx = SessionContext::new_with_config(config);
+        Self { ctx }
+    }
+
     /// Get a reference to the session context
     pub fn context(&self) -> &SessionContext {
         &self.ctx
@@ -63,18 +496,117 @@ impl DataFusionRunner {
     }
 
     /// Register a CSV file as a table
-    pub async fn register_csv(
+    pub async fn register_csv(&self, table_name: &str, path: &str) -> Result<(), FusionLabError> {
+        self.register_csv_with_options(table_name, path, CsvOptions::default())
+            .await
+    }
+
+    /// Register a CSV file (or directory of files) as a table with explicit
+    /// format options, e.g. `CsvOptions::dbgen()` with `ssb_schemas()` for
+    /// dbgen output
+    pub async fn register_csv_with_options(
+        &self,
+        table_name: &str,
+        path: &str,
+        options: CsvOptions,
+    ) -> Result<(), FusionLabError> {
+        let extension = single_file_extension(path).unwrap_or(options.file_extension);
+        // The field after a trailing delimiter needs a column of its own
+        let schema = options.schema.map(|schema| {
+            let mut fields: Vec<Field> =
+                schema.fields().iter().map(|f| f.as_ref().clone()).collect();
+            if options.trailing_delimiter {
+                fields.push(Field::new("__trailing", DataType::Utf8, true));
+            }
+            Schema::new(fields)
+        });
+        let mut read_options = CsvReadOptions::new()
+            .delimiter(options.delimiter)
+            .has_header(options.has_header)
+            .quote(options.quote)
+            .file_extension(&extension);
+        if let Some(schema) = &schema {
+            read_options = read_options.schema(schema);
+        }
+        if !options.null_markers.is_empty() {
+            read_options = read_options.null_regex(Some(null_regex(&options.null_markers)));
+        }
+
+        let to_error = |e: DataFusionError| FusionLabError::DataFusion(e.to_string());
+        if !options.trailing_delimiter {
+            self.ctx
+                .register_csv(table_name, path, read_options)
+                .await
+                .map_err(to_error)?;
+            return Ok(());
+        }
+        let df = self
+            .ctx
+            .read_csv(path, read_options)
+            .await
+            .map_err(to_error)?;
+        let fields = df.schema().fields();
+        let columns: Vec<String> = fields[..fields.len().saturating_sub(1)]
+            .iter()
+            .map(|f| f.name().clone())
+            .collect();
+        let columns: Vec<&str> = columns.iter().map(String::as_str).collect();
+        let view = df.select_columns(&columns).map_err(to_error)?.into_view();
+        self.ctx
+            .register_table(table_name, view)
+            .map_err(to_error)?;
+        Ok(())
+    }
+
+    /// Register a Parquet file (or directory of files) as a table
+    pub async fn register_parquet(
         &self,
         table_name: &str,
         path: &str,
     ) -> Result<(), FusionLabError> {
         self.ctx
-            .register_csv(table_name, path, CsvReadOptions::default())
+            .register_parquet(table_name, path, ParquetReadOptions::default())
             .await
             .map_err(|e| FusionLabError::DataFusion(e.to_string()))?;
         Ok(())
     }
 
+    /// Register a newline-delimited JSON file (or directory of `.json`
+    /// files) as a table; the schema is inferred from the records
+    pub async fn register_json(&self, table_name: &str, path: &str) -> Result<(), FusionLabError> {
+        self.register_json_with_options(table_name, path, NdJsonReadOptions::default())
+            .await
+    }
+
+    /// Register a newline-delimited JSON file (or directory) as a table with
+    /// explicit read options, e.g. how many records schema inference reads
+    /// (`schema_infer_max_records`) or a fixed schema (`schema`)
+    ///
+    /// A malformed line among the records read for inference fails with
+    /// `FusionLabError::Parse` naming its file and line. Later malformed
+    /// lines, or any with an explicit schema, fail when queried.
+    pub async fn register_json_with_options(
+        &self,
+        table_name: &str,
+        path: &str,
+        options: NdJsonReadOptions<'_>,
+    ) -> Result<(), FusionLabError> {
+        let extension = single_file_extension(path);
+        let options = match &extension {
+            Some(extension) => NdJsonReadOptions {
+                file_extension: extension,
+                ..options
+            },
+            None => options,
+        };
+        let file_extension = options.file_extension.to_string();
+        self.ctx
+            .register_json(table_name, path, options)
+            .await
+            .map_err(|e| json_read_error(Path::new(path), &file_extension, e))?;
+        Ok(())
+    }
+
     /// Register an in-memory RecordBatch as a table
     pub fn register_batch(
         &self,
@@ -120,60 +652,177 @@ impl DataFusionRunner {
         Ok(())
     }
 
-    /// Register the SSB sample data for testing
-    /// Creates small in-memory versions of SSB tables
-    pub fn register_ssb_sample(&self) -> Result<(), FusionLabError> {
-        // Sample lineorder data
-        let lineorder = create_sample_lineorder()?;
-        self.register_batch("lineorder", lineorder)?;
-
-        // Sample customer data
-        let customer = create_sample_customer()?;
-        self.register_batch("customer", customer)?;
-
-        // Sample supplier data
-        let supplier = create_sample_supplier()?;
-        self.register_batch("supplier", supplier)?;
-
-        // Sample part data
-        let part = create_sample_part()?;
-        self.register_batch("part", part)?;
+    /// Register the partition files of one InnoDB table (`t#p#p0.ibd`,
+    /// `t#p#p1.ibd`, ...) as a single table
+    ///
+    /// All files are read with the table's SDI and must share its schema.
+    /// Each file is scanned as a separate DataFusion partition.
+    pub fn register_ibd_partitioned(
+        &self,
+        table_name: &str,
+        ibd_paths: &[PathBuf],
+        sdi_path: &Path,
+    ) -> Result<(), FusionLabError> {
+        let provider = IbdTableProvider::try_new_partitioned(ibd_paths, sdi_path)
+            .map_err(|e| FusionLabError::IbdReader(e.to_string()))?;
 
-        // Sample date data
-        let date = create_sample_date()?;
-        self.register_batch("date", date)?;
+        self.ctx
+            .register_table(table_name, Arc::new(provider))
+            .map_err(|e| FusionLabError::DataFusion(e.to_string()))?;
 
         Ok(())
     }
 
-    /// Run a query using collect() - gets all results at once
-    pub async fn run_query_collect(&self, sql: &str) -> Result<DfQueryResult, FusionLabError> {
-        let start = Instant::now();
-
-        let df = self
+    /// Count the rows of a table registered with one of the `register_ibd`
+    /// methods, without reading any column values
+    ///
+    /// The count is also stored as the table's exact row count statistic, so
+    /// a following `SELECT COUNT(*) FROM <table>` without filters, grouping
+    /// or joins is answered from statistics instead of a scan. Queries with a
+    /// `WHERE` clause, `COUNT(column)` or `COUNT(DISTINCT ...)` still scan.
+    pub async fn count_ibd_rows(&self, table_name: &str) -> Result<u64, FusionLabError> {
+        let provider = self
             .ctx
-            .sql(sql)
+            .table_provider(table_name)
             .await
             .map_err(|e| FusionLabError::DataFusion(e.to_string()))?;
+        if provider
+            .as_any()
+            .downcast_ref::<IbdTableProvider>()
+            .is_none()
+        {
+            return Err(FusionLabError::DataFusion(format!(
+                "{} is not an .ibd table",
+                table_name
+            )));
+        }
+        // The reader makes blocking FFI calls
+        tokio::task::spawn_blocking(move || {
+            provider
+                .as_any()
+                .downcast_ref::<IbdTableProvider>()
+                .expect("checked above")
+                .count_rows()
+        })
+        .await
+        .map_err(|e| FusionLabError::IbdReader(e.to_string()))?
+        .map_err(|e| FusionLabError::IbdReader(e.to_string()))
+    }
 
-        let batches = df
-            .collect()
-            .await
+    /// Register the rows of a MySQL query result as an in-memory table
+    ///
+    /// Columns get the Arrow types of `QueryResult::to_record_batches`, so
+    /// the result can be joined or aggregated further with DataFusion.
+    pub fn register_query_result(
+        &self,
+        table_name: &str,
+        result: &QueryResult,
+    ) -> Result<(), FusionLabError> {
+        let batches = result.to_record_batches(None)?;
+        let table = MemTable::try_new(batches[0].schema(), vec![batches])
             .map_err(|e| FusionLabError::DataFusion(e.to_string()))?;
 
-        let duration_ms = start.elapsed().as_secs_f64() * 1000.0;
+        self.ctx
+            .register_table(table_name, Arc::new(table))
+            .map_err(|e| FusionLabError::DataFusion(e.to_string()))?;
 
-        let row_count: usize = batches.iter().map(|b| b.num_rows()).sum();
+        Ok(())
+    }
 
-        Ok(DfQueryResult {
-            row_count,
-            duration_ms,
-            batches,
-        })
+    /// Run `sql` on MySQL and register its rows as the in-memory table `name`
+    ///
+    /// DataFusion cannot scan MySQL itself, so this is how live MySQL rows
+    /// meet `.ibd` or file tables in one statement, e.g. `SELECT ... FROM
+    /// archive UNION ALL SELECT ... FROM live`. The whole result is fetched
+    /// and converted up front: it is held twice in memory while converting
+    /// (rows, then Arrow batches), later queries see a snapshot rather than
+    /// the live table, and no filter is pushed down to MySQL, so put the
+    /// predicates in `sql`. Registering `name` again replaces the table, so
+    /// that is how to see newer rows. Returns the MySQL result for its
+    /// timings.
+    pub async fn register_mysql_query(
+        &self,
+        name: &str,
+        runner: &MySQLRunner,
+        sql: &str,
+    ) -> Result<QueryResult, FusionLabError> {
+        let result = runner.run_query(sql).await?;
+        // Only replaced once the new rows are in hand
+        self.ctx
+            .deregister_table(name)
+            .map_err(|e| FusionLabError::DataFusion(e.to_string()))?;
+        self.register_query_result(name, &result)?;
+        Ok(result)
+    }
+
+    /// Register the SSB sample data for testing
+    /// Creates small in-memory versions of SSB tables
+    pub fn register_ssb_sample(&self) -> Result<(), FusionLabError> {
+        for (name, batch) in ssb_sample_tables()? {
+            self.register_batch(name, batch)?;
+        }
+        Ok(())
+    }
+
+    /// Run a query using collect() - gets all results at once
+    pub async fn run_query_collect(&self, sql: &str) -> Result<DfQueryResult, FusionLabError> {
+        collect_query(&self.ctx, sql).await
+    }
+
+    /// Run a query with `collect()`, giving up with `FusionLabError::Timeout`
+    /// after `timeout`
+    ///
+    /// The query runs as its own task, which is aborted on timeout. Aborting
+    /// drops the plan's streams at their next yield point; an IBD partition
+    /// that is mid-batch on a blocking thread finishes that batch and then
+    /// closes its reader. Operators that never yield (such as a recursive
+    /// CTE) keep a worker busy until they finish, so the deadline is only
+    /// reliable on a multi-threaded runtime with a worker to spare.
+    pub async fn run_query_collect_with_timeout(
+        &self,
+        sql: &str,
+        timeout: Duration,
+    ) -> Result<DfQueryResult, FusionLabError> {
+        let ctx = self.ctx.clone();
+        let sql = sql.to_string();
+        let mut task = tokio::spawn(async move { collect_query(&ctx, &sql).await });
+        match tokio::time::timeout(timeout, &mut task).await {
+            Ok(joined) => joined.map_err(|e| FusionLabError::DataFusion(e.to_string()))?,
+            Err(_) => {
+                task.abort();
+                Err(FusionLabError::Timeout {
+                    elapsed_ms: timeout.as_millis() as u64,
+                })
+            }
+        }
     }
 
     /// Run a query using execute_stream() - processes batches incrementally
     pub async fn run_query_stream(&self, sql: &str) -> Result<DfQueryResult, FusionLabError> {
+        let mut batches = Vec::new();
+        let mut result = self
+            .run_query_stream_with(sql, |batch| {
+                batches.push(batch.clone());
+                Ok(())
+            })
+            .await?;
+        result.batches = batches;
+        Ok(result)
+    }
+
+    /// Stream a query, handing each batch to `on_batch` as it is produced
+    ///
+    /// Batches are not retained, so the returned result has no `batches`;
+    /// `duration_ms` includes the time spent in `on_batch`. An error from
+    /// `on_batch` stops the query and is returned as is.
+    pub async fn run_query_stream_with<F>(
+        &self,
+        sql: &str,
+        mut on_batch: F,
+    ) -> Result<DfQueryResult, FusionLabError>
+    where
+        F: FnMut(&RecordBatch) -> Result<(), FusionLabError>,
+    {
         let start = Instant::now();
 
         let df = self
@@ -181,28 +830,66 @@ impl DataFusionRunner {
             .sql(sql)
             .await
             .map_err(|e| FusionLabError::DataFusion(e.to_string()))?;
+        let schema: SchemaRef = Arc::new(df.schema().as_arrow().clone());
 
         let mut stream = df
             .execute_stream()
             .await
             .map_err(|e| FusionLabError::DataFusion(e.to_string()))?;
 
-        let mut batches = Vec::new();
+        let mut row_count = 0;
         while let Some(batch_result) = stream.next().await {
             let batch = batch_result.map_err(|e| FusionLabError::DataFusion(e.to_string()))?;
-            batches.push(batch);
+            row_count += batch.num_rows();
+            on_batch(&batch)?;
         }
 
         let duration_ms = start.elapsed().as_secs_f64() * 1000.0;
-        let row_count: usize = batches.iter().map(|b| b.num_rows()).sum();
 
         Ok(DfQueryResult {
             row_count,
             duration_ms,
-            batches,
+            batches: vec![],
+            schema,
         })
     }
 
+    /// Run a query `warmup + iterations` times with `collect()` and
+    /// summarize the timings; warmup runs are excluded from the statistics
+    pub async fn run_query_repeat(
+        &self,
+        sql: &str,
+        iterations: usize,
+        warmup: usize,
+    ) -> Result<QueryStats, FusionLabError> {
+        for _ in 0..warmup {
+            self.run_query_collect(sql).await?;
+        }
+        let mut samples = Vec::with_capacity(iterations);
+        let mut row_count = 0;
+        for _ in 0..iterations {
+            let result = self.run_query_collect(sql).await?;
+            samples.push(result.duration_ms);
+            row_count = result.row_count;
+        }
+        Ok(QueryStats::from_samples(samples, warmup, row_count))
+    }
+
+    /// Check that `sql` parses and its tables and columns resolve, without
+    /// running it
+    ///
+    /// Only builds the logical plan, so unlike `ctx.sql` it does not run DDL
+    /// such as `CREATE TABLE ... AS SELECT` either. Unknown tables and
+    /// columns are reported by name.
+    pub async fn validate(&self, sql: &str) -> Result<(), FusionLabError> {
+        self.ctx
+            .state()
+            .create_logical_plan(sql)
+            .await
+            .map_err(|e| FusionLabError::DataFusion(e.to_string()))?;
+        Ok(())
+    }
+
     /// Get the logical plan for a query
     pub async fn explain(&self, sql: &str) -> Result<String, FusionLabError> {
         let df = self
@@ -230,6 +917,68 @@ impl DataFusionRunner {
 
         Ok(format!("{}", datafusion::physical_plan::displayable(plan.as_ref()).indent(true)))
     }
+
+    /// Run a query and show its physical plan annotated with runtime metrics
+    ///
+    /// Like MySQL `EXPLAIN ANALYZE`, the query is actually executed; every
+    /// operator line carries its `output_rows`, `elapsed_compute` and other
+    /// metrics aggregated over partitions.
+    pub async fn explain_analyze(&self, sql: &str) -> Result<String, FusionLabError> {
+        let start = Instant::now();
+        let df = self
+            .ctx
+            .sql(sql)
+            .await
+            .map_err(|e| FusionLabError::DataFusion(e.to_string()))?;
+        let task_ctx = Arc::new(df.task_ctx());
+        let plan = df
+            .create_physical_plan()
+            .await
+            .map_err(|e| FusionLabError::DataFusion(e.to_string()))?;
+
+        let batches = datafusion::physical_plan::collect(plan.clone(), task_ctx)
+            .await
+            .map_err(|e| FusionLabError::DataFusion(e.to_string()))?;
+        let row_count: usize = batches.iter().map(|b| b.num_rows()).sum();
+        let duration_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+        Ok(format!(
+            "Rows: {}, Time: {:.2}ms\n{}",
+            row_count,
+            duration_ms,
+            datafusion::physical_plan::display::DisplayableExecutionPlan::with_metrics(
+                plan.as_ref()
+            )
+            .indent(true)
+        ))
+    }
+}
+
+/// Plan and `collect()` a query; shared by the plain and timed variants
+async fn collect_query(ctx: &SessionContext, sql: &str) -> Result<DfQueryResult, FusionLabError> {
+    let start = Instant::now();
+
+    let df = ctx
+        .sql(sql)
+        .await
+        .map_err(|e| FusionLabError::DataFusion(e.to_string()))?;
+    let schema: SchemaRef = Arc::new(df.schema().as_arrow().clone());
+
+    let batches = df
+        .collect()
+        .await
+        .map_err(|e| FusionLabError::DataFusion(e.to_string()))?;
+
+    let duration_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+    let row_count: usize = batches.iter().map(|b| b.num_rows()).sum();
+
+    Ok(DfQueryResult {
+        row_count,
+        duration_ms,
+        batches,
+        schema,
+    })
 }
 
 impl Default for DataFusionRunner {
@@ -240,6 +989,18 @@ impl Default for DataFusionRunner {
 
 // Helper functions to create sample SSB data
 
+/// The SSB sample tables, shared by `DataFusionRunner::register_ssb_sample`
+/// and `MySQLRunner::load_ssb_sample` so both engines see the same rows
+pub(crate) fn ssb_sample_tables() -> Result<Vec<(&'static str, RecordBatch)>, FusionLabError> {
+    Ok(vec![
+        ("lineorder", create_sample_lineorder()?),
+        ("customer", create_sample_customer()?),
+        ("supplier", create_sample_supplier()?),
+        ("part", create_sample_part()?),
+        ("date", create_sample_date()?),
+    ])
+}
+
 fn create_sample_lineorder() -> Result<RecordBatch, FusionLabError> {
     let schema = Arc::new(Schema::new(vec![
         Field::new("lo_orderkey", DataType::Int64, false),
@@ -462,24 +1223,291 @@ fn create_sample_date() -> Result<RecordBatch, FusionLabError> {
     Ok(batch)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::path::Path;
+/// Extension of `path` (with the dot) if it is a single file
+///
+/// A single file is only picked up if it carries the expected extension, so
+/// readers accept whatever the file is named (`.ndjson`, `.log`, `.tbl`).
+fn single_file_extension(path: &str) -> Option<String> {
+    Path::new(path)
+        .extension()
+        .filter(|_| Path::new(path).is_file())
+        .and_then(|ext| ext.to_str())
+        .map(|ext| format!(".{}", ext))
+}
 
-    fn ibd_available() -> bool {
-        if let Ok(path) = std::env::var("IBD_READER_LIB_PATH") {
-            let lib_path = Path::new(&path);
-            let lib_found = lib_path.join("libibd_reader.so").exists()
-                || lib_path.join("libibd_reader.dylib").exists()
-                || lib_path.join("ibd_reader.dll").exists();
-            if lib_found {
-                return true;
-            }
+/// Regex matching exactly one of `markers`
+fn null_regex(markers: &[String]) -> String {
+    let alternatives: Vec<String> = markers
+        .iter()
+        .map(|marker| {
+            marker.chars().fold(String::new(), |mut escaped, c| {
+                if "\\.+*?()|[]{}^$".contains(c) {
+                    escaped.push('\\');
+                }
+                escaped.push(c);
+                escaped
+            })
+        })
+        .collect();
+    format!("^(?:{})$", alternatives.join("|"))
+}
+
+/// Schemas of the five SSB tables as dbgen writes them, keyed by table name,
+/// for registering headerless `.tbl` files with their column names
+///
+/// Types follow `docker/init.sql`: integers are `Int64`, text is `Utf8`.
+pub fn ssb_schemas() -> HashMap<String, SchemaRef> {
+    use DataType::{Int64, Utf8};
+    let schema = |columns: &[(&str, DataType)]| {
+        let fields: Vec<Field> = columns
+            .iter()
+            .map(|(name, data_type)| Field::new(*name, data_type.clone(), false))
+            .collect();
+        Arc::new(Schema::new(fields))
+    };
+
+    HashMap::from([
+        (
+            "lineorder".to_string(),
+            schema(&[
+                ("lo_orderkey", Int64),
+                ("lo_linenumber", Int64),
+                ("lo_custkey", Int64),
+                ("lo_partkey", Int64),
+                ("lo_suppkey", Int64),
+                ("lo_orderdate", Int64),
+                ("lo_orderpriority", Utf8),
+                ("lo_shippriority", Utf8),
+                ("lo_quantity", Int64),
+                ("lo_extendedprice", Int64),
+                ("lo_ordtotalprice", Int64),
+                ("lo_discount", Int64),
+                ("lo_revenue", Int64),
+                ("lo_supplycost", Int64),
+                ("lo_tax", Int64),
+                ("lo_commitdate", Int64),
+                ("lo_shipmode", Utf8),
+            ]),
+        ),
+        (
+            "customer".to_string(),
+            schema(&[
+                ("c_custkey", Int64),
+                ("c_name", Utf8),
+                ("c_address", Utf8),
+                ("c_city", Utf8),
+                ("c_nation", Utf8),
+                ("c_region", Utf8),
+                ("c_phone", Utf8),
+                ("c_mktsegment", Utf8),
+            ]),
+        ),
+        (
+            "supplier".to_string(),
+            schema(&[
+                ("s_suppkey", Int64),
+                ("s_name", Utf8),
+                ("s_address", Utf8),
+                ("s_city", Utf8),
+                ("s_nation", Utf8),
+                ("s_region", Utf8),
+                ("s_phone", Utf8),
+            ]),
+        ),
+        (
+            "part".to_string(),
+            schema(&[
+                ("p_partkey", Int64),
+                ("p_name", Utf8),
+                ("p_mfgr", Utf8),
+                ("p_category", Utf8),
+                ("p_brand1", Utf8),
+                ("p_color", Utf8),
+                ("p_type", Utf8),
+                ("p_size", Int64),
+                ("p_container", Utf8),
+            ]),
+        ),
+        (
+            "date".to_string(),
+            schema(&[
+                ("d_datekey", Int64),
+                ("d_date", Utf8),
+                ("d_dayofweek", Utf8),
+                ("d_month", Utf8),
+                ("d_year", Int64),
+                ("d_yearmonthnum", Int64),
+                ("d_yearmonth", Utf8),
+                ("d_daynuminmonth", Int64),
+                ("d_daynuminyear", Int64),
+                ("d_monthnuminyear", Int64),
+                ("d_weeknuminyear", Int64),
+                ("d_sellingseason", Utf8),
+                ("d_lastdayinweekfl", Int64),
+                ("d_lastdayinmonthfl", Int64),
+                ("d_holidayfl", Int64),
+                ("d_weekdayfl", Int64),
+            ]),
+        ),
+    ])
+}
+
+/// Error for JSON files DataFusion failed to read
+///
+/// Arrow's JSON errors do not say where the bad record is, so for those the
+/// files are scanned for the first line that does not parse.
+fn json_read_error(path: &Path, extension: &str, err: DataFusionError) -> FusionLabError {
+    if matches!(
+        err.find_root(),
+        DataFusionError::ArrowError(ArrowError::JsonError(_), _)
+    ) {
+        if let Some((file, line, message)) = first_malformed_line(path, extension) {
+            return FusionLabError::Parse(format!(
+                "{}:{}: malformed JSON: {}",
+                file.display(),
+                line,
+                message
+            ));
         }
+    }
+    FusionLabError::DataFusion(err.to_string())
+}
 
-        let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
-        let default_path = manifest_dir.join("../../..").join("percona-parser/build");
+/// File, 1-based line and error of the first non-blank line under `path`
+/// that is not valid JSON; directories are read like DataFusion lists them,
+/// files ending in `extension` in name order
+fn first_malformed_line(path: &Path, extension: &str) -> Option<(PathBuf, usize, String)> {
+    let files = if path.is_dir() {
+        let mut files: Vec<PathBuf> = std::fs::read_dir(path)
+            .ok()?
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|file| file.to_str().is_some_and(|name| name.ends_with(extension)))
+            .collect();
+        files.sort();
+        files
+    } else {
+        vec![path.to_path_buf()]
+    };
+
+    files.into_iter().find_map(|file| {
+        let reader = BufReader::new(std::fs::File::open(&file).ok()?);
+        reader
+            .split(b'\n')
+            .enumerate()
+            .find_map(|(i, line)| {
+                let line = line.ok()?;
+                if std::str::from_utf8(&line).is_ok_and(|line| line.trim().is_empty()) {
+                    return None;
+                }
+                let err = serde_json::from_slice::<serde_json::Value>(&line).err()?;
+                // serde_json counts lines within the record, always line 1
+                let message = err.to_string();
+                let message = message.split(" at line ").next().unwrap_or_default();
+                Some((i + 1, format!("{} at column {}", message, err.column())))
+            })
+            .map(|(line, message)| (file, line, message))
+    })
+}
+
+/// One column of a `--csv-schema` definition
+#[derive(Deserialize)]
+struct CsvColumn {
+    name: String,
+    #[serde(rename = "type")]
+    data_type: String,
+    #[serde(default = "default_nullable")]
+    nullable: bool,
+}
+
+fn default_nullable() -> bool {
+    true
+}
+
+/// Parse CSV schemas from JSON, keyed by table name:
+///
+/// ```json
+/// {"customer": [{"name": "c_custkey", "type": "Int32"},
+///               {"name": "c_name", "type": "Utf8", "nullable": false}]}
+/// ```
+///
+/// Types use Arrow's `DataType` display names (`Int64`, `Utf8`,
+/// `Decimal128(15, 2)`, `Date32`, ...).
+pub fn parse_csv_schemas(json: &str) -> Result<HashMap<String, Schema>, FusionLabError> {
+    let tables: HashMap<String, Vec<CsvColumn>> = serde_json::from_str(json)
+        .map_err(|e| FusionLabError::Config(format!("invalid CSV schema: {}", e)))?;
+    tables
+        .into_iter()
+        .map(|(table, columns)| {
+            let fields = columns
+                .into_iter()
+                .map(|column| {
+                    let data_type = column.data_type.parse::<DataType>().map_err(|e| {
+                        FusionLabError::Config(format!(
+                            "invalid type for {}.{}: {}",
+                            table, column.name, e
+                        ))
+                    })?;
+                    Ok(Field::new(column.name, data_type, column.nullable))
+                })
+                .collect::<Result<Vec<_>, FusionLabError>>()?;
+            Ok((table, Schema::new(fields)))
+        })
+        .collect()
+}
+
+/// `sql` with `ORDER BY order_by` added if its outermost query has none
+///
+/// Makes the first rows of a result reproducible, since a parallel scan
+/// returns batches in no particular order. The clause goes before any
+/// `LIMIT`, so a limited query keeps the first rows of the sorted result.
+/// The query is re-rendered from its syntax tree when the clause is added,
+/// and returned unchanged when it already has one.
+pub fn ensure_order_by(sql: &str, order_by: &str) -> Result<String, FusionLabError> {
+    let parse = |sql: &str| {
+        Parser::parse_sql(&GenericDialect {}, sql).map_err(|e| FusionLabError::Parse(e.to_string()))
+    };
+    let mut statements = parse(sql)?;
+    let [Statement::Query(query)] = statements.as_mut_slice() else {
+        return Err(FusionLabError::Parse(
+            "only a single query can be sorted".to_string(),
+        ));
+    };
+    if query.order_by.is_some() {
+        return Ok(sql.to_string());
+    }
+
+    // Let the parser build the clause
+    let mut template = parse(&format!("SELECT 1 ORDER BY {}", order_by))?;
+    let [Statement::Query(sorted)] = template.as_mut_slice() else {
+        return Err(FusionLabError::Parse(format!(
+            "invalid ORDER BY list: {}",
+            order_by
+        )));
+    };
+    query.order_by = sorted.order_by.take();
+    Ok(query.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::ipc::reader::FileReader;
+    use std::io::Cursor;
+    use std::path::Path;
+
+    fn ibd_available() -> bool {
+        if let Ok(path) = std::env::var("IBD_READER_LIB_PATH") {
+            let lib_path = Path::new(&path);
+            let lib_found = lib_path.join("libibd_reader.so").exists()
+                || lib_path.join("libibd_reader.dylib").exists()
+                || lib_path.join("ibd_reader.dll").exists();
+            if lib_found {
+                return true;
+            }
+        }
+
+        let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let default_path = manifest_dir.join("../../..").join("percona-parser/build");
         let fallback_path = manifest_dir.join("../../percona-parser/build");
         let candidates = [default_path, fallback_path];
         candidates.into_iter().any(|path| {
@@ -489,6 +1517,16 @@ mod tests {
         })
     }
 
+    const TYPES_IBD: &str = "/home/cslog/mysql/percona-parser/tests/types_test.ibd";
+    const TYPES_SDI: &str = "/home/cslog/mysql/percona-parser/tests/types_test_sdi.json";
+
+    /// Paths of the types fixture, or `None` when it or the reader library
+    /// is missing and the test should be skipped
+    fn types_fixture() -> Option<(&'static str, &'static str)> {
+        (ibd_available() && Path::new(TYPES_IBD).exists() && Path::new(TYPES_SDI).exists())
+            .then_some((TYPES_IBD, TYPES_SDI))
+    }
+
     #[tokio::test]
     async fn test_simple_query() {
         let runner = DataFusionRunner::new();
@@ -503,6 +1541,21 @@ mod tests {
         assert!(result.duration_ms > 0.0);
     }
 
+    #[tokio::test]
+    async fn test_run_query_repeat() {
+        let runner = DataFusionRunner::new();
+        runner.register_ssb_sample().unwrap();
+
+        let stats = runner
+            .run_query_repeat("SELECT COUNT(*) FROM lineorder", 3, 1)
+            .await
+            .unwrap();
+        assert_eq!(stats.iterations(), 3);
+        assert_eq!(stats.warmup, 1);
+        assert_eq!(stats.row_count, 1);
+        assert!(stats.min_ms > 0.0 && stats.min_ms <= stats.max_ms);
+    }
+
     #[tokio::test]
     async fn test_group_by_query() {
         let runner = DataFusionRunner::new();
@@ -523,6 +1576,581 @@ mod tests {
         println!("{}", result.to_table());
     }
 
+    #[tokio::test]
+    async fn test_to_table_with_max_col_width() {
+        let runner = DataFusionRunner::new();
+        let result = runner
+            .run_query_collect("SELECT 'abcdefghij' AS s, NULL AS n")
+            .await
+            .unwrap();
+
+        let options = TableFormatOptions {
+            max_col_width: Some(5),
+            ..TableFormatOptions::default()
+        };
+        let table = result.to_table_with_options("NULL", &options);
+        assert!(table.contains("| abcd… | NULL |"), "{}", table);
+
+        let result = runner
+            .run_query_collect("SELECT 'a' AS s, 1234 AS n UNION ALL SELECT 'bc', 5 ORDER BY n")
+            .await
+            .unwrap();
+        let options = TableFormatOptions {
+            thousands_separators: true,
+            ..TableFormatOptions::default()
+        };
+        let table = result.to_table_with_options("NULL", &options);
+        assert!(table.contains("| bc |     5 |"), "{}", table);
+        assert!(table.contains("| a  | 1,234 |"), "{}", table);
+    }
+
+    #[tokio::test]
+    async fn test_validate() {
+        let runner = DataFusionRunner::new();
+        runner.register_ssb_sample().unwrap();
+
+        runner
+            .validate("SELECT c_nation, COUNT(*) FROM customer GROUP BY c_nation")
+            .await
+            .unwrap();
+
+        async fn error(runner: &DataFusionRunner, sql: &str) -> String {
+            runner.validate(sql).await.unwrap_err().to_string()
+        }
+        assert!(error(&runner, "SELEC 1").await.contains("SELEC"));
+        assert!(error(&runner, "SELECT * FROM no_such_table")
+            .await
+            .contains("no_such_table"));
+        assert!(error(&runner, "SELECT c_no_such_column FROM customer")
+            .await
+            .contains("c_no_such_column"));
+
+        // DDL is planned, not run
+        runner
+            .validate("CREATE TABLE scratch AS SELECT * FROM customer")
+            .await
+            .unwrap();
+        assert!(error(&runner, "SELECT * FROM scratch")
+            .await
+            .contains("scratch"));
+    }
+
+    #[tokio::test]
+    async fn test_explain_analyze() {
+        let runner = DataFusionRunner::new();
+        runner.register_ssb_sample().unwrap();
+
+        let output = runner
+            .explain_analyze(
+                "SELECT c_nation, COUNT(*) FROM customer WHERE c_custkey > 1 GROUP BY c_nation",
+            )
+            .await
+            .unwrap();
+        println!("{}", output);
+        assert!(output.starts_with("Rows: "));
+        assert!(output.contains("AggregateExec"));
+        assert!(output.contains("output_rows="));
+        assert!(output.contains("elapsed_compute="));
+
+        assert!(runner
+            .explain_analyze("SELECT * FROM missing")
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_register_query_result() {
+        use crate::{CellValue, ColumnType};
+
+        let result = QueryResult {
+            row_count: 3,
+            rows: vec![
+                vec![
+                    CellValue::Int(1),
+                    CellValue::Str("UNITED STATES".to_string()),
+                ],
+                vec![CellValue::Int(2), CellValue::Str("CHINA".to_string())],
+                vec![CellValue::Int(3), CellValue::Null],
+            ],
+            columns: vec!["c_custkey".to_string(), "nation".to_string()],
+            column_types: vec![ColumnType::Int, ColumnType::String],
+            ..QueryResult::default()
+        };
+
+        let runner = DataFusionRunner::new();
+        runner.register_ssb_sample().unwrap();
+        runner.register_query_result("mysql_rows", &result).unwrap();
+
+        // Join the MySQL rows with a DataFusion table
+        let joined = runner
+            .run_query_collect(
+                "SELECT m.c_custkey, m.nation, c.c_nation \
+                 FROM mysql_rows m JOIN customer c ON m.c_custkey = c.c_custkey \
+                 WHERE m.nation IS NOT NULL ORDER BY m.c_custkey",
+            )
+            .await
+            .unwrap();
+        assert_eq!(joined.row_count, 2);
+        assert_eq!(joined.schema.field(0).data_type(), &DataType::Int64);
+    }
+
+    #[tokio::test]
+    async fn test_to_json() {
+        let runner = DataFusionRunner::new();
+        runner.register_ssb_sample().unwrap();
+
+        let result = runner
+            .run_query_collect(
+                "SELECT c_custkey, c_nation FROM customer ORDER BY c_custkey LIMIT 2",
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            result.to_json().unwrap(),
+            r#"[{"c_custkey":1,"c_nation":"UNITED STATES"},{"c_custkey":2,"c_nation":"UNITED STATES"}]"#
+        );
+
+        let empty = runner
+            .run_query_collect("SELECT c_custkey FROM customer WHERE c_custkey < 0")
+            .await
+            .unwrap();
+        assert_eq!(empty.to_json().unwrap(), "[]");
+        let doc: serde_json::Value =
+            serde_json::from_str(&empty.to_json_document().unwrap()).unwrap();
+        assert_eq!(doc["columns"], serde_json::json!(["c_custkey"]));
+        assert_eq!(doc["rows"], serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_to_json_document() {
+        let runner = DataFusionRunner::new();
+        let result = runner
+            .run_query_collect("SELECT 1 AS n, 2.5 AS x, CAST(NULL AS INT) AS missing, 'a' AS s")
+            .await
+            .unwrap();
+
+        let doc: serde_json::Value =
+            serde_json::from_str(&result.to_json_document().unwrap()).unwrap();
+        assert_eq!(
+            doc["columns"],
+            serde_json::json!(["n", "x", "missing", "s"])
+        );
+        assert_eq!(doc["row_count"], 1);
+        assert!(doc["duration_ms"].is_number());
+        assert_eq!(doc["rows"], serde_json::json!([[1, 2.5, null, "a"]]));
+
+        // Rows are arrays, so repeated column names keep every value
+        let result = runner
+            .run_query_collect("SELECT c.a, d.a FROM (SELECT 1 AS a) c, (SELECT 2 AS a) d")
+            .await
+            .unwrap();
+        let doc: serde_json::Value =
+            serde_json::from_str(&result.to_json_document().unwrap()).unwrap();
+        assert_eq!(doc["columns"], serde_json::json!(["a", "a"]));
+        assert_eq!(doc["rows"], serde_json::json!([[1, 2]]));
+    }
+
+    #[test]
+    fn test_rows_and_columns() {
+        use datafusion::arrow::array::UInt64Array;
+
+        // The column types the IBD provider produces
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("big", DataType::UInt64, true),
+            Field::new("price", DataType::Float64, true),
+            Field::new("name", DataType::Utf8, true),
+        ]));
+        let batch = |ids: Vec<i64>, bigs, prices, names| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int64Array::from(ids)) as ArrayRef,
+                    Arc::new(UInt64Array::from(bigs)) as ArrayRef,
+                    Arc::new(Float64Array::from(prices)) as ArrayRef,
+                    Arc::new(StringArray::from(names)) as ArrayRef,
+                ],
+            )
+            .unwrap()
+        };
+        let result = DfQueryResult {
+            row_count: 3,
+            duration_ms: 0.0,
+            batches: vec![
+                batch(
+                    vec![1, -2],
+                    vec![Some(u64::MAX), None],
+                    vec![Some(1.5), Some(-0.25)],
+                    vec![Some("a"), None],
+                ),
+                batch(vec![3], vec![Some(7)], vec![None], vec![Some("c, d")]),
+            ],
+            schema: schema.clone(),
+        };
+
+        assert_eq!(result.columns(), vec!["id", "big", "price", "name"]);
+        let rows: Vec<Vec<String>> = result.rows().collect();
+        assert_eq!(
+            rows,
+            vec![
+                vec!["1", "18446744073709551615", "1.5", "a"],
+                vec!["-2", "NULL", "-0.25", "NULL"],
+                vec!["3", "7", "NULL", "c, d"],
+            ]
+        );
+
+        let empty = DfQueryResult {
+            row_count: 0,
+            duration_ms: 0.0,
+            batches: vec![],
+            schema,
+        };
+        let rows: Vec<Vec<String>> = result.rows_with_null("").collect();
+        assert_eq!(rows[1], vec!["-2", "", "-0.25", ""]);
+
+        assert_eq!(empty.columns().len(), 4);
+        assert_eq!(empty.rows().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_to_csv() {
+        let runner = DataFusionRunner::new();
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("note", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2, 3])) as ArrayRef,
+                Arc::new(StringArray::from(vec![
+                    Some("a,b"),
+                    Some("line\nbreak"),
+                    None,
+                ])) as ArrayRef,
+            ],
+        )
+        .unwrap();
+        runner.register_batch("notes", batch).unwrap();
+
+        let result = runner
+            .run_query_collect("SELECT id, note FROM notes ORDER BY id")
+            .await
+            .unwrap();
+        assert_eq!(
+            result.to_csv().unwrap(),
+            "id,note\n1,\"a,b\"\n2,\"line\nbreak\"\n3,\n"
+        );
+        assert!(result.to_csv_with_null("\\N").unwrap().ends_with("3,\\N\n"));
+
+        let empty = runner
+            .run_query_collect("SELECT id, note FROM notes WHERE id < 0")
+            .await
+            .unwrap();
+        assert_eq!(empty.to_csv().unwrap(), "id,note\n");
+    }
+
+    #[tokio::test]
+    async fn test_to_ipc() {
+        let runner = DataFusionRunner::new();
+        runner.register_ssb_sample().unwrap();
+        let read = |result: &DfQueryResult| {
+            let mut buf = Vec::new();
+            result.to_ipc(&mut buf).unwrap();
+            let reader = FileReader::try_new(Cursor::new(buf), None).unwrap();
+            let schema = reader.schema();
+            let batches: Vec<RecordBatch> = reader.map(|batch| batch.unwrap()).collect();
+            (schema, batches)
+        };
+
+        let result = runner
+            .run_query_collect("SELECT c_custkey, c_name FROM customer ORDER BY c_custkey")
+            .await
+            .unwrap();
+        let (schema, batches) = read(&result);
+        assert_eq!(schema, result.schema);
+        assert_eq!(
+            pretty_format_batches(&batches).unwrap().to_string(),
+            result.to_table()
+        );
+
+        // Empty results still carry the schema
+        let empty = runner
+            .run_query_collect("SELECT c_custkey FROM customer WHERE c_custkey < 0")
+            .await
+            .unwrap();
+        let (schema, batches) = read(&empty);
+        assert_eq!(schema.field(0).name(), "c_custkey");
+        assert!(batches.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_register_parquet() {
+        use datafusion::parquet::arrow::ArrowWriter;
+
+        let path = std::env::temp_dir().join(format!(
+            "fusionlab_test_{}_customer.parquet",
+            std::process::id()
+        ));
+        let batch = create_sample_customer().unwrap();
+        let file = std::fs::File::create(&path).unwrap();
+        let mut writer = ArrowWriter::try_new(file, batch.schema(), None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+
+        let runner = DataFusionRunner::new();
+        runner
+            .register_parquet("customer", path.to_str().unwrap())
+            .await
+            .unwrap();
+        let result = runner
+            .run_query_collect("SELECT * FROM customer")
+            .await
+            .unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(result.row_count, batch.num_rows());
+    }
+
+    #[tokio::test]
+    async fn test_register_csv_with_options() {
+        let path = std::env::temp_dir().join(format!(
+            "fusionlab_test_{}_supplier.csv",
+            std::process::id()
+        ));
+        std::fs::write(&path, "1|Supplier#1|0042\n2|Supplier#2|0007\n").unwrap();
+
+        let schemas = parse_csv_schemas(
+            r#"{"supplier": [
+                {"name": "s_suppkey", "type": "Int32", "nullable": false},
+                {"name": "s_name", "type": "Utf8"},
+                {"name": "s_code", "type": "Utf8"}
+            ]}"#,
+        )
+        .unwrap();
+        let options = CsvOptions {
+            delimiter: b'|',
+            has_header: false,
+            ..CsvOptions::default()
+        }
+        .with_schema(Arc::new(schemas["supplier"].clone()));
+
+        let runner = DataFusionRunner::new();
+        let registered = runner
+            .register_csv_with_options("supplier", path.to_str().unwrap(), options)
+            .await;
+        let result = runner
+            .run_query_collect("SELECT s_name, s_code FROM supplier WHERE s_suppkey = 1")
+            .await;
+        std::fs::remove_file(&path).ok();
+
+        registered.unwrap();
+        // Leading zeros survive because the column is declared as text
+        assert_eq!(
+            result.unwrap().rows().collect::<Vec<_>>(),
+            vec![vec!["Supplier#1".to_string(), "0042".to_string()]]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_register_dbgen_tbl() {
+        let path = std::env::temp_dir().join(format!(
+            "fusionlab_test_{}_supplier.tbl",
+            std::process::id()
+        ));
+        std::fs::write(
+            &path,
+            "1|Supplier#000000001|N kD4on9OM|PERU     0|PERU|AMERICA|27-989-741-2988|\n\
+             2|Supplier#000000002|89eJ5ksX3Imx|ETHIOPIA 1|ETHIOPIA|AFRICA|15-768-687-3665|\n",
+        )
+        .unwrap();
+
+        let runner = DataFusionRunner::new();
+        let options = CsvOptions::dbgen().with_schema(ssb_schemas()["supplier"].clone());
+        let registered = runner
+            .register_csv_with_options("supplier", path.to_str().unwrap(), options)
+            .await;
+        let result = runner
+            .run_query_collect("SELECT * FROM supplier WHERE s_region = 'AFRICA'")
+            .await;
+        std::fs::remove_file(&path).ok();
+
+        registered.unwrap();
+        let result = result.unwrap();
+        // The empty field after the last `|` is not a column
+        let columns: Vec<&str> = result
+            .schema
+            .fields()
+            .iter()
+            .map(|f| f.name().as_str())
+            .collect();
+        assert_eq!(columns.first(), Some(&"s_suppkey"));
+        assert_eq!(columns.last(), Some(&"s_phone"));
+        assert_eq!(columns.len(), 7);
+        assert_eq!(result.row_count, 1);
+        assert_eq!(result.rows().next().unwrap()[0], "2");
+    }
+
+    #[tokio::test]
+    async fn test_register_csv_null_markers() {
+        let path =
+            std::env::temp_dir().join(format!("fusionlab_test_{}_nulls.csv", std::process::id()));
+        std::fs::write(&path, "id,qty,note\n1,\\N,a\n2,7,NULL\n3,NULL,\\N\n").unwrap();
+
+        let runner = DataFusionRunner::new();
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("qty", DataType::Int64, true),
+            Field::new("note", DataType::Utf8, true),
+        ]);
+        let options = CsvOptions {
+            null_markers: vec!["\\N".to_string(), "NULL".to_string()],
+            ..CsvOptions::default()
+        }
+        .with_schema(Arc::new(schema));
+        let registered = runner
+            .register_csv_with_options("nulls", path.to_str().unwrap(), options)
+            .await;
+        let result = runner
+            .run_query_collect(
+                "SELECT COUNT(qty) AS qty, COUNT(note) AS note, SUM(qty) AS total FROM nulls",
+            )
+            .await;
+        std::fs::remove_file(&path).ok();
+
+        registered.unwrap();
+        assert_eq!(
+            result.unwrap().rows().collect::<Vec<_>>(),
+            vec![vec!["1".to_string(), "1".to_string(), "7".to_string()]]
+        );
+    }
+
+    #[test]
+    fn test_null_regex() {
+        assert_eq!(
+            null_regex(&["\\N".to_string(), "n/a (x)".to_string()]),
+            r"^(?:\\N|n/a \(x\))$"
+        );
+    }
+
+    #[test]
+    fn test_ensure_order_by() {
+        assert_eq!(
+            ensure_order_by("SELECT c_name FROM customer LIMIT 5", "c_name").unwrap(),
+            "SELECT c_name FROM customer ORDER BY c_name LIMIT 5"
+        );
+        assert_eq!(
+            ensure_order_by("SELECT a, b FROM t", "b DESC, a").unwrap(),
+            "SELECT a, b FROM t ORDER BY b DESC, a"
+        );
+        // An existing ORDER BY wins, and the text is kept as written
+        let sorted = "select a from t order by a desc";
+        assert_eq!(ensure_order_by(sorted, "b").unwrap(), sorted);
+        // Only the outermost query counts
+        assert_eq!(
+            ensure_order_by("SELECT * FROM (SELECT a FROM t ORDER BY a) s", "a").unwrap(),
+            "SELECT * FROM (SELECT a FROM t ORDER BY a) AS s ORDER BY a"
+        );
+
+        assert!(ensure_order_by("SELECT 1; SELECT 2", "1").is_err());
+        assert!(ensure_order_by("SELECT a FROM t", "a; DROP TABLE t").is_err());
+    }
+
+    #[test]
+    fn test_parse_csv_schemas_rejects_unknown_type() {
+        let err = parse_csv_schemas(r#"{"t": [{"name": "a", "type": "Integer"}]}"#).unwrap_err();
+        assert!(err.to_string().contains("t.a"));
+        assert!(parse_csv_schemas("[]").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_register_json() {
+        let path = std::env::temp_dir().join(format!(
+            "fusionlab_test_{}_events.ndjson",
+            std::process::id()
+        ));
+        std::fs::write(
+            &path,
+            concat!(
+                r#"{"id": 1, "level": "info", "ctx": {"user": "ana", "ms": 12}}"#,
+                "\n",
+                r#"{"id": 2, "level": "warn", "ctx": {"user": "bo", "ms": 250}}"#,
+                "\n",
+                r#"{"id": 3, "level": "info", "ctx": {"user": "ana", "ms": 7}}"#,
+                "\n",
+            ),
+        )
+        .unwrap();
+
+        let runner = DataFusionRunner::new();
+        let registered = runner.register_json("events", path.to_str().unwrap()).await;
+        let count = runner.run_query_collect("SELECT * FROM events").await;
+        let nested = runner
+            .run_query_collect("SELECT SUM(ctx['ms']) AS ms FROM events WHERE ctx['user'] = 'ana'")
+            .await;
+        std::fs::remove_file(&path).ok();
+
+        registered.unwrap();
+        assert_eq!(count.unwrap().row_count, 3);
+        let nested = nested.unwrap();
+        let ms = nested.batches[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap()
+            .value(0);
+        assert_eq!(ms, 19);
+    }
+
+    #[tokio::test]
+    async fn test_register_json_options() {
+        let path = std::env::temp_dir().join(format!(
+            "fusionlab_test_{}_options.ndjson",
+            std::process::id()
+        ));
+        std::fs::write(
+            &path,
+            "{\"id\": 1, \"tag\": \"a\"}\n\n{\"id\": 2, \"tag\": \"b\"}\n{\"id\": 3, \"tag\": }\n",
+        )
+        .unwrap();
+        let path_str = path.to_str().unwrap();
+
+        let runner = DataFusionRunner::new();
+        let malformed = runner.register_json("events", path_str).await;
+        // Inferring from the first record only never reaches the bad line
+        let shallow = runner
+            .register_json_with_options(
+                "first",
+                path_str,
+                NdJsonReadOptions::default().schema_infer_max_records(1),
+            )
+            .await;
+        let schema = Schema::new(vec![Field::new("id", DataType::Utf8, true)]);
+        let fixed = runner
+            .register_json_with_options(
+                "fixed",
+                path_str,
+                NdJsonReadOptions::default().schema(&schema),
+            )
+            .await;
+        let fixed_table = runner.context().table("fixed").await;
+        std::fs::remove_file(&path).ok();
+
+        match malformed {
+            Err(FusionLabError::Parse(message)) => {
+                assert!(message.starts_with(&format!("{}:4: malformed JSON: ", path_str)));
+                assert!(message.ends_with("at column 18"), "{}", message);
+            }
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+        shallow.unwrap();
+        fixed.unwrap();
+        let fixed_table = fixed_table.unwrap();
+        let fixed_schema = fixed_table.schema();
+        assert_eq!(fixed_schema.fields().len(), 1);
+        assert_eq!(fixed_schema.field(0).data_type(), &DataType::Utf8);
+    }
+
     #[tokio::test]
     async fn test_stream_mode() {
         let runner = DataFusionRunner::new();
@@ -537,16 +2165,88 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_ibd_table_provider() {
+    async fn test_batch_writer() {
         let runner = DataFusionRunner::new();
+        runner.register_ssb_sample().unwrap();
+        let sql = "SELECT c_custkey, c_name FROM customer ORDER BY c_custkey";
+        let expected = runner.run_query_collect(sql).await.unwrap();
+
+        // Write in slices of 4 rows so the writers see more than one batch
+        let write_sliced = |format| {
+            let mut writer = BatchWriter::new(Vec::new(), format);
+            for batch in &expected.batches {
+                for offset in (0..batch.num_rows()).step_by(4) {
+                    let len = 4.min(batch.num_rows() - offset);
+                    writer.write(&batch.slice(offset, len)).unwrap();
+                }
+            }
+            writer.finish(&expected).unwrap()
+        };
 
-        let ibd_path = "/home/cslog/mysql/percona-parser/tests/types_test.ibd";
-        let sdi_path = "/home/cslog/mysql/percona-parser/tests/types_test_sdi.json";
+        let csv = write_sliced(BatchFormat::Csv);
+        assert_eq!(String::from_utf8(csv).unwrap(), expected.to_csv().unwrap());
 
-        if !ibd_available() || !Path::new(ibd_path).exists() || !Path::new(sdi_path).exists() {
-            return;
+        let doc: serde_json::Value =
+            serde_json::from_slice(&write_sliced(BatchFormat::Json)).unwrap();
+        let expected_doc: serde_json::Value =
+            serde_json::from_str(&expected.to_json_document().unwrap()).unwrap();
+        assert_eq!(doc, expected_doc);
+
+        let mut writer = BatchWriter::new(Vec::new(), BatchFormat::Csv);
+        let result = runner
+            .run_query_stream_with(sql, |batch| writer.write(batch))
+            .await
+            .unwrap();
+        assert!(result.batches.is_empty());
+        assert_eq!(result.row_count, 30);
+        let csv = writer.finish(&result).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap(), expected.to_csv().unwrap());
+
+        // Empty results still get a header and an empty `rows`
+        let sql = "SELECT c_custkey FROM customer WHERE c_custkey < 0";
+        for (format, output) in [
+            (BatchFormat::Csv, "c_custkey\n"),
+            (
+                BatchFormat::Json,
+                "{\"columns\":[\"c_custkey\"],\"rows\":[],\"row_count\":0,",
+            ),
+        ] {
+            let mut writer = BatchWriter::new(Vec::new(), format);
+            let result = runner
+                .run_query_stream_with(sql, |batch| writer.write(batch))
+                .await
+                .unwrap();
+            let bytes = writer.finish(&result).unwrap();
+            assert!(String::from_utf8(bytes).unwrap().starts_with(output));
         }
 
+        let ipc = write_sliced(BatchFormat::Arrow);
+        let reader = FileReader::try_new(Cursor::new(ipc), None).unwrap();
+        let batches: Vec<RecordBatch> = reader.map(|batch| batch.unwrap()).collect();
+        assert!(batches.len() > 1);
+        assert_eq!(
+            pretty_format_batches(&batches).unwrap().to_string(),
+            expected.to_table()
+        );
+
+        // Errors from the callback stop the query
+        let err = runner
+            .run_query_stream_with("SELECT * FROM customer", |_| {
+                Err(FusionLabError::DataFusion("stop".to_string()))
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "DataFusion error: stop");
+    }
+
+    #[tokio::test]
+    async fn test_ibd_table_provider() {
+        let runner = DataFusionRunner::new();
+
+        let Some((ibd_path, sdi_path)) = types_fixture() else {
+            return;
+        };
+
         // Register the IBD table (table name is 'types_fixture' in SDI)
         runner.register_ibd(None, ibd_path, sdi_path).unwrap();
 
@@ -563,6 +2263,287 @@ mod tests {
         assert!(result.row_count > 0);
     }
 
+    #[tokio::test]
+    async fn test_ibd_open_count() {
+        let runner = DataFusionRunner::new();
+
+        let Some((ibd_path, sdi_path)) = types_fixture() else {
+            return;
+        };
+
+        runner.register_ibd(None, ibd_path, sdi_path).unwrap();
+        let provider = runner.ctx.table_provider("types_fixture").await.unwrap();
+        let provider = provider
+            .as_any()
+            .downcast_ref::<IbdTableProvider>()
+            .unwrap();
+        assert_eq!(provider.open_count(), 1);
+
+        // Planning alone does not open the file; the first scan reuses the
+        // table opened for the schema (previously 2 opens at this point)
+        runner.explain("SELECT * FROM types_fixture").await.unwrap();
+        let first = runner
+            .run_query_collect("SELECT * FROM types_fixture")
+            .await
+            .unwrap();
+        assert_eq!(provider.open_count(), 1);
+
+        // Later scans have to start over
+        let second = runner
+            .run_query_collect("SELECT * FROM types_fixture")
+            .await
+            .unwrap();
+        assert_eq!(provider.open_count(), 2);
+        assert_eq!(first.row_count, second.row_count);
+    }
+
+    #[tokio::test]
+    async fn test_count_ibd_rows() {
+        let runner = DataFusionRunner::new();
+
+        let Some((ibd_path, sdi_path)) = types_fixture() else {
+            return;
+        };
+
+        runner.register_ibd(None, ibd_path, sdi_path).unwrap();
+        let count = runner.count_ibd_rows("types_fixture").await.unwrap();
+        assert_eq!(count, 2);
+
+        // COUNT(*) is now answered from statistics, without a scan
+        let plan = runner
+            .explain_physical("SELECT COUNT(*) FROM types_fixture")
+            .await
+            .unwrap();
+        assert!(!plan.contains("IbdExec"), "{}", plan);
+        let result = runner
+            .run_query_collect("SELECT COUNT(*) AS n FROM types_fixture")
+            .await
+            .unwrap();
+        let n = result.batches[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap()
+            .value(0);
+        assert_eq!(n as u64, count);
+
+        runner.register_ssb_sample().unwrap();
+        assert!(runner.count_ibd_rows("lineorder").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ibd_date_predicate() {
+        let runner = DataFusionRunner::new();
+
+        let Some((ibd_path, sdi_path)) = types_fixture() else {
+            return;
+        };
+
+        runner.register_ibd(None, ibd_path, sdi_path).unwrap();
+
+        // Only row 1 (2024-12-31) is after the cutoff; row 2 is 2001-01-02
+        let result = runner
+            .run_query_collect("SELECT id, d, dt FROM types_fixture WHERE d > DATE '2024-01-01'")
+            .await
+            .unwrap();
+
+        assert_eq!(result.row_count, 1);
+        let schema = &result.schema;
+        assert_eq!(schema.field(1).data_type(), &DataType::Date32);
+        assert!(matches!(
+            schema.field(2).data_type(),
+            DataType::Timestamp(_, None)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_ibd_statistics_after_scan() {
+        use datafusion::common::stats::Precision;
+        use datafusion::common::ScalarValue;
+        use datafusion::physical_plan::displayable;
+
+        let runner = DataFusionRunner::new();
+
+        let Some((ibd_path, sdi_path)) = types_fixture() else {
+            return;
+        };
+
+        runner.register_ibd(None, ibd_path, sdi_path).unwrap();
+
+        let plan_with_statistics = || async {
+            let plan = runner
+                .context()
+                .sql("SELECT id, note FROM types_fixture")
+                .await
+                .unwrap()
+                .create_physical_plan()
+                .await
+                .unwrap();
+            let text = displayable(plan.as_ref())
+                .set_show_statistics(true)
+                .indent(true)
+                .to_string();
+            text
+        };
+
+        // Nothing is known before the first full scan
+        assert!(plan_with_statistics().await.contains("Rows=Absent"));
+
+        let result = runner
+            .run_query_collect("SELECT * FROM types_fixture")
+            .await
+            .unwrap();
+        let plan = plan_with_statistics().await;
+        assert!(
+            plan.contains(&format!("Rows=Exact({})", result.row_count)),
+            "{}",
+            plan
+        );
+        assert!(plan.contains("Null=Exact("), "{}", plan);
+
+        // The fixture's rows have ids 1 and 2
+        let provider = runner.ctx.table_provider("types_fixture").await.unwrap();
+        let id = &provider.statistics().unwrap().column_statistics[0];
+        assert_eq!(id.min_value, Precision::Exact(ScalarValue::Int64(Some(1))));
+        assert_eq!(id.max_value, Precision::Exact(ScalarValue::Int64(Some(2))));
+    }
+
+    #[tokio::test]
+    async fn test_ibd_partitioned_union() {
+        let runner = DataFusionRunner::new();
+
+        let Some((ibd_path, sdi_path)) = types_fixture() else {
+            return;
+        };
+
+        // The same file twice stands in for two partitions of one table
+        runner
+            .register_ibd(Some("single"), ibd_path, sdi_path)
+            .unwrap();
+        runner
+            .register_ibd_partitioned(
+                "partitioned",
+                &[PathBuf::from(ibd_path), PathBuf::from(ibd_path)],
+                Path::new(sdi_path),
+            )
+            .unwrap();
+
+        let single = runner
+            .run_query_collect("SELECT id FROM single")
+            .await
+            .unwrap();
+        let both = runner
+            .run_query_collect("SELECT id FROM partitioned")
+            .await
+            .unwrap();
+        assert!(single.row_count > 0);
+        assert_eq!(both.row_count, 2 * single.row_count);
+
+        let plan = runner
+            .explain_physical("SELECT id FROM partitioned")
+            .await
+            .unwrap();
+        assert!(plan.contains("partitions=2"), "{}", plan);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_run_query_collect_with_timeout() {
+        let runner = DataFusionRunner::new();
+        runner.register_ssb_sample().unwrap();
+
+        let result = runner
+            .run_query_collect_with_timeout(
+                "SELECT COUNT(*) FROM lineorder",
+                Duration::from_secs(30),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.row_count, 1);
+
+        // One batch per iteration, well past the deadline
+        let slow = "WITH RECURSIVE r AS (SELECT 1 AS n UNION ALL \
+                    SELECT n + 1 FROM r WHERE n < 20000) SELECT COUNT(*) FROM r";
+        let start = Instant::now();
+        let result = runner
+            .run_query_collect_with_timeout(slow, Duration::from_millis(100))
+            .await;
+        assert!(
+            matches!(result, Err(FusionLabError::Timeout { elapsed_ms: 100 })),
+            "{:?}",
+            result.map(|r| r.row_count)
+        );
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+
+    /// Open file descriptors pointing at `path` (Linux only)
+    fn open_fds_for(path: &Path) -> usize {
+        let Ok(entries) = std::fs::read_dir("/proc/self/fd") else {
+            return 0;
+        };
+        let target = std::fs::canonicalize(path).unwrap();
+        entries
+            .filter_map(|entry| std::fs::read_link(entry.ok()?.path()).ok())
+            .filter(|link| *link == target)
+            .count()
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_ibd_timeout_closes_reader() {
+        let runner = DataFusionRunner::new();
+
+        let Some((ibd_path, sdi_path)) = types_fixture() else {
+            return;
+        };
+
+        runner
+            .register_ibd(Some("types"), ibd_path, sdi_path)
+            .unwrap();
+        // Keep the scan open while the recursive side runs past the deadline
+        let slow = "WITH RECURSIVE r AS (SELECT 1 AS n UNION ALL \
+                    SELECT n + 1 FROM r WHERE n < 20000) \
+                    SELECT COUNT(*) FROM types CROSS JOIN r";
+        let result = runner
+            .run_query_collect_with_timeout(slow, Duration::from_millis(200))
+            .await;
+        assert!(matches!(result, Err(FusionLabError::Timeout { .. })));
+
+        // Blocking reads finish their current batch before the reader drops
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while open_fds_for(Path::new(ibd_path)) > 0 && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(open_fds_for(Path::new(ibd_path)), 0);
+    }
+
+    #[tokio::test]
+    async fn test_ibd_decimal_sum() {
+        let runner = DataFusionRunner::new();
+
+        let Some((ibd_path, sdi_path)) = types_fixture() else {
+            return;
+        };
+
+        runner.register_ibd(None, ibd_path, sdi_path).unwrap();
+
+        // amount is DECIMAL(10,2) holding 1234.56 and -0.99
+        let result = runner
+            .run_query_collect("SELECT SUM(amount) AS total FROM types_fixture")
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            result.schema.field(0).data_type(),
+            DataType::Decimal128(_, 2)
+        ));
+        let total = result.batches[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<datafusion::arrow::array::Decimal128Array>()
+            .unwrap()
+            .value(0);
+        assert_eq!(total, 123357);
+    }
+
     #[tokio::test]
     async fn test_ibd_multi_table_join() {
         let runner = DataFusionRunner::new();
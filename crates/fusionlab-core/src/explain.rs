@@ -0,0 +1,230 @@
+//! Structured MySQL EXPLAIN plans
+//!
+//! Parses `EXPLAIN FORMAT=JSON` output into typed structs. The JSON shape
+//! differs between MySQL 5.7 and 8.0 (and between query shapes), so only the
+//! commonly used fields are typed and everything else is kept in `other`.
+
+use serde::{Deserialize, Deserializer};
+use serde_json::{Map, Value};
+
+use crate::{FusionLabError, Result};
+
+/// A parsed `EXPLAIN FORMAT=JSON` plan
+#[derive(Debug, Clone)]
+pub struct MySQLExplainPlan {
+    /// Top-level query block
+    pub query_block: QueryBlock,
+    /// Every table access found anywhere in the plan, in plan order
+    pub tables: Vec<ExplainTable>,
+    /// The raw JSON returned by the server
+    pub raw_json: String,
+}
+
+/// The `query_block` object of an EXPLAIN plan
+#[derive(Debug, Clone, Deserialize)]
+pub struct QueryBlock {
+    pub select_id: Option<u64>,
+    pub cost_info: Option<CostInfo>,
+    /// Fields not modelled above (nested_loop, ordering_operation, ...)
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+/// Optimizer cost estimates
+#[derive(Debug, Clone, Deserialize)]
+pub struct CostInfo {
+    #[serde(default, deserialize_with = "lenient_f64")]
+    pub query_cost: Option<f64>,
+    #[serde(default, deserialize_with = "lenient_f64")]
+    pub read_cost: Option<f64>,
+    #[serde(default, deserialize_with = "lenient_f64")]
+    pub eval_cost: Option<f64>,
+    #[serde(default, deserialize_with = "lenient_f64")]
+    pub prefix_cost: Option<f64>,
+    pub data_read_per_join: Option<String>,
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+/// A single table access in the plan
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExplainTable {
+    pub table_name: String,
+    pub access_type: Option<String>,
+    pub possible_keys: Option<Vec<String>>,
+    pub key: Option<String>,
+    #[serde(default, deserialize_with = "lenient_u64")]
+    pub rows_examined_per_scan: Option<u64>,
+    #[serde(default, deserialize_with = "lenient_u64")]
+    pub rows_produced_per_join: Option<u64>,
+    #[serde(default, deserialize_with = "lenient_f64")]
+    pub filtered: Option<f64>,
+    pub cost_info: Option<CostInfo>,
+    pub attached_condition: Option<String>,
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+impl MySQLExplainPlan {
+    /// Parse the JSON document produced by `EXPLAIN FORMAT=JSON`
+    pub fn parse(json: &str) -> Result<Self> {
+        let root: Value = serde_json::from_str(json)
+            .map_err(|e| FusionLabError::Parse(format!("Invalid EXPLAIN JSON: {}", e)))?;
+
+        let block = root
+            .get("query_block")
+            .ok_or_else(|| FusionLabError::Parse("EXPLAIN JSON has no query_block".to_string()))?;
+
+        let query_block: QueryBlock = serde_json::from_value(block.clone())
+            .map_err(|e| FusionLabError::Parse(format!("Invalid query_block: {}", e)))?;
+
+        let mut tables = Vec::new();
+        collect_tables(block, &mut tables)?;
+
+        Ok(Self {
+            query_block,
+            tables,
+            raw_json: json.to_string(),
+        })
+    }
+
+    /// Estimated rows examined per scan for each table, in plan order
+    pub fn estimated_rows(&self) -> Vec<(&str, u64)> {
+        self.tables
+            .iter()
+            .filter_map(|t| {
+                t.rows_examined_per_scan
+                    .map(|rows| (t.table_name.as_str(), rows))
+            })
+            .collect()
+    }
+
+    /// Total estimated query cost, if reported
+    pub fn query_cost(&self) -> Option<f64> {
+        self.query_block
+            .cost_info
+            .as_ref()
+            .and_then(|c| c.query_cost)
+    }
+}
+
+/// Walk the plan and collect every `"table": { ... }` object
+fn collect_tables(value: &Value, out: &mut Vec<ExplainTable>) -> Result<()> {
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map {
+                if key == "table" && child.is_object() {
+                    let table: ExplainTable =
+                        serde_json::from_value(child.clone()).map_err(|e| {
+                            FusionLabError::Parse(format!("Invalid table entry: {}", e))
+                        })?;
+                    out.push(table);
+                }
+                collect_tables(child, out)?;
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                collect_tables(item, out)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// MySQL emits numbers as either JSON numbers or strings ("100.00")
+fn lenient_f64<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<f64>, D::Error> {
+    Ok(match Option::<Value>::deserialize(deserializer)? {
+        Some(Value::Number(n)) => n.as_f64(),
+        Some(Value::String(s)) => s.parse().ok(),
+        _ => None,
+    })
+}
+
+fn lenient_u64<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<u64>, D::Error> {
+    Ok(match Option::<Value>::deserialize(deserializer)? {
+        Some(Value::Number(n)) => n.as_u64(),
+        Some(Value::String(s)) => s.parse().ok(),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mysql80_nested_loop() {
+        let json = r#"{
+          "query_block": {
+            "select_id": 1,
+            "cost_info": { "query_cost": "12.50" },
+            "nested_loop": [
+              { "table": {
+                  "table_name": "c",
+                  "access_type": "ALL",
+                  "rows_examined_per_scan": 30,
+                  "rows_produced_per_join": 3,
+                  "filtered": "10.00",
+                  "cost_info": { "read_cost": "2.70", "eval_cost": "0.30", "prefix_cost": "3.25" },
+                  "used_columns": ["c_custkey", "c_region"],
+                  "attached_condition": "(`ssb`.`c`.`c_region` = 'ASIA')"
+              } },
+              { "table": {
+                  "table_name": "lo",
+                  "access_type": "ref",
+                  "possible_keys": ["idx_custkey"],
+                  "key": "idx_custkey",
+                  "rows_examined_per_scan": 4,
+                  "rows_produced_per_join": 12,
+                  "filtered": "100.00"
+              } }
+            ]
+          }
+        }"#;
+
+        let plan = MySQLExplainPlan::parse(json).unwrap();
+        assert_eq!(plan.query_block.select_id, Some(1));
+        assert_eq!(plan.query_cost(), Some(12.5));
+        assert_eq!(plan.tables.len(), 2);
+        assert_eq!(plan.tables[1].key.as_deref(), Some("idx_custkey"));
+        assert_eq!(plan.tables[0].filtered, Some(10.0));
+        assert!(plan.tables[0].other.contains_key("used_columns"));
+        assert_eq!(plan.estimated_rows(), vec![("c", 30), ("lo", 4)]);
+    }
+
+    #[test]
+    fn test_parse_mysql57_single_table() {
+        // 5.7 nests tables under ordering/grouping operations and has fewer fields
+        let json = r#"{
+          "query_block": {
+            "select_id": 1,
+            "ordering_operation": {
+              "using_filesort": true,
+              "table": {
+                "table_name": "lineorder",
+                "access_type": "ALL",
+                "rows_examined_per_scan": 6001215,
+                "filtered": 100
+              }
+            }
+          }
+        }"#;
+
+        let plan = MySQLExplainPlan::parse(json).unwrap();
+        assert_eq!(plan.query_cost(), None);
+        assert_eq!(plan.estimated_rows(), vec![("lineorder", 6001215)]);
+        assert!(plan.query_block.other.contains_key("ordering_operation"));
+    }
+
+    #[test]
+    fn test_parse_invalid_json() {
+        assert!(MySQLExplainPlan::parse("not json").is_err());
+        assert!(MySQLExplainPlan::parse("{}").is_err());
+    }
+}
@@ -4,17 +4,23 @@
 
 use async_trait::async_trait;
 use datafusion::arrow::array::{
-    ArrayRef, Float64Array, Int64Array, RecordBatch, StringArray, UInt64Array,
+    ArrayRef, BinaryArray, Date32Array, Decimal128Array, Float64Array, Int64Array, RecordBatch,
+    StringArray, Time64MicrosecondArray, TimestampMicrosecondArray, UInt64Array,
+};
+use datafusion::arrow::compute::kernels::cast_utils::parse_decimal;
+use datafusion::arrow::datatypes::{
+    DataType, Decimal128Type, Field, Schema, SchemaRef, TimeUnit, DECIMAL128_MAX_PRECISION,
 };
-use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
 use datafusion::catalog::Session;
+use datafusion::common::stats::Precision;
+use datafusion::common::{ColumnStatistics, ScalarValue, Statistics};
 use datafusion::datasource::{TableProvider, TableType};
 use datafusion::error::Result as DfResult;
 use datafusion::execution::context::TaskContext;
-use datafusion::logical_expr::{Expr, TableProviderFilterPushDown};
+use datafusion::logical_expr::{BinaryExpr, Expr, Operator, TableProviderFilterPushDown};
 use datafusion::physical_expr::EquivalenceProperties;
-use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
 use datafusion::physical_plan::execution_plan::{Boundedness, EmissionType};
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
 use datafusion::physical_plan::{
     DisplayAs, DisplayFormatType, ExecutionPlan, Partitioning, PlanProperties,
     SendableRecordBatchStream,
@@ -23,23 +29,189 @@ use futures::stream;
 use std::any::Any;
 use std::fmt::{self, Debug, Formatter};
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
-use fusionlab_ibd::{ColumnType, ColumnValue, IbdReader};
+use fusionlab_ibd::{
+    ColumnInfo, ColumnStats, ColumnType, ColumnValue, IbdError, IbdReader, IbdRow, IbdTable,
+    RowFilter,
+};
+
+use crate::mysql_arrow::{cell_to_date32, cell_to_time64_micros, cell_to_timestamp_micros};
+use crate::value::{parse_date, parse_datetime, parse_time};
 
 /// Configuration for an InnoDB table
 #[derive(Debug, Clone)]
 pub struct IbdTableConfig {
-    pub ibd_path: PathBuf,
+    /// One file per partition; unpartitioned tables have a single file
+    pub ibd_paths: Vec<PathBuf>,
     pub sdi_path: PathBuf,
     pub table_name: String,
 }
 
+impl IbdTableConfig {
+    /// Open the file of one partition
+    fn open(&self, partition: usize) -> Result<OpenTable, IbdError> {
+        OpenTable::open(&self.ibd_paths[partition], &self.sdi_path)
+    }
+}
+
+/// A table with the reader that opened it
+///
+/// Each open table gets its own reader, so partitions can be read on
+/// different threads.
+struct OpenTable {
+    table: IbdTable,
+    // Declared after `table` so the table is closed first
+    _reader: IbdReader,
+}
+
+impl OpenTable {
+    /// Open a table file
+    fn open(ibd_path: &Path, sdi_path: &Path) -> Result<Self, IbdError> {
+        let reader = IbdReader::new()?;
+        let table = reader.open_table(ibd_path, sdi_path)?;
+        Ok(Self {
+            table,
+            _reader: reader,
+        })
+    }
+}
+
+/// Table handles shared by a provider and its scans
+///
+/// Building the provider has to open every partition file to learn the
+/// schema. Those tables are kept and handed to the first scan of each
+/// partition, so a table that is registered and queried once opens each file
+/// once instead of twice. Tables are forward-only cursors, so later scans
+/// open the file again.
+struct TableHandles {
+    /// Per partition; taken by the first scan
+    prepared: Mutex<Vec<Option<OpenTable>>>,
+    /// Files opened so far, by the provider and all of its scans
+    opens: AtomicUsize,
+}
+
+impl TableHandles {
+    fn new(prepared: Vec<OpenTable>) -> Self {
+        Self {
+            opens: AtomicUsize::new(prepared.len()),
+            prepared: Mutex::new(prepared.into_iter().map(Some).collect()),
+        }
+    }
+
+    /// The table prepared for `partition`, or a newly opened one once that
+    /// has been used
+    fn take_or_open(
+        &self,
+        config: &IbdTableConfig,
+        partition: usize,
+    ) -> Result<OpenTable, IbdError> {
+        if let Some(table) = self.prepared.lock().unwrap()[partition].take() {
+            return Ok(table);
+        }
+        self.opens.fetch_add(1, Ordering::Relaxed);
+        config.open(partition)
+    }
+}
+
+impl Debug for TableHandles {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TableHandles")
+            .field("opens", &self.opens.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+/// Statistics learned from completed scans, shared by all scans of a table
+///
+/// Nothing is known until every partition has been read to the end, either
+/// by a scan or by `IbdTableProvider::count_rows`; null counts are known for
+/// the columns full scans projected, and min/max for the numeric ones among
+/// them.
+#[derive(Debug)]
+struct ScanStatistics {
+    /// Per partition
+    num_rows: Vec<Option<usize>>,
+    /// Per partition, then per table column
+    null_counts: Vec<Vec<Option<usize>>>,
+    /// Per partition, then per table column
+    ranges: Vec<Vec<Option<ColumnRange>>>,
+}
+
+/// Min and max of a column, or `None` if it held only NULLs
+type ColumnRange = Option<(ScalarValue, ScalarValue)>;
+
+impl ScanStatistics {
+    fn new(partition_count: usize, column_count: usize) -> Self {
+        Self {
+            num_rows: vec![None; partition_count],
+            null_counts: vec![vec![None; column_count]; partition_count],
+            ranges: vec![vec![None; column_count]; partition_count],
+        }
+    }
+
+    /// Range of `column` across all partitions, if every partition's is known
+    fn column_range(&self, column: usize) -> ColumnRange {
+        let mut range: ColumnRange = None;
+        for partition in &self.ranges {
+            let Some((min, max)) = partition[column].as_ref()? else {
+                continue;
+            };
+            range = Some(match range {
+                Some((lo, hi)) => (
+                    if *min < lo { min.clone() } else { lo },
+                    if *max > hi { max.clone() } else { hi },
+                ),
+                None => (min.clone(), max.clone()),
+            });
+        }
+        range
+    }
+
+    fn to_statistics(&self) -> Statistics {
+        let column_count = self.null_counts.first().map_or(0, Vec::len);
+        Statistics {
+            num_rows: sum_partitions(self.num_rows.iter().copied()),
+            total_byte_size: Precision::Absent,
+            column_statistics: (0..column_count)
+                .map(|column| {
+                    let (min_value, max_value) = match self.column_range(column) {
+                        Some((min, max)) => (Precision::Exact(min), Precision::Exact(max)),
+                        None => (Precision::Absent, Precision::Absent),
+                    };
+                    ColumnStatistics {
+                        null_count: sum_partitions(
+                            self.null_counts.iter().map(|partition| partition[column]),
+                        ),
+                        min_value,
+                        max_value,
+                        ..ColumnStatistics::new_unknown()
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Exact total if every partition's value is known
+fn sum_partitions(values: impl Iterator<Item = Option<usize>>) -> Precision<usize> {
+    values
+        .sum::<Option<usize>>()
+        .map_or(Precision::Absent, Precision::Exact)
+}
+
+type SharedStatistics = Arc<Mutex<ScanStatistics>>;
+
 /// TableProvider for InnoDB .ibd files
 pub struct IbdTableProvider {
     config: IbdTableConfig,
     schema: SchemaRef,
-    column_mapping: Vec<(String, ColumnType, usize)>, // (name, type, ibd_index)
+    column_mapping: Vec<(String, DataType, usize)>, // (name, arrow type, ibd_index)
+    /// Integer column leading the clustered index: (name, ibd_index)
+    index_column: Option<(String, usize)>,
+    statistics: SharedStatistics,
+    tables: Arc<TableHandles>,
 }
 
 impl Debug for IbdTableProvider {
@@ -57,43 +229,65 @@ impl IbdTableProvider {
         ibd_path: P,
         sdi_path: Q,
     ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-        let reader = IbdReader::new()?;
-        let table = reader.open_table(ibd_path.as_ref(), sdi_path.as_ref())?;
+        Self::try_new_partitioned(&[ibd_path.as_ref().to_path_buf()], sdi_path)
+    }
 
-        let table_name = table.name().to_string();
-        let columns = table.columns();
-
-        // Build Arrow schema from IBD column info
-        // Note: The C API skips internal columns (DB_TRX_ID, DB_ROLL_PTR) in row data,
-        // so we track the sequential row index, not the SDI column index.
-        let mut fields = Vec::new();
-        let mut column_mapping = Vec::new();
-        let mut row_idx: usize = 0;
-
-        for col in columns {
-            // Skip internal columns (DB_TRX_ID, DB_ROLL_PTR)
-            if col.col_type == ColumnType::Internal {
-                continue;
-            }
+    /// Create a provider over the partition files of one table
+    ///
+    /// Every file is opened with the same SDI and must yield the same schema.
+    /// Each file becomes one DataFusion partition, so partitions are scanned
+    /// in parallel.
+    pub fn try_new_partitioned<Q: AsRef<Path>>(
+        ibd_paths: &[PathBuf],
+        sdi_path: Q,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let sdi_path = sdi_path.as_ref();
+        let (first, rest) = ibd_paths
+            .split_first()
+            .ok_or("at least one .ibd file is required")?;
 
-            let arrow_type = ibd_to_arrow_type(col.col_type);
-            let nullable = true; // Conservative - assume all columns can be NULL
+        let first_table = OpenTable::open(first, sdi_path)?;
+        let table = &first_table.table;
+        let table_name = table.name().to_string();
+        let (schema, column_mapping) = table_schema(table.columns());
+        let index_column = table.index_column().and_then(|name| {
+            column_mapping
+                .iter()
+                .find(|(column, data_type, _)| {
+                    column == name && matches!(data_type, DataType::Int64 | DataType::UInt64)
+                })
+                .map(|(column, _, ibd_idx)| (column.clone(), *ibd_idx))
+        });
 
-            fields.push(Field::new(&col.name, arrow_type, nullable));
-            column_mapping.push((col.name.clone(), col.col_type, row_idx));
-            row_idx += 1;
+        let mut prepared = vec![first_table];
+        for path in rest {
+            let partition = OpenTable::open(path, sdi_path)?;
+            let (partition_schema, _) = table_schema(partition.table.columns());
+            if partition_schema != schema {
+                return Err(format!(
+                    "partition {} does not match the schema of {}",
+                    path.display(),
+                    first.display()
+                )
+                .into());
+            }
+            prepared.push(partition);
         }
 
-        let schema = Arc::new(Schema::new(fields));
-
         Ok(Self {
             config: IbdTableConfig {
-                ibd_path: ibd_path.as_ref().to_path_buf(),
-                sdi_path: sdi_path.as_ref().to_path_buf(),
+                ibd_paths: ibd_paths.to_vec(),
+                sdi_path: sdi_path.to_path_buf(),
                 table_name,
             },
+            statistics: Arc::new(Mutex::new(ScanStatistics::new(
+                ibd_paths.len(),
+                column_mapping.len(),
+            ))),
             schema,
             column_mapping,
+            index_column,
+            tables: Arc::new(TableHandles::new(prepared)),
         })
     }
 
@@ -101,26 +295,111 @@ impl IbdTableProvider {
     pub fn table_name(&self) -> &str {
         &self.config.table_name
     }
+
+    /// Count the rows of every partition without building record batches
+    ///
+    /// Rows are walked by the reader but never decoded, which is much cheaper
+    /// than a scan. The counts are kept as exact `num_rows` statistics, so
+    /// later plans see them: DataFusion then answers a bare
+    /// `SELECT COUNT(*) FROM t` (no `WHERE`, `GROUP BY` or join) from the
+    /// statistics without scanning the table at all. Like a scan, this uses
+    /// up the tables opened when the provider was built.
+    pub fn count_rows(&self) -> Result<u64, IbdError> {
+        let mut total = 0;
+        for partition in 0..self.config.ibd_paths.len() {
+            let mut table = self.tables.take_or_open(&self.config, partition)?;
+            let count = table.table.count_rows()?;
+            self.statistics.lock().unwrap().num_rows[partition] = Some(count as usize);
+            total += count;
+        }
+        Ok(total)
+    }
+
+    /// How many times partition files have been opened, counting the opens
+    /// needed to build the provider
+    ///
+    /// Registering a table with N partitions opens N files; the first scan
+    /// reuses them and every later scan opens N more.
+    pub fn open_count(&self) -> usize {
+        self.tables.opens.load(Ordering::Relaxed)
+    }
 }
 
 const DEFAULT_BATCH_SIZE: usize = 1024;
 
-fn ibd_to_arrow_type(ibd_type: ColumnType) -> DataType {
-    match ibd_type {
+/// The literal of a `column = <integer>` predicate, in either operand order
+fn int_eq_value(expr: &Expr, column: &str) -> Option<i64> {
+    let Expr::BinaryExpr(BinaryExpr {
+        left,
+        op: Operator::Eq,
+        right,
+    }) = expr
+    else {
+        return None;
+    };
+    let (col, value) = match (left.as_ref(), right.as_ref()) {
+        (Expr::Column(col), Expr::Literal(value)) | (Expr::Literal(value), Expr::Column(col)) => {
+            (col, value)
+        }
+        _ => return None,
+    };
+    if col.name != column || !value.data_type().is_integer() {
+        return None;
+    }
+    match value.cast_to(&DataType::Int64).ok()? {
+        ScalarValue::Int64(value) => value,
+        _ => None,
+    }
+}
+
+/// Arrow schema and (name, arrow type, ibd_index) mapping for IBD columns
+fn table_schema(columns: &[ColumnInfo]) -> (SchemaRef, Vec<(String, DataType, usize)>) {
+    // Note: The C API skips internal columns (DB_TRX_ID, DB_ROLL_PTR) in row data,
+    // so we track the sequential row index, not the SDI column index.
+    let mut fields = Vec::new();
+    let mut column_mapping = Vec::new();
+    let mut row_idx: usize = 0;
+
+    for col in columns {
+        // Skip internal columns (DB_TRX_ID, DB_ROLL_PTR)
+        if col.col_type == ColumnType::Internal {
+            continue;
+        }
+
+        let arrow_type = ibd_to_arrow_type(col);
+        let nullable = true; // Conservative - assume all columns can be NULL
+
+        fields.push(Field::new(&col.name, arrow_type.clone(), nullable));
+        column_mapping.push((col.name.clone(), arrow_type, row_idx));
+        row_idx += 1;
+    }
+
+    (Arc::new(Schema::new(fields)), column_mapping)
+}
+
+fn ibd_to_arrow_type(col: &ColumnInfo) -> DataType {
+    match col.col_type {
         ColumnType::Int => DataType::Int64,
         ColumnType::UInt => DataType::UInt64,
         ColumnType::Float | ColumnType::Double => DataType::Float64,
+        ColumnType::Date => DataType::Date32,
+        ColumnType::DateTime | ColumnType::Timestamp => {
+            DataType::Timestamp(TimeUnit::Microsecond, None)
+        }
+        ColumnType::Time => DataType::Time64(TimeUnit::Microsecond),
+        ColumnType::Decimal => match (col.precision, col.scale) {
+            (Some(precision), Some(scale))
+                if (1..=DECIMAL128_MAX_PRECISION).contains(&precision) =>
+            {
+                DataType::Decimal128(precision, scale)
+            }
+            // Without precision/scale from the SDI, keep the exact text
+            _ => DataType::Utf8,
+        },
+        // Raw bytes, not a hex string
+        ColumnType::Binary => DataType::Binary,
         // All other types stored as formatted strings for simplicity
-        // TODO: Parse temporal types to native Arrow Date32/Timestamp for better performance
-        ColumnType::String
-        | ColumnType::Binary
-        | ColumnType::DateTime
-        | ColumnType::Timestamp
-        | ColumnType::Date
-        | ColumnType::Time
-        | ColumnType::Decimal
-        | ColumnType::Null
-        | ColumnType::Internal => DataType::Utf8,
+        ColumnType::String | ColumnType::Null | ColumnType::Internal => DataType::Utf8,
     }
 }
 
@@ -142,25 +421,48 @@ impl TableProvider for IbdTableProvider {
         &self,
         filters: &[&Expr],
     ) -> DfResult<Vec<TableProviderFilterPushDown>> {
-        // No filter pushdown support yet
+        // Equality on the leading index column is pushed into the reader. Kept
+        // Inexact so DataFusion still applies the filter: the reader skips rows
+        // rather than seeking, and range predicates are not pushed yet.
         Ok(filters
             .iter()
-            .map(|_| TableProviderFilterPushDown::Unsupported)
+            .map(|filter| match &self.index_column {
+                Some((name, _)) if int_eq_value(filter, name).is_some() => {
+                    TableProviderFilterPushDown::Inexact
+                }
+                _ => TableProviderFilterPushDown::Unsupported,
+            })
             .collect())
     }
 
+    fn statistics(&self) -> Option<Statistics> {
+        Some(self.statistics.lock().unwrap().to_statistics())
+    }
+
     async fn scan(
         &self,
         _state: &dyn Session,
         projection: Option<&Vec<usize>>,
-        _filters: &[Expr],
+        filters: &[Expr],
         _limit: Option<usize>,
     ) -> DfResult<Arc<dyn ExecutionPlan>> {
+        let filter = self.index_column.as_ref().and_then(|(name, ibd_idx)| {
+            filters
+                .iter()
+                .find_map(|filter| int_eq_value(filter, name))
+                .map(|value| RowFilter::IntEq {
+                    column: *ibd_idx as u32,
+                    value,
+                })
+        });
         Ok(Arc::new(IbdExec::new(
             self.config.clone(),
             self.schema.clone(),
             self.column_mapping.clone(),
             projection.cloned(),
+            filter,
+            self.statistics.clone(),
+            self.tables.clone(),
         )))
     }
 }
@@ -169,18 +471,24 @@ impl TableProvider for IbdTableProvider {
 #[derive(Debug)]
 struct IbdExec {
     config: IbdTableConfig,
-    column_mapping: Vec<(String, ColumnType, usize)>,
+    column_mapping: Vec<(String, DataType, usize)>,
     projection: Option<Vec<usize>>,
+    filter: Option<RowFilter>,
     projected_schema: SchemaRef,
     properties: PlanProperties,
+    statistics: SharedStatistics,
+    tables: Arc<TableHandles>,
 }
 
 impl IbdExec {
     fn new(
         config: IbdTableConfig,
         schema: SchemaRef,
-        column_mapping: Vec<(String, ColumnType, usize)>,
+        column_mapping: Vec<(String, DataType, usize)>,
         projection: Option<Vec<usize>>,
+        filter: Option<RowFilter>,
+        statistics: SharedStatistics,
+        tables: Arc<TableHandles>,
     ) -> Self {
         let projected_schema = match &projection {
             Some(indices) => Arc::new(schema.project(indices).unwrap()),
@@ -189,7 +497,7 @@ impl IbdExec {
 
         let properties = PlanProperties::new(
             EquivalenceProperties::new(projected_schema.clone()),
-            Partitioning::UnknownPartitioning(1),
+            Partitioning::UnknownPartitioning(config.ibd_paths.len()),
             EmissionType::Final,
             Boundedness::Bounded,
         );
@@ -198,8 +506,11 @@ impl IbdExec {
             config,
             column_mapping,
             projection,
+            filter,
             projected_schema,
             properties,
+            statistics,
+            tables,
         }
     }
 }
@@ -208,9 +519,15 @@ impl DisplayAs for IbdExec {
     fn fmt_as(&self, _t: DisplayFormatType, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "IbdExec: table={}, projection={:?}",
-            self.config.table_name, self.projection
-        )
+            "IbdExec: table={}, partitions={}, projection={:?}",
+            self.config.table_name,
+            self.config.ibd_paths.len(),
+            self.projection
+        )?;
+        if let Some(filter) = &self.filter {
+            write!(f, ", filter={:?}", filter)?;
+        }
+        Ok(())
     }
 }
 
@@ -242,28 +559,52 @@ impl ExecutionPlan for IbdExec {
         Ok(self)
     }
 
+    fn statistics(&self) -> DfResult<Statistics> {
+        let statistics = self.statistics.lock().unwrap().to_statistics();
+        Ok(statistics.project(self.projection.as_ref()))
+    }
+
     fn execute(
         &self,
-        _partition: usize,
+        partition: usize,
         _context: Arc<TaskContext>,
     ) -> DfResult<SendableRecordBatchStream> {
+        if partition >= self.config.ibd_paths.len() {
+            return Err(datafusion::error::DataFusionError::Internal(format!(
+                "IbdExec has {} partitions, partition {} requested",
+                self.config.ibd_paths.len(),
+                partition
+            )));
+        }
         let config = self.config.clone();
         let column_mapping = self.column_mapping.clone();
         let projection = self.projection.clone();
         let schema = self.projected_schema.clone();
 
-        let state = IbdStreamState::try_new(
-            &config,
+        let table = self
+            .tables
+            .take_or_open(&config, partition)
+            .map_err(|e| datafusion::error::DataFusionError::External(Box::new(e)))?;
+        let state = IbdStreamState::new(
+            table,
+            partition,
             &column_mapping,
             projection.as_ref(),
+            self.filter,
             schema.clone(),
-        )
-            .map_err(datafusion::error::DataFusionError::External)?;
+            self.statistics.clone(),
+        );
 
+        // Reads go through blocking FFI calls; run them off the async workers so
+        // partitions are decoded in parallel
         let stream = stream::try_unfold(state, |mut state| async move {
-            let batch = state
-                .read_next_batch()
-                .map_err(datafusion::error::DataFusionError::External)?;
+            let (state, batch) = tokio::task::spawn_blocking(move || {
+                let batch = state.read_next_batch();
+                (state, batch)
+            })
+            .await
+            .map_err(|e| datafusion::error::DataFusionError::External(Box::new(e)))?;
+            let batch = batch.map_err(datafusion::error::DataFusionError::External)?;
             Ok(batch.map(|b| (b, state)))
         });
         Ok(Box::pin(RecordBatchStreamAdapter::new(schema, stream)))
@@ -271,30 +612,84 @@ impl ExecutionPlan for IbdExec {
 }
 
 struct ProjectedColumn {
-    col_type: ColumnType,
+    data_type: DataType,
     ibd_index: u32,
 }
 
+/// Per-column access to a row; each `get` is one FFI call for `IbdRow`
+trait RowValues {
+    fn get(&self, index: u32) -> Result<ColumnValue, IbdError>;
+}
+
+impl RowValues for IbdRow {
+    fn get(&self, index: u32) -> Result<ColumnValue, IbdError> {
+        IbdRow::get(self, index)
+    }
+}
+
+/// Append one row to the builders, reading only the projected columns
+///
+/// `stats` has an entry per projected column; the tracked ones are updated
+/// from the values read for the builders, so they cost no extra read.
+fn append_row<R: RowValues>(
+    row: &R,
+    columns: &[ProjectedColumn],
+    builders: &mut [ColumnBuilder],
+    stats: &mut [Option<ColumnStats>],
+) -> Result<(), IbdError> {
+    for ((builder, col), stats) in builders.iter_mut().zip(columns).zip(stats) {
+        let value = row.get(col.ibd_index)?;
+        if let Some(stats) = stats {
+            stats.update(&value);
+        }
+        builder.push(value)?;
+    }
+    Ok(())
+}
+
 enum ColumnBuilder {
     Int(Vec<Option<i64>>),
     UInt(Vec<Option<u64>>),
     Float(Vec<Option<f64>>),
+    /// Days since the epoch
+    Date32(Vec<Option<i32>>),
+    /// Microseconds since the epoch
+    Timestamp(Vec<Option<i64>>),
+    /// Microseconds since midnight
+    Time64(Vec<Option<i64>>),
+    /// Unscaled decimal values
+    Decimal128 {
+        values: Vec<Option<i128>>,
+        precision: u8,
+        scale: i8,
+    },
     String(Vec<Option<String>>),
+    Binary(Vec<Option<Vec<u8>>>),
 }
 
 impl ColumnBuilder {
-    fn with_capacity(col_type: ColumnType, capacity: usize) -> Self {
-        match col_type {
-            ColumnType::Int => ColumnBuilder::Int(Vec::with_capacity(capacity)),
-            ColumnType::UInt => ColumnBuilder::UInt(Vec::with_capacity(capacity)),
-            ColumnType::Float | ColumnType::Double => {
-                ColumnBuilder::Float(Vec::with_capacity(capacity))
-            }
+    /// Builder for a column of the given Arrow type (from `ibd_to_arrow_type`)
+    fn with_capacity(data_type: &DataType, capacity: usize) -> Self {
+        match data_type {
+            DataType::Int64 => ColumnBuilder::Int(Vec::with_capacity(capacity)),
+            DataType::UInt64 => ColumnBuilder::UInt(Vec::with_capacity(capacity)),
+            DataType::Float64 => ColumnBuilder::Float(Vec::with_capacity(capacity)),
+            DataType::Date32 => ColumnBuilder::Date32(Vec::with_capacity(capacity)),
+            DataType::Timestamp(_, _) => ColumnBuilder::Timestamp(Vec::with_capacity(capacity)),
+            DataType::Time64(_) => ColumnBuilder::Time64(Vec::with_capacity(capacity)),
+            DataType::Decimal128(precision, scale) => ColumnBuilder::Decimal128 {
+                values: Vec::with_capacity(capacity),
+                precision: *precision,
+                scale: *scale,
+            },
+            DataType::Binary => ColumnBuilder::Binary(Vec::with_capacity(capacity)),
             _ => ColumnBuilder::String(Vec::with_capacity(capacity)),
         }
     }
 
-    fn push(&mut self, value: ColumnValue) {
+    /// Append one value; fails only for a TIME outside a single day, which
+    /// `Time64` cannot hold
+    fn push(&mut self, value: ColumnValue) -> Result<(), IbdError> {
         match self {
             ColumnBuilder::Int(values) => {
                 let parsed = match value {
@@ -323,6 +718,48 @@ impl ColumnBuilder {
                 };
                 values.push(parsed);
             }
+            // Temporal values arrive formatted; unparseable ones become NULL
+            ColumnBuilder::Date32(values) => {
+                let parsed = match value {
+                    ColumnValue::Formatted(s) => parse_date(&s).and_then(|c| cell_to_date32(&c)),
+                    _ => None,
+                };
+                values.push(parsed);
+            }
+            ColumnBuilder::Timestamp(values) => {
+                let parsed = match value {
+                    ColumnValue::Formatted(s) => {
+                        parse_datetime(&s).and_then(|c| cell_to_timestamp_micros(&c))
+                    }
+                    _ => None,
+                };
+                values.push(parsed);
+            }
+            ColumnBuilder::Time64(values) => {
+                let parsed = match value {
+                    ColumnValue::Formatted(s) => match parse_time(&s) {
+                        Some(cell) => cell_to_time64_micros(&cell)
+                            .map_err(|e| IbdError::InvalidFormat(e.to_string()))?,
+                        None => None,
+                    },
+                    _ => None,
+                };
+                values.push(parsed);
+            }
+            ColumnBuilder::Decimal128 {
+                values,
+                precision,
+                scale,
+            } => {
+                let parsed = match value {
+                    ColumnValue::Formatted(s) | ColumnValue::String(s) => {
+                        parse_decimal::<Decimal128Type>(s.trim(), *precision, *scale).ok()
+                    }
+                    ColumnValue::Int(v) => Some(v as i128 * 10i128.pow(*scale as u32)),
+                    _ => None,
+                };
+                values.push(parsed);
+            }
             ColumnBuilder::String(values) => {
                 let parsed = match value {
                     ColumnValue::Null => None,
@@ -330,7 +767,16 @@ impl ColumnBuilder {
                 };
                 values.push(parsed);
             }
+            ColumnBuilder::Binary(values) => {
+                let parsed = match value {
+                    ColumnValue::Null => None,
+                    ColumnValue::Binary(bytes) => Some(bytes),
+                    v => Some(v.as_string().into_bytes()),
+                };
+                values.push(parsed);
+            }
         }
+        Ok(())
     }
 
     fn finish(self) -> ArrayRef {
@@ -338,52 +784,96 @@ impl ColumnBuilder {
             ColumnBuilder::Int(values) => Arc::new(Int64Array::from(values)),
             ColumnBuilder::UInt(values) => Arc::new(UInt64Array::from(values)),
             ColumnBuilder::Float(values) => Arc::new(Float64Array::from(values)),
+            ColumnBuilder::Date32(values) => Arc::new(Date32Array::from(values)),
+            ColumnBuilder::Timestamp(values) => Arc::new(TimestampMicrosecondArray::from(values)),
+            ColumnBuilder::Time64(values) => Arc::new(Time64MicrosecondArray::from(values)),
+            ColumnBuilder::Decimal128 {
+                values,
+                precision,
+                scale,
+            } => Arc::new(
+                Decimal128Array::from(values)
+                    .with_precision_and_scale(precision, scale)
+                    .expect("precision and scale validated by ibd_to_arrow_type"),
+            ),
             ColumnBuilder::String(values) => Arc::new(StringArray::from(values)),
+            ColumnBuilder::Binary(values) => Arc::new(BinaryArray::from_iter(values)),
         }
     }
 }
 
 struct IbdStreamState {
-    table: fusionlab_ibd::IbdTable,
+    table: OpenTable,
+    partition: usize,
     projected_columns: Vec<ProjectedColumn>,
+    /// Table column index of each projected column
+    table_indices: Vec<usize>,
     schema: SchemaRef,
     batch_size: usize,
     done: bool,
+    rows_read: usize,
+    /// Nulls seen so far, per projected column
+    null_counts: Vec<usize>,
+    /// Min/max so far, per projected column; `None` for columns without
+    /// numeric ranges, and for all of them in filtered scans
+    column_stats: Vec<Option<ColumnStats>>,
+    /// Filtered scans see a subset of rows and do not publish statistics
+    filtered: bool,
+    statistics: SharedStatistics,
 }
 
 impl IbdStreamState {
-    fn try_new(
-        config: &IbdTableConfig,
-        column_mapping: &[(String, ColumnType, usize)],
+    fn new(
+        mut table: OpenTable,
+        partition: usize,
+        column_mapping: &[(String, DataType, usize)],
         projection: Option<&Vec<usize>>,
+        filter: Option<RowFilter>,
         schema: SchemaRef,
-    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-        let reader = IbdReader::new()?;
-        let table = reader.open_table(&config.ibd_path, &config.sdi_path)?;
+        statistics: SharedStatistics,
+    ) -> Self {
+        table.table.set_filter(filter);
 
         let indices: Vec<usize> = match projection {
             Some(proj) => proj.clone(),
             None => (0..column_mapping.len()).collect(),
         };
 
-        let projected_columns = indices
-            .into_iter()
-            .map(|idx| {
-                let (_, col_type, ibd_idx) = &column_mapping[idx];
+        let projected_columns: Vec<ProjectedColumn> = indices
+            .iter()
+            .map(|&idx| {
+                let (_, data_type, ibd_idx) = &column_mapping[idx];
                 ProjectedColumn {
-                    col_type: *col_type,
+                    data_type: data_type.clone(),
                     ibd_index: *ibd_idx as u32,
                 }
             })
             .collect();
+        let column_stats = projected_columns
+            .iter()
+            .map(|c| {
+                let numeric = matches!(
+                    c.data_type,
+                    DataType::Int64 | DataType::UInt64 | DataType::Float64
+                );
+                (filter.is_none() && numeric).then(|| ColumnStats::new(c.ibd_index))
+            })
+            .collect();
 
-        Ok(Self {
+        Self {
             table,
+            partition,
+            null_counts: vec![0; projected_columns.len()],
+            column_stats,
             projected_columns,
+            table_indices: indices,
             schema,
             batch_size: DEFAULT_BATCH_SIZE,
             done: false,
-        })
+            rows_read: 0,
+            filtered: filter.is_some(),
+            statistics,
+        }
     }
 
     fn read_next_batch(
@@ -396,33 +886,274 @@ impl IbdStreamState {
         let mut builders: Vec<ColumnBuilder> = self
             .projected_columns
             .iter()
-            .map(|col| ColumnBuilder::with_capacity(col.col_type, self.batch_size))
+            .map(|col| ColumnBuilder::with_capacity(&col.data_type, self.batch_size))
             .collect();
 
-        let mut rows_read = 0usize;
+        let rows = self.table.table.next_rows(self.batch_size)?;
+        if rows.len() < self.batch_size {
+            self.done = true;
+        }
 
-        while rows_read < self.batch_size {
-            match self.table.next_row()? {
-                Some(row) => {
-                    for (builder, col) in builders.iter_mut().zip(self.projected_columns.iter()) {
-                        let value = row.get(col.ibd_index)?;
-                        builder.push(value);
-                    }
-                    rows_read += 1;
-                }
-                None => {
-                    self.done = true;
-                    break;
-                }
+        let batch = if rows.is_empty() {
+            None
+        } else {
+            for row in &rows {
+                append_row(
+                    row,
+                    &self.projected_columns,
+                    &mut builders,
+                    &mut self.column_stats,
+                )?;
+            }
+
+            let arrays: Vec<ArrayRef> = builders.into_iter().map(|b| b.finish()).collect();
+            self.rows_read += rows.len();
+            for (count, array) in self.null_counts.iter_mut().zip(&arrays) {
+                *count += array.null_count();
+            }
+            Some(RecordBatch::try_new(self.schema.clone(), arrays)?)
+        };
+
+        if self.done && !self.filtered {
+            self.record_statistics();
+        }
+        Ok(batch)
+    }
+
+    /// Publish row and null counts, and the min/max of the numeric columns,
+    /// once the scan has read the whole partition
+    fn record_statistics(&self) {
+        let mut statistics = self.statistics.lock().unwrap();
+        statistics.num_rows[self.partition] = Some(self.rows_read);
+        let null_counts = &mut statistics.null_counts[self.partition];
+        for (&column, &nulls) in self.table_indices.iter().zip(&self.null_counts) {
+            null_counts[column] = Some(nulls);
+        }
+        let ranges = &mut statistics.ranges[self.partition];
+        for (k, stats) in self.column_stats.iter().enumerate() {
+            if let Some(stats) = stats {
+                ranges[self.table_indices[k]] =
+                    Some(stats_range(stats, &self.projected_columns[k].data_type));
             }
         }
+    }
+}
 
-        if rows_read == 0 {
-            return Ok(None);
+/// Min and max of `stats` as scalars of the column's Arrow type
+fn stats_range(stats: &ColumnStats, data_type: &DataType) -> ColumnRange {
+    let scalar = |value: &ColumnValue| match (value, data_type) {
+        (ColumnValue::Int(v), DataType::Int64) => Some(ScalarValue::Int64(Some(*v))),
+        (ColumnValue::UInt(v), DataType::UInt64) => Some(ScalarValue::UInt64(Some(*v))),
+        (ColumnValue::Float(v), DataType::Float64) => Some(ScalarValue::Float64(Some(*v))),
+        _ => None,
+    };
+    Some((scalar(stats.min.as_ref()?)?, scalar(stats.max.as_ref()?)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::Array;
+    use std::cell::RefCell;
+
+    /// A wide fake row that records which columns were read
+    struct CountingRow {
+        width: u32,
+        reads: RefCell<Vec<u32>>,
+    }
+
+    impl RowValues for CountingRow {
+        fn get(&self, index: u32) -> Result<ColumnValue, IbdError> {
+            if index >= self.width {
+                return Err(IbdError::InvalidParam);
+            }
+            self.reads.borrow_mut().push(index);
+            Ok(ColumnValue::Int(index as i64))
         }
+    }
+
+    #[test]
+    fn test_append_row_reads_only_projected_columns() {
+        let row = CountingRow {
+            width: 50,
+            reads: RefCell::new(Vec::new()),
+        };
+        let columns = vec![
+            ProjectedColumn {
+                data_type: DataType::Int64,
+                ibd_index: 7,
+            },
+            ProjectedColumn {
+                data_type: DataType::Utf8,
+                ibd_index: 42,
+            },
+        ];
+        let mut builders: Vec<ColumnBuilder> = columns
+            .iter()
+            .map(|c| ColumnBuilder::with_capacity(&c.data_type, 1))
+            .collect();
+
+        let mut stats = vec![Some(ColumnStats::new(7)), None];
+
+        for _ in 0..100 {
+            append_row(&row, &columns, &mut builders, &mut stats).unwrap();
+        }
+
+        // Two reads per row instead of one per table column, and none more
+        // for the stats
+        let reads = row.reads.borrow();
+        assert_eq!(reads.len(), 200);
+        assert!(reads.iter().all(|&i| i == 7 || i == 42));
+        let stats = stats[0].as_ref().unwrap();
+        assert!(matches!(stats.min, Some(ColumnValue::Int(7))));
+        assert!(matches!(stats.max, Some(ColumnValue::Int(7))));
 
         let arrays: Vec<ArrayRef> = builders.into_iter().map(|b| b.finish()).collect();
-        let batch = RecordBatch::try_new(self.schema.clone(), arrays)?;
-        Ok(Some(batch))
+        assert_eq!(arrays[0].len(), 100);
+        assert_eq!(arrays[1].data_type(), &DataType::Utf8);
+    }
+
+    #[test]
+    fn test_temporal_builders() {
+        let formatted = |s: &str| ColumnValue::Formatted(s.to_string());
+
+        let mut date = ColumnBuilder::with_capacity(&DataType::Date32, 3);
+        date.push(formatted("2024-01-15")).unwrap();
+        date.push(formatted("0000-00-00")).unwrap();
+        date.push(ColumnValue::Null).unwrap();
+        let date = date.finish();
+        let date = date.as_any().downcast_ref::<Date32Array>().unwrap();
+        assert_eq!(date.value(0), 19737);
+        assert!(date.is_null(1) && date.is_null(2));
+
+        let mut ts =
+            ColumnBuilder::with_capacity(&DataType::Timestamp(TimeUnit::Microsecond, None), 1);
+        ts.push(formatted("1970-01-02 00:00:01.000005")).unwrap();
+        let ts = ts.finish();
+        let ts = ts
+            .as_any()
+            .downcast_ref::<TimestampMicrosecondArray>()
+            .unwrap();
+        assert_eq!(ts.value(0), 86_401_000_005);
+
+        let mut time = ColumnBuilder::with_capacity(&DataType::Time64(TimeUnit::Microsecond), 2);
+        time.push(formatted("12:34:56.123456")).unwrap();
+        time.push(formatted("not a time")).unwrap();
+        assert!(time.push(formatted("-01:00:00")).is_err());
+        assert!(time.push(formatted("25:00:00")).is_err());
+        let time = time.finish();
+        let time = time
+            .as_any()
+            .downcast_ref::<Time64MicrosecondArray>()
+            .unwrap();
+        assert_eq!(time.value(0), 45_296_123_456);
+        assert!(time.is_null(1));
+    }
+
+    #[test]
+    fn test_decimal_builder() {
+        let column = ColumnInfo {
+            name: "amount".to_string(),
+            col_type: ColumnType::Decimal,
+            index: 1,
+            precision: Some(10),
+            scale: Some(2),
+        };
+        let data_type = ibd_to_arrow_type(&column);
+        assert_eq!(data_type, DataType::Decimal128(10, 2));
+
+        let mut builder = ColumnBuilder::with_capacity(&data_type, 3);
+        builder
+            .push(ColumnValue::Formatted("1234.56".to_string()))
+            .unwrap();
+        builder
+            .push(ColumnValue::Formatted("-0.99".to_string()))
+            .unwrap();
+        builder.push(ColumnValue::Null).unwrap();
+        let array = builder.finish();
+        let array = array.as_any().downcast_ref::<Decimal128Array>().unwrap();
+        assert_eq!(array.value(0), 123456);
+        assert_eq!(array.value(1), -99);
+        assert!(array.is_null(2));
+
+        // No precision from the SDI: fall back to exact text
+        let unknown = ColumnInfo {
+            precision: None,
+            scale: None,
+            ..column
+        };
+        assert_eq!(ibd_to_arrow_type(&unknown), DataType::Utf8);
+    }
+
+    #[test]
+    fn test_binary_builder() {
+        let column = ColumnInfo {
+            name: "payload".to_string(),
+            col_type: ColumnType::Binary,
+            index: 1,
+            precision: None,
+            scale: None,
+        };
+        let data_type = ibd_to_arrow_type(&column);
+        assert_eq!(data_type, DataType::Binary);
+
+        // VARBINARY values with bytes that are not valid UTF-8
+        let values: [&[u8]; 3] = [&[0x00, 0xff, 0x80, 0x0a], b"", b"plain"];
+        let mut builder = ColumnBuilder::with_capacity(&data_type, 4);
+        for value in values {
+            builder.push(ColumnValue::Binary(value.to_vec())).unwrap();
+        }
+        builder.push(ColumnValue::Null).unwrap();
+        let array = builder.finish();
+        let array = array.as_any().downcast_ref::<BinaryArray>().unwrap();
+        for (i, value) in values.iter().enumerate() {
+            assert_eq!(array.value(i), *value);
+        }
+        assert!(!array.is_null(1));
+        assert!(array.is_null(3));
+    }
+
+    #[test]
+    fn test_scan_statistics() {
+        let mut statistics = ScanStatistics::new(2, 3);
+        let unknown = statistics.to_statistics();
+        assert_eq!(unknown.num_rows, Precision::Absent);
+        assert_eq!(unknown.column_statistics.len(), 3);
+
+        // Totals stay unknown until every partition has been scanned
+        statistics.num_rows[0] = Some(10);
+        statistics.null_counts[0][2] = Some(4);
+        assert_eq!(statistics.to_statistics().num_rows, Precision::Absent);
+
+        statistics.num_rows[1] = Some(5);
+        statistics.null_counts[1][2] = Some(1);
+        let known = statistics.to_statistics().project(Some(&vec![2, 0]));
+        assert_eq!(known.num_rows, Precision::Exact(15));
+        assert_eq!(known.column_statistics[0].null_count, Precision::Exact(5));
+        assert_eq!(known.column_statistics[1].null_count, Precision::Absent);
+    }
+
+    #[test]
+    fn test_partitioned_requires_files() {
+        let err = IbdTableProvider::try_new_partitioned(&[], "table.json").unwrap_err();
+        assert!(err.to_string().contains("at least one"), "{}", err);
+    }
+
+    #[test]
+    fn test_int_eq_value() {
+        use datafusion::prelude::{col, lit};
+
+        assert_eq!(int_eq_value(&col("id").eq(lit(42i64)), "id"), Some(42));
+        assert_eq!(int_eq_value(&lit(7u32).eq(col("id")), "id"), Some(7));
+        assert_eq!(int_eq_value(&col("id").eq(lit(-3i8)), "id"), Some(-3));
+
+        assert_eq!(int_eq_value(&col("other").eq(lit(42i64)), "id"), None);
+        assert_eq!(int_eq_value(&col("id").gt(lit(42i64)), "id"), None);
+        assert_eq!(int_eq_value(&col("id").eq(lit("42")), "id"), None);
+        assert_eq!(int_eq_value(&col("id").eq(lit(u64::MAX)), "id"), None);
+        assert_eq!(
+            int_eq_value(&col("id").eq(lit(ScalarValue::Int64(None))), "id"),
+            None
+        );
     }
 }
@@ -0,0 +1,137 @@
+//! Lexing of the parts of SQL text that hide statement structure
+//!
+//! Both script splitting (`script`) and statement classification
+//! (`statement`) need to step over string literals, quoted identifiers and
+//! comments without looking inside them. `piece` does that one element at a
+//! time; everything else is left to the callers, one byte per piece.
+
+/// Kind of a `Piece`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PieceKind {
+    /// One whitespace byte
+    Whitespace,
+    /// `# ...` or `-- ...`, up to and including the newline
+    LineComment,
+    /// `/* ... */`; `executable` for `/*!` and `/*+` comments, which the
+    /// server runs
+    BlockComment { executable: bool },
+    /// A `'` or `"` string literal, or a backticked identifier
+    Quoted,
+    /// Any other byte
+    Other,
+}
+
+/// Element of SQL text starting at some offset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Piece {
+    pub(crate) kind: PieceKind,
+    /// Offset just past the element
+    pub(crate) end: usize,
+    /// False for a literal, identifier or block comment that runs to the end
+    /// of the text without being closed
+    pub(crate) closed: bool,
+}
+
+/// The element of `sql` starting at byte offset `pos`, which must be below
+/// `sql.len()`
+pub(crate) fn piece(sql: &str, pos: usize) -> Piece {
+    let bytes = sql.as_bytes();
+    let b = bytes[pos];
+    let next = bytes.get(pos + 1).copied();
+    let find = |from: usize, needle: &[u8]| {
+        bytes
+            .get(from..)
+            .and_then(|rest| rest.windows(needle.len()).position(|w| w == needle))
+            .map(|n| from + n + needle.len())
+    };
+    let piece = |kind, end: Option<usize>| Piece {
+        kind,
+        end: end.unwrap_or(bytes.len()),
+        closed: end.is_some(),
+    };
+    // A line comment may end with the text instead of a newline
+    let line_comment = || Piece {
+        kind: PieceKind::LineComment,
+        end: find(pos, b"\n").unwrap_or(bytes.len()),
+        closed: true,
+    };
+
+    match b {
+        b if b.is_ascii_whitespace() => piece(PieceKind::Whitespace, Some(pos + 1)),
+        b'#' => line_comment(),
+        // MySQL only treats `--` as a comment when followed by whitespace
+        b'-' if next == Some(b'-')
+            && bytes
+                .get(pos + 2)
+                .map_or(true, |c| c.is_ascii_whitespace() || c.is_ascii_control()) =>
+        {
+            line_comment()
+        }
+        b'/' if next == Some(b'*') => {
+            let executable = matches!(bytes.get(pos + 2), Some(b'!' | b'+'));
+            piece(PieceKind::BlockComment { executable }, find(pos + 2, b"*/"))
+        }
+        b'\'' | b'"' | b'`' => {
+            let mut i = pos + 1;
+            while let Some(&c) = bytes.get(i) {
+                i += 1;
+                if c == b'\\' && b != b'`' {
+                    // Skip the escaped character, which may be the quote itself
+                    i += 1;
+                } else if c == b {
+                    return piece(PieceKind::Quoted, Some(i));
+                }
+            }
+            piece(PieceKind::Quoted, None)
+        }
+        _ => piece(PieceKind::Other, Some(pos + 1)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(sql: &str) -> Vec<(PieceKind, &str, bool)> {
+        let mut pieces = Vec::new();
+        let mut pos = 0;
+        while pos < sql.len() {
+            let p = piece(sql, pos);
+            pieces.push((p.kind, &sql[pos..p.end], p.closed));
+            pos = p.end;
+        }
+        pieces
+    }
+
+    #[test]
+    fn test_piece() {
+        use PieceKind::*;
+        assert_eq!(
+            kinds("a 'b\\'c' `d``e`"),
+            vec![
+                (Other, "a", true),
+                (Whitespace, " ", true),
+                (Quoted, "'b\\'c'", true),
+                (Whitespace, " ", true),
+                (Quoted, "`d`", true),
+                (Quoted, "`e`", true),
+            ]
+        );
+        assert_eq!(
+            kinds("-- x\n#y"),
+            vec![(LineComment, "-- x\n", true), (LineComment, "#y", true)]
+        );
+        // `--` without trailing whitespace is an operator
+        assert_eq!(kinds("1--1")[1], (Other, "-", true));
+        assert_eq!(
+            kinds("/*! 1 */"),
+            vec![(BlockComment { executable: true }, "/*! 1 */", true)]
+        );
+        assert_eq!(
+            kinds("/* open"),
+            vec![(BlockComment { executable: false }, "/* open", false)]
+        );
+        assert_eq!(kinds("'open"), vec![(Quoted, "'open", false)]);
+        assert_eq!(kinds("'a\\"), vec![(Quoted, "'a\\", false)]);
+    }
+}
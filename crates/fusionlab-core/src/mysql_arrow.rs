@@ -0,0 +1,806 @@
+//! Conversion between MySQL query results and Arrow record batches
+//!
+//! Lets a `QueryResult` be registered as a DataFusion table so both engines
+//! can be compared on identical data, and turns a `DfQueryResult` back into
+//! a `QueryResult` so both engines feed the same result tooling.
+
+use datafusion::arrow::array::{
+    Array, ArrayRef, AsArray, BinaryArray, Date32Array, Float64Array, Int64Array, StringArray,
+    TimestampMicrosecondArray, UInt64Array,
+};
+use datafusion::arrow::compute::{cast, cast_with_options, concat_batches, CastOptions};
+use datafusion::arrow::datatypes::{
+    DataType, Date32Type, Field, Float32Type, Float64Type, Int64Type, Schema, SchemaRef, TimeUnit,
+    TimestampMicrosecondType, UInt64Type, DECIMAL128_MAX_PRECISION,
+};
+use datafusion::arrow::error::ArrowError;
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::arrow::util::display::{ArrayFormatter, FormatOptions};
+use std::sync::Arc;
+
+use crate::{
+    CellValue, ColumnMeta, ColumnType, DfQueryResult, FusionLabError, QueryResult, Result,
+};
+
+/// Rows per record batch, matching DataFusion's default batch size
+const BATCH_SIZE: usize = 8192;
+
+/// Arrow type used for a MySQL column type
+pub(crate) fn mysql_to_arrow_type(col_type: ColumnType) -> DataType {
+    match col_type {
+        ColumnType::Int | ColumnType::Year => DataType::Int64,
+        ColumnType::UInt => DataType::UInt64,
+        ColumnType::Float | ColumnType::Double => DataType::Float64,
+        ColumnType::Date => DataType::Date32,
+        ColumnType::DateTime | ColumnType::Timestamp => {
+            DataType::Timestamp(TimeUnit::Microsecond, None)
+        }
+        ColumnType::Blob | ColumnType::Geometry | ColumnType::Bit => DataType::Binary,
+        // DECIMAL of unknown precision stays textual to remain exact; TIME
+        // can exceed 24h
+        ColumnType::Decimal
+        | ColumnType::Time
+        | ColumnType::String
+        | ColumnType::Json
+        | ColumnType::Null
+        | ColumnType::Unknown => DataType::Utf8,
+    }
+}
+
+impl QueryResult {
+    /// Convert the rows into Arrow record batches
+    ///
+    /// Column types are derived from the MySQL metadata unless `schema_hint`
+    /// is given, in which case its names and types are used (values are cast
+    /// to the hinted types). DECIMAL columns whose precision `column_meta`
+    /// gives become `Decimal128`. NULLs are preserved as Arrow nulls. At
+    /// least one (possibly empty) batch is always returned.
+    pub fn to_record_batches(&self, schema_hint: Option<SchemaRef>) -> Result<Vec<RecordBatch>> {
+        let natural_schema = Arc::new(Schema::new(
+            self.columns
+                .iter()
+                .enumerate()
+                .map(|(i, name)| Field::new(name, self.arrow_type(i), true))
+                .collect::<Vec<_>>(),
+        ));
+
+        let schema = match schema_hint {
+            Some(hint) if hint.fields().len() != natural_schema.fields().len() => {
+                return Err(FusionLabError::DataFusion(format!(
+                    "Schema hint has {} fields but the result has {} columns",
+                    hint.fields().len(),
+                    natural_schema.fields().len()
+                )));
+            }
+            Some(hint) => hint,
+            None => natural_schema.clone(),
+        };
+
+        if self.rows.is_empty() {
+            return Ok(vec![RecordBatch::new_empty(schema)]);
+        }
+
+        self.rows
+            .chunks(BATCH_SIZE)
+            .map(|chunk| {
+                let arrays = natural_schema
+                    .fields()
+                    .iter()
+                    .zip(schema.fields())
+                    .enumerate()
+                    .map(|(idx, (natural, target))| {
+                        let array = build_array(chunk, idx, natural.data_type())?;
+                        if natural.data_type() == target.data_type() {
+                            Ok(array)
+                        } else {
+                            cast(&array, target.data_type())
+                                .map_err(|e| FusionLabError::DataFusion(e.to_string()))
+                        }
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                RecordBatch::try_new(schema.clone(), arrays)
+                    .map_err(|e| FusionLabError::DataFusion(e.to_string()))
+            })
+            .collect()
+    }
+
+    /// Arrow type of column `idx`, preferring `column_meta` over
+    /// `column_types`
+    fn arrow_type(&self, idx: usize) -> DataType {
+        let decimal = self
+            .column_meta
+            .get(idx)
+            .and_then(ColumnMeta::decimal_precision);
+        match decimal {
+            Some((precision, scale)) if precision <= DECIMAL128_MAX_PRECISION => {
+                DataType::Decimal128(precision, scale as i8)
+            }
+            _ => mysql_to_arrow_type(
+                self.column_types
+                    .get(idx)
+                    .copied()
+                    .unwrap_or(ColumnType::Unknown),
+            ),
+        }
+    }
+
+    /// Convert the rows into a single Arrow record batch with the types
+    /// derived from the MySQL metadata
+    ///
+    /// Same conversion as `to_record_batches(None)`, concatenated.
+    pub fn to_record_batch(&self) -> Result<RecordBatch> {
+        let batches = self.to_record_batches(None)?;
+        concat_batches(&batches[0].schema(), &batches)
+            .map_err(|e| FusionLabError::DataFusion(e.to_string()))
+    }
+}
+
+/// MySQL column type closest to an Arrow type
+fn arrow_to_mysql_type(data_type: &DataType) -> ColumnType {
+    match data_type {
+        DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64 => ColumnType::Int,
+        DataType::UInt8 | DataType::UInt16 | DataType::UInt32 | DataType::UInt64 => {
+            ColumnType::UInt
+        }
+        DataType::Float16 | DataType::Float32 => ColumnType::Float,
+        DataType::Float64 => ColumnType::Double,
+        DataType::Decimal128(..) | DataType::Decimal256(..) => ColumnType::Decimal,
+        DataType::Date32 | DataType::Date64 => ColumnType::Date,
+        DataType::Timestamp(..) => ColumnType::DateTime,
+        DataType::Time32(_) | DataType::Time64(_) => ColumnType::Time,
+        DataType::Binary | DataType::LargeBinary => ColumnType::Blob,
+        DataType::Null => ColumnType::Null,
+        _ => ColumnType::String,
+    }
+}
+
+impl DfQueryResult {
+    /// Convert the batches into a `QueryResult`, so results of both engines
+    /// can go through the same code, such as `verify::diff_results`
+    ///
+    /// Integers, floats, binary values, dates and timestamps keep their
+    /// type, decimals keep their digits; other columns become strings as
+    /// Arrow displays them. `exec_ms` and `fetch_ms` are zero.
+    pub fn to_query_result(&self) -> Result<QueryResult> {
+        let column_types: Vec<ColumnType> = self
+            .schema
+            .fields()
+            .iter()
+            .map(|f| arrow_to_mysql_type(f.data_type()))
+            .collect();
+        let mut rows = Vec::with_capacity(self.row_count);
+        for batch in &self.batches {
+            let mut columns = batch
+                .columns()
+                .iter()
+                .zip(&column_types)
+                .map(|(array, t)| array_to_cells(array, *t).map(Vec::into_iter))
+                .collect::<Result<Vec<_>>>()?;
+            for _ in 0..batch.num_rows() {
+                rows.push(
+                    columns
+                        .iter_mut()
+                        .map(|cells| cells.next().expect("one cell per row"))
+                        .collect(),
+                );
+            }
+        }
+
+        Ok(QueryResult {
+            row_count: rows.len(),
+            duration_ms: self.duration_ms,
+            rows,
+            columns: self.columns(),
+            column_types,
+            ..QueryResult::default()
+        })
+    }
+}
+
+/// The cells of one Arrow column, typed as `col_type`
+fn array_to_cells(array: &ArrayRef, col_type: ColumnType) -> Result<Vec<CellValue>> {
+    let arrow_err = |e: ArrowError| FusionLabError::DataFusion(e.to_string());
+    let cast_to = |data_type: DataType| cast(array, &data_type).map_err(arrow_err);
+    let or_null = |value: Option<CellValue>| value.unwrap_or(CellValue::Null);
+
+    let cells = match col_type {
+        ColumnType::Int | ColumnType::Year => cast_to(DataType::Int64)?
+            .as_primitive::<Int64Type>()
+            .iter()
+            .map(|v| or_null(v.map(CellValue::Int)))
+            .collect(),
+        ColumnType::UInt => cast_to(DataType::UInt64)?
+            .as_primitive::<UInt64Type>()
+            .iter()
+            .map(|v| or_null(v.map(CellValue::UInt)))
+            .collect(),
+        ColumnType::Float => cast_to(DataType::Float32)?
+            .as_primitive::<Float32Type>()
+            .iter()
+            .map(|v| or_null(v.map(CellValue::Float)))
+            .collect(),
+        ColumnType::Double => cast_to(DataType::Float64)?
+            .as_primitive::<Float64Type>()
+            .iter()
+            .map(|v| or_null(v.map(CellValue::Double)))
+            .collect(),
+        ColumnType::Date => cast_to(DataType::Date32)?
+            .as_primitive::<Date32Type>()
+            .iter()
+            .map(|v| or_null(v.and_then(date32_to_cell)))
+            .collect(),
+        ColumnType::DateTime => cast_to(DataType::Timestamp(TimeUnit::Microsecond, None))?
+            .as_primitive::<TimestampMicrosecondType>()
+            .iter()
+            .map(|v| or_null(v.and_then(timestamp_micros_to_cell)))
+            .collect(),
+        ColumnType::Blob => cast_to(DataType::Binary)?
+            .as_binary::<i32>()
+            .iter()
+            .map(|v| or_null(v.map(|bytes| CellValue::Bytes(bytes.to_vec()))))
+            .collect(),
+        _ => {
+            let formatter = ArrayFormatter::try_new(array.as_ref(), &FormatOptions::default())
+                .map_err(arrow_err)?;
+            (0..array.len())
+                .map(|i| {
+                    if array.is_null(i) {
+                        CellValue::Null
+                    } else if col_type == ColumnType::Decimal {
+                        CellValue::Decimal(formatter.value(i).to_string())
+                    } else {
+                        CellValue::Str(formatter.value(i).to_string())
+                    }
+                })
+                .collect()
+        }
+    };
+    Ok(cells)
+}
+
+/// A `Date` cell for days since 1970-01-01, or `None` outside years 0-9999
+fn date32_to_cell(days: i32) -> Option<CellValue> {
+    let (year, month, day) = civil_from_days(days as i64)?;
+    Some(CellValue::Date { year, month, day })
+}
+
+/// A `DateTime` cell for microseconds since the epoch, or `None` outside
+/// years 0-9999
+fn timestamp_micros_to_cell(micros: i64) -> Option<CellValue> {
+    let days = micros.div_euclid(86_400_000_000);
+    let in_day = micros.rem_euclid(86_400_000_000);
+    let (year, month, day) = civil_from_days(days)?;
+    let secs = in_day / 1_000_000;
+    Some(CellValue::DateTime {
+        year,
+        month,
+        day,
+        hour: (secs / 3600) as u8,
+        minute: (secs / 60 % 60) as u8,
+        second: (secs % 60) as u8,
+        micros: (in_day % 1_000_000) as u32,
+    })
+}
+
+/// Build one column of a batch from typed cells
+fn build_array(rows: &[Vec<CellValue>], idx: usize, data_type: &DataType) -> Result<ArrayRef> {
+    let cells = rows
+        .iter()
+        .map(|row| row.get(idx).unwrap_or(&CellValue::Null));
+
+    let array: ArrayRef = match data_type {
+        DataType::Int64 => Arc::new(
+            cells
+                .map(cell_to_i64)
+                .collect::<Result<Vec<_>>>()?
+                .into_iter()
+                .collect::<Int64Array>(),
+        ),
+        DataType::UInt64 => Arc::new(
+            cells
+                .map(cell_to_u64)
+                .collect::<Result<Vec<_>>>()?
+                .into_iter()
+                .collect::<UInt64Array>(),
+        ),
+        DataType::Float64 => Arc::new(
+            cells
+                .map(cell_to_f64)
+                .collect::<Result<Vec<_>>>()?
+                .into_iter()
+                .collect::<Float64Array>(),
+        ),
+        DataType::Date32 => Arc::new(cells.map(cell_to_date32).collect::<Date32Array>()),
+        DataType::Timestamp(TimeUnit::Microsecond, None) => Arc::new(
+            cells
+                .map(cell_to_timestamp_micros)
+                .collect::<TimestampMicrosecondArray>(),
+        ),
+        DataType::Binary => {
+            let values: Vec<Option<Vec<u8>>> = cells.map(cell_to_bytes).collect();
+            Arc::new(BinaryArray::from_iter(values))
+        }
+        // Parsed from the text MySQL sends, so no digit goes through a float
+        DataType::Decimal128(..) => {
+            let text: StringArray = cells
+                .map(|c| (!c.is_null()).then(|| c.format_value()))
+                .collect();
+            let options = CastOptions {
+                safe: false,
+                ..CastOptions::default()
+            };
+            cast_with_options(&text, data_type, &options)
+                .map_err(|e| FusionLabError::DataFusion(e.to_string()))?
+        }
+        _ => Arc::new(
+            cells
+                .map(|c| (!c.is_null()).then(|| c.format_value()))
+                .collect::<StringArray>(),
+        ),
+    };
+    Ok(array)
+}
+
+fn cell_to_i64(cell: &CellValue) -> Result<Option<i64>> {
+    Ok(match cell {
+        CellValue::Null => None,
+        CellValue::Int(i) => Some(*i),
+        CellValue::UInt(u) => Some(i64::try_from(*u).map_err(|_| {
+            FusionLabError::DataFusion(format!("Value {} does not fit in Int64", u))
+        })?),
+        other => Some(parse_cell(other, "Int64")?),
+    })
+}
+
+fn cell_to_u64(cell: &CellValue) -> Result<Option<u64>> {
+    Ok(match cell {
+        CellValue::Null => None,
+        CellValue::UInt(u) => Some(*u),
+        CellValue::Int(i) => Some(u64::try_from(*i).map_err(|_| {
+            FusionLabError::DataFusion(format!("Value {} does not fit in UInt64", i))
+        })?),
+        other => Some(parse_cell(other, "UInt64")?),
+    })
+}
+
+fn cell_to_f64(cell: &CellValue) -> Result<Option<f64>> {
+    Ok(match cell {
+        CellValue::Null => None,
+        CellValue::Float(f) => Some(*f as f64),
+        CellValue::Double(d) => Some(*d),
+        CellValue::Int(i) => Some(*i as f64),
+        CellValue::UInt(u) => Some(*u as f64),
+        other => Some(parse_cell(other, "Float64")?),
+    })
+}
+
+/// Parse a cell's text as a number, failing rather than turning it into NULL
+fn parse_cell<T: std::str::FromStr>(cell: &CellValue, type_name: &str) -> Result<T> {
+    let text = cell.format_value();
+    text.trim().parse().map_err(|_| {
+        FusionLabError::DataFusion(format!("Value '{}' is not a valid {}", text, type_name))
+    })
+}
+
+fn cell_to_bytes(cell: &CellValue) -> Option<Vec<u8>> {
+    match cell {
+        CellValue::Null => None,
+        CellValue::Bytes(b) => Some(b.clone()),
+        CellValue::Str(s) => Some(s.as_bytes().to_vec()),
+        other => Some(other.format_value().into_bytes()),
+    }
+}
+
+pub(crate) fn cell_to_date32(cell: &CellValue) -> Option<i32> {
+    match cell {
+        CellValue::Date { year, month, day }
+        | CellValue::DateTime {
+            year, month, day, ..
+        } => days_from_civil(*year, *month, *day),
+        _ => None,
+    }
+}
+
+pub(crate) fn cell_to_timestamp_micros(cell: &CellValue) -> Option<i64> {
+    match cell {
+        CellValue::Date { year, month, day } => {
+            Some(days_from_civil(*year, *month, *day)? as i64 * 86_400_000_000)
+        }
+        CellValue::DateTime {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            micros,
+        } => {
+            let days = days_from_civil(*year, *month, *day)? as i64;
+            let secs = days * 86_400 + *hour as i64 * 3600 + *minute as i64 * 60 + *second as i64;
+            Some(secs * 1_000_000 + *micros as i64)
+        }
+        _ => None,
+    }
+}
+
+/// Microseconds since midnight for a TIME value within a single day
+///
+/// Negative durations and values of 24 hours or more have no time-of-day
+/// representation and are an error rather than NULL.
+pub(crate) fn cell_to_time64_micros(cell: &CellValue) -> Result<Option<i64>> {
+    Ok(match cell {
+        CellValue::Null => None,
+        CellValue::Time {
+            negative: false,
+            days: 0,
+            hours,
+            minutes,
+            seconds,
+            micros,
+        } => {
+            let secs = *hours as i64 * 3600 + *minutes as i64 * 60 + *seconds as i64;
+            Some(secs * 1_000_000 + *micros as i64)
+        }
+        other => {
+            return Err(FusionLabError::DataFusion(format!(
+                "Value '{}' is not a time of day",
+                other.format_value()
+            )))
+        }
+    })
+}
+
+/// Proleptic Gregorian date for days since 1970-01-01, the inverse of
+/// `days_from_civil`, or `None` outside years 0-9999
+fn civil_from_days(days: i64) -> Option<(u16, u8, u8)> {
+    // Howard Hinnant's civil_from_days
+    let z = days + 719_468;
+    let era = (if z >= 0 { z } else { z - 146_096 }) / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u8;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u8;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    let year = u16::try_from(year).ok().filter(|y| *y <= 9999)?;
+    Some((year, month, day))
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date
+///
+/// Returns `None` for MySQL zero dates (`0000-00-00`) and invalid months/days.
+pub(crate) fn days_from_civil(year: u16, month: u8, day: u8) -> Option<i32> {
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    // Howard Hinnant's days_from_civil
+    let y = year as i64 - if month <= 2 { 1 } else { 0 };
+    let era = (if y >= 0 { y } else { y - 399 }) / 400;
+    let yoe = y - era * 400;
+    let m = month as i64;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    Some((era * 146_097 + doe - 719_468) as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::datatypes::Decimal128Type;
+
+    fn sample_result() -> QueryResult {
+        QueryResult {
+            row_count: 2,
+            rows: vec![
+                vec![
+                    CellValue::Int(1),
+                    CellValue::Double(1.5),
+                    CellValue::Str("NULL".to_string()),
+                    CellValue::Bytes(vec![0xff, 0x00]),
+                    CellValue::Date {
+                        year: 2024,
+                        month: 1,
+                        day: 15,
+                    },
+                    CellValue::DateTime {
+                        year: 1970,
+                        month: 1,
+                        day: 2,
+                        hour: 0,
+                        minute: 0,
+                        second: 1,
+                        micros: 5,
+                    },
+                ],
+                vec![
+                    CellValue::Null,
+                    CellValue::Null,
+                    CellValue::Null,
+                    CellValue::Null,
+                    CellValue::Null,
+                    CellValue::Null,
+                ],
+            ],
+            columns: ["id", "price", "name", "payload", "day", "ts"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            column_types: vec![
+                ColumnType::Int,
+                ColumnType::Double,
+                ColumnType::String,
+                ColumnType::Blob,
+                ColumnType::Date,
+                ColumnType::DateTime,
+            ],
+            ..QueryResult::default()
+        }
+    }
+
+    #[test]
+    fn test_to_record_batches_types_and_nulls() {
+        let batches = sample_result().to_record_batches(None).unwrap();
+        assert_eq!(batches.len(), 1);
+        let batch = &batches[0];
+
+        let types: Vec<DataType> = batch
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| f.data_type().clone())
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                DataType::Int64,
+                DataType::Float64,
+                DataType::Utf8,
+                DataType::Binary,
+                DataType::Date32,
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+            ]
+        );
+
+        // The literal string "NULL" is a value, a real NULL is an Arrow null
+        let names = batch
+            .column(2)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(names.value(0), "NULL");
+        assert!(names.is_null(1));
+        for column in batch.columns() {
+            assert!(column.is_null(1));
+        }
+
+        let days = batch
+            .column(4)
+            .as_any()
+            .downcast_ref::<Date32Array>()
+            .unwrap();
+        assert_eq!(days.value(0), 19737);
+        let ts = batch
+            .column(5)
+            .as_any()
+            .downcast_ref::<TimestampMicrosecondArray>()
+            .unwrap();
+        assert_eq!(ts.value(0), 86_401_000_005);
+    }
+
+    #[test]
+    fn test_to_record_batches_schema_hint() {
+        let hint = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, true),
+            Field::new("price", DataType::Float64, true),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("payload", DataType::Binary, true),
+            Field::new("day", DataType::Date32, true),
+            Field::new("ts", DataType::Timestamp(TimeUnit::Microsecond, None), true),
+        ]));
+        let batches = sample_result().to_record_batches(Some(hint)).unwrap();
+        assert_eq!(batches[0].schema().field(0).data_type(), &DataType::Int32);
+
+        let bad_hint = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, true)]));
+        assert!(sample_result().to_record_batches(Some(bad_hint)).is_err());
+    }
+
+    #[test]
+    fn test_to_record_batches_unsigned() {
+        let mut result = sample_result();
+        result.columns = vec!["big".to_string()];
+        result.column_types = vec![ColumnType::UInt];
+        result.rows = vec![
+            vec![CellValue::UInt(u64::MAX)],
+            vec![CellValue::Int(7)],
+            vec![CellValue::Null],
+        ];
+        result.row_count = 3;
+
+        let batch = result.to_record_batch().unwrap();
+        assert_eq!(batch.schema().field(0).data_type(), &DataType::UInt64);
+        let big = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap();
+        assert_eq!(big.value(0), u64::MAX);
+        assert_eq!(big.value(1), 7);
+        assert!(big.is_null(2));
+
+        result.rows = vec![vec![CellValue::Int(-1)]];
+        result.row_count = 1;
+        assert!(result.to_record_batch().is_err());
+    }
+
+    #[test]
+    fn test_to_record_batches_rejects_unparseable() {
+        for column_type in [ColumnType::Int, ColumnType::UInt, ColumnType::Double] {
+            let mut result = sample_result();
+            result.columns = vec!["n".to_string()];
+            result.column_types = vec![column_type];
+            result.rows = vec![vec![CellValue::Str(" 42 ".to_string())]];
+            result.row_count = 1;
+            assert!(result.to_record_batch().is_ok());
+
+            result.rows = vec![vec![CellValue::Str("n/a".to_string())]];
+            assert!(result.to_record_batch().is_err());
+        }
+    }
+
+    #[test]
+    fn test_cell_to_time64_micros() {
+        let time = |negative, days, hours| CellValue::Time {
+            negative,
+            days,
+            hours,
+            minutes: 34,
+            seconds: 56,
+            micros: 7,
+        };
+        assert_eq!(
+            cell_to_time64_micros(&time(false, 0, 12)).unwrap(),
+            Some(45_296_000_007)
+        );
+        assert_eq!(cell_to_time64_micros(&CellValue::Null).unwrap(), None);
+        assert!(cell_to_time64_micros(&time(true, 0, 1)).is_err());
+        assert!(cell_to_time64_micros(&time(false, 1, 0)).is_err());
+    }
+
+    #[test]
+    fn test_to_record_batches_decimal_meta() {
+        let mut result = sample_result();
+        result.columns = vec!["revenue".to_string()];
+        result.column_types = vec![ColumnType::Decimal];
+        result.rows = vec![
+            vec![CellValue::Decimal("12345678901234.56".to_string())],
+            vec![CellValue::Null],
+        ];
+        result.row_count = 2;
+
+        // Without metadata the digits stay text
+        let batch = result.to_record_batch().unwrap();
+        assert_eq!(batch.schema().field(0).data_type(), &DataType::Utf8);
+
+        // DECIMAL(16,2)
+        result.column_meta = vec![ColumnMeta {
+            column_type: ColumnType::Decimal,
+            unsigned: false,
+            charset: 63,
+            decimals: 2,
+            length: 18,
+        }];
+        let batch = result.to_record_batch().unwrap();
+        assert_eq!(
+            batch.schema().field(0).data_type(),
+            &DataType::Decimal128(16, 2)
+        );
+        let revenue = batch.column(0).as_primitive::<Decimal128Type>();
+        assert_eq!(revenue.value(0), 1_234_567_890_123_456);
+        assert!(revenue.is_null(1));
+
+        result.rows = vec![vec![CellValue::Str("n/a".to_string())]];
+        result.row_count = 1;
+        assert!(result.to_record_batch().is_err());
+    }
+
+    #[test]
+    fn test_to_record_batch() {
+        let batch = sample_result().to_record_batch().unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(batch.num_columns(), 6);
+        assert_eq!(batch.schema().field(1).name(), "price");
+
+        // Rows spanning several batches end up in one
+        let mut large = sample_result();
+        large.rows = vec![large.rows[0].clone(); BATCH_SIZE + 5];
+        let batch = large.to_record_batch().unwrap();
+        assert_eq!(batch.num_rows(), BATCH_SIZE + 5);
+
+        large.rows.clear();
+        assert_eq!(large.to_record_batch().unwrap().num_rows(), 0);
+    }
+
+    #[test]
+    fn test_days_from_civil() {
+        assert_eq!(days_from_civil(1970, 1, 1), Some(0));
+        assert_eq!(days_from_civil(2000, 3, 1), Some(11017));
+        assert_eq!(days_from_civil(1969, 12, 31), Some(-1));
+        assert_eq!(days_from_civil(0, 0, 0), None);
+
+        for (year, month, day) in [(1970, 1, 1), (2000, 2, 29), (1969, 12, 31), (9999, 12, 31)] {
+            let days = days_from_civil(year, month, day).unwrap();
+            assert_eq!(civil_from_days(days as i64), Some((year, month, day)));
+        }
+        assert_eq!(
+            civil_from_days(days_from_civil(9999, 12, 31).unwrap() as i64 + 1),
+            None
+        );
+    }
+
+    #[test]
+    fn test_to_query_result_round_trip() {
+        let original = sample_result();
+        let batches = original.to_record_batches(None).unwrap();
+        let df = DfQueryResult {
+            row_count: 2,
+            duration_ms: 1.5,
+            schema: batches[0].schema(),
+            batches,
+        };
+
+        let result = df.to_query_result().unwrap();
+        assert_eq!(result.columns, original.columns);
+        assert_eq!(result.column_types, original.column_types);
+        assert_eq!(result.rows, original.rows);
+        assert_eq!(result.row_count, 2);
+        assert_eq!(result.duration_ms, 1.5);
+    }
+
+    #[test]
+    fn test_to_query_result_narrow_types() {
+        use datafusion::arrow::array::{Decimal128Array, Int32Array, UInt8Array};
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, true),
+            Field::new("b", DataType::UInt8, true),
+            Field::new("c", DataType::Decimal128(10, 2), true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![Some(-4), None])) as ArrayRef,
+                Arc::new(UInt8Array::from(vec![Some(200), Some(1)])) as ArrayRef,
+                Arc::new(
+                    Decimal128Array::from(vec![Some(1230), None])
+                        .with_precision_and_scale(10, 2)
+                        .unwrap(),
+                ) as ArrayRef,
+            ],
+        )
+        .unwrap();
+        let df = DfQueryResult {
+            row_count: 2,
+            duration_ms: 0.0,
+            batches: vec![batch],
+            schema,
+        };
+
+        let result = df.to_query_result().unwrap();
+        assert_eq!(
+            result.column_types,
+            vec![ColumnType::Int, ColumnType::UInt, ColumnType::Decimal]
+        );
+        assert_eq!(
+            result.rows,
+            vec![
+                vec![
+                    CellValue::Int(-4),
+                    CellValue::UInt(200),
+                    CellValue::Decimal("12.30".to_string())
+                ],
+                vec![CellValue::Null, CellValue::UInt(1), CellValue::Null],
+            ]
+        );
+    }
+}
@@ -0,0 +1,537 @@
+//! Loading of Arrow batches and delimited files into MySQL tables
+//!
+//! Used to put the SSB sample data that `DataFusionRunner` registers in
+//! memory into MySQL as well, so both engines can be compared on the same
+//! rows. Tables are created from the batch schema and filled with
+//! multi-row prepared INSERTs. Generated SSB files are much larger and go
+//! through `LOAD DATA LOCAL INFILE` instead.
+
+use datafusion::arrow::array::{
+    Array, Float64Array, Int32Array, Int64Array, StringArray, UInt64Array,
+};
+use datafusion::arrow::datatypes::DataType;
+use datafusion::arrow::record_batch::RecordBatch;
+use futures::StreamExt;
+use mysql_async::prelude::Queryable;
+use mysql_async::{InfileData, TxOpts, Value};
+use std::path::Path;
+use std::time::Instant;
+use tokio::io::AsyncReadExt;
+
+use crate::datafusion::ssb_sample_tables;
+use crate::{FusionLabError, MySQLRunner, Result};
+
+/// Rows per INSERT, well below the 65535 placeholder limit for the sample
+/// tables
+const ROWS_PER_INSERT: usize = 500;
+
+/// Bytes sent per packet while streaming a file to the server
+const INFILE_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Server errors for `LOAD DATA LOCAL` with `local_infile` off (5.7, 8.0)
+const ER_NOT_ALLOWED_COMMAND: u16 = 1148;
+const ER_CLIENT_LOCAL_FILES_DISABLED: u16 = 3948;
+
+/// File format for `MySQLRunner::load_data_local`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadDataOptions {
+    /// `FIELDS TERMINATED BY`
+    pub field_terminator: String,
+    /// `OPTIONALLY ENCLOSED BY`, for quoted CSV fields
+    pub enclosed_by: Option<char>,
+    /// `LINES TERMINATED BY`
+    pub line_terminator: String,
+    /// Leading lines to skip, e.g. 1 for a header row
+    pub ignore_lines: usize,
+    /// Field text loaded as NULL besides MySQL's own `\N`, e.g. `NULL` or
+    /// an empty string
+    pub null_marker: Option<String>,
+    /// Table columns in file order; all columns in table order if empty
+    pub columns: Vec<String>,
+}
+
+impl Default for LoadDataOptions {
+    fn default() -> Self {
+        Self {
+            field_terminator: ",".to_string(),
+            enclosed_by: Some('"'),
+            line_terminator: "\n".to_string(),
+            ignore_lines: 0,
+            null_marker: None,
+            columns: Vec::new(),
+        }
+    }
+}
+
+impl LoadDataOptions {
+    /// The `.tbl` format of SSB dbgen: `|` after every field, including
+    /// the last
+    pub fn dbgen() -> Self {
+        Self {
+            field_terminator: "|".to_string(),
+            enclosed_by: None,
+            line_terminator: "|\n".to_string(),
+            ..Self::default()
+        }
+    }
+}
+
+/// Outcome of `MySQLRunner::load_data_local`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadDataStats {
+    pub rows: u64,
+    /// Warnings the server raised, e.g. for truncated values
+    pub warnings: u16,
+    pub duration_ms: f64,
+}
+
+impl MySQLRunner {
+    /// Create the SSB sample tables of `DataFusionRunner::register_ssb_sample`
+    /// in `database` and fill them with the same rows
+    ///
+    /// The database is created if needed and existing tables of the same
+    /// names are dropped first, so loading twice leaves the same data. The
+    /// rows are inserted in one transaction. Returns the number of rows
+    /// loaded into each table.
+    pub async fn load_ssb_sample(&self, database: &str) -> Result<Vec<(String, usize)>> {
+        let tables = ssb_sample_tables()?;
+        let mut conn = self.get_conn().await?;
+
+        // DDL commits implicitly, so it runs before the transaction
+        conn.query_drop(format!(
+            "CREATE DATABASE IF NOT EXISTS {}",
+            quote_ident(database)
+        ))
+        .await?;
+        for (name, batch) in &tables {
+            let table = format!("{}.{}", quote_ident(database), quote_ident(name));
+            conn.query_drop(format!("DROP TABLE IF EXISTS {}", table))
+                .await?;
+            conn.query_drop(create_table_sql(&table, batch)?).await?;
+        }
+
+        let mut tx = conn.start_transaction(TxOpts::default()).await?;
+        let mut loaded = Vec::with_capacity(tables.len());
+        for (name, batch) in &tables {
+            let table = format!("{}.{}", quote_ident(database), quote_ident(name));
+            for offset in (0..batch.num_rows()).step_by(ROWS_PER_INSERT) {
+                let chunk = batch.slice(offset, ROWS_PER_INSERT.min(batch.num_rows() - offset));
+                let (sql, params) = insert_sql(&table, &chunk)?;
+                tx.exec_drop(sql, params).await?;
+            }
+            loaded.push((name.to_string(), batch.num_rows()));
+        }
+        tx.commit().await?;
+        Ok(loaded)
+    }
+
+    /// Load a delimited file into `table` (`name` or `db.name`) with
+    /// `LOAD DATA LOCAL INFILE`
+    ///
+    /// The file is streamed to the server from this process, so it does not
+    /// need to be on the server host. The server must allow it
+    /// (`local_infile=ON`); otherwise this fails with
+    /// `FusionLabError::Unsupported`.
+    pub async fn load_data_local(
+        &self,
+        table: &str,
+        csv_path: &Path,
+        opts: LoadDataOptions,
+    ) -> Result<LoadDataStats> {
+        // Open before asking the server, so a missing file is reported as such
+        let file = tokio::fs::File::open(csv_path).await?;
+        let mut conn = self.get_conn().await?;
+
+        let mut opts = opts;
+        if opts.null_marker.is_some() && opts.columns.is_empty() {
+            opts.columns = table_columns(&mut conn, table).await?;
+        }
+        let sql = load_data_sql(table, &csv_path.to_string_lossy(), &opts);
+
+        let start = Instant::now();
+        conn.set_infile_handler(async move { Ok(file_stream(file)) });
+        match conn.query_drop(sql).await {
+            Ok(()) => {}
+            Err(mysql_async::Error::Server(e))
+                if matches!(
+                    e.code,
+                    ER_NOT_ALLOWED_COMMAND | ER_CLIENT_LOCAL_FILES_DISABLED
+                ) =>
+            {
+                let server = self
+                    .server_info()
+                    .await
+                    .map_or_else(|_| "(unknown)".to_string(), |info| info.to_string());
+                return Err(FusionLabError::Unsupported {
+                    feature: "LOAD DATA LOCAL INFILE (local_infile is OFF)".to_string(),
+                    server,
+                });
+            }
+            Err(e) => return Err(e.into()),
+        }
+        Ok(LoadDataStats {
+            rows: conn.affected_rows(),
+            warnings: conn.get_warnings(),
+            duration_ms: start.elapsed().as_secs_f64() * 1000.0,
+        })
+    }
+}
+
+/// The contents of `file` in `INFILE_CHUNK_SIZE` pieces
+fn file_stream(file: tokio::fs::File) -> InfileData {
+    futures::stream::unfold(Some(file), |file| async move {
+        let mut file = file?;
+        let mut chunk = vec![0; INFILE_CHUNK_SIZE];
+        match file.read(&mut chunk).await {
+            Ok(0) => None,
+            Ok(n) => {
+                chunk.truncate(n);
+                Some((Ok(chunk.into()), Some(file)))
+            }
+            // Ends the stream after the error
+            Err(e) => Some((Err(e), None)),
+        }
+    })
+    .boxed()
+}
+
+/// Column names of `table` in table order
+async fn table_columns(conn: &mut mysql_async::Conn, table: &str) -> Result<Vec<String>> {
+    let (schema, name) = match table.split_once('.') {
+        Some((schema, name)) => (Some(schema), name),
+        None => (None, table),
+    };
+    let columns: Vec<String> = conn
+        .exec(
+            "SELECT COLUMN_NAME FROM information_schema.COLUMNS \
+             WHERE TABLE_SCHEMA = COALESCE(?, DATABASE()) AND TABLE_NAME = ? \
+             ORDER BY ORDINAL_POSITION",
+            (schema, name),
+        )
+        .await?;
+    if columns.is_empty() {
+        return Err(FusionLabError::Config(format!("table {} not found", table)));
+    }
+    Ok(columns)
+}
+
+/// `LOAD DATA LOCAL INFILE` for `opts`
+///
+/// With a NULL marker every field goes through a user variable so it can
+/// be compared with the marker.
+fn load_data_sql(table: &str, file_name: &str, opts: &LoadDataOptions) -> String {
+    let table = table
+        .split('.')
+        .map(quote_ident)
+        .collect::<Vec<_>>()
+        .join(".");
+    let mut sql = format!(
+        "LOAD DATA LOCAL INFILE {} INTO TABLE {} CHARACTER SET utf8mb4 \
+         FIELDS TERMINATED BY {}",
+        quote_string(file_name),
+        table,
+        quote_string(&opts.field_terminator)
+    );
+    if let Some(quote) = opts.enclosed_by {
+        sql.push_str(&format!(
+            " OPTIONALLY ENCLOSED BY {}",
+            quote_string(&quote.to_string())
+        ));
+    }
+    sql.push_str(&format!(
+        " LINES TERMINATED BY {}",
+        quote_string(&opts.line_terminator)
+    ));
+    if opts.ignore_lines > 0 {
+        sql.push_str(&format!(" IGNORE {} LINES", opts.ignore_lines));
+    }
+    match &opts.null_marker {
+        Some(marker) if !opts.columns.is_empty() => {
+            let variables: Vec<String> = (0..opts.columns.len())
+                .map(|i| format!("@c{}", i))
+                .collect();
+            let assignments: Vec<String> = opts
+                .columns
+                .iter()
+                .zip(&variables)
+                .map(|(column, variable)| {
+                    format!(
+                        "{} = NULLIF({}, {})",
+                        quote_ident(column),
+                        variable,
+                        quote_string(marker)
+                    )
+                })
+                .collect();
+            sql.push_str(&format!(
+                " ({}) SET {}",
+                variables.join(", "),
+                assignments.join(", ")
+            ));
+        }
+        _ if !opts.columns.is_empty() => {
+            let columns: Vec<String> = opts.columns.iter().map(|c| quote_ident(c)).collect();
+            sql.push_str(&format!(" ({})", columns.join(", ")));
+        }
+        _ => {}
+    }
+    sql
+}
+
+/// A MySQL string literal
+fn quote_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('\'');
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\'' => out.push_str("\\'"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            _ => out.push(c),
+        }
+    }
+    out.push('\'');
+    out
+}
+
+/// Backtick-quote an identifier
+fn quote_ident(name: &str) -> String {
+    format!("`{}`", name.replace('`', "``"))
+}
+
+/// `CREATE TABLE` with a column per field of the batch schema
+///
+/// Strings become `VARCHAR` sized to the longest value in the batch.
+fn create_table_sql(table: &str, batch: &RecordBatch) -> Result<String> {
+    let schema = batch.schema();
+    let columns = schema
+        .fields()
+        .iter()
+        .zip(batch.columns())
+        .map(|(field, array)| {
+            let sql_type = match field.data_type() {
+                DataType::Int32 => "INT".to_string(),
+                DataType::Int64 => "BIGINT".to_string(),
+                DataType::UInt64 => "BIGINT UNSIGNED".to_string(),
+                DataType::Float64 => "DOUBLE".to_string(),
+                DataType::Utf8 => {
+                    let strings = array.as_any().downcast_ref::<StringArray>().unwrap();
+                    let width = strings
+                        .iter()
+                        .flatten()
+                        .map(|s| s.chars().count())
+                        .max()
+                        .unwrap_or(0);
+                    format!("VARCHAR({})", width.max(1))
+                }
+                other => return Err(unsupported(field.name(), other)),
+            };
+            let null = if field.is_nullable() {
+                "NULL"
+            } else {
+                "NOT NULL"
+            };
+            Ok(format!(
+                "{} {} {}",
+                quote_ident(field.name()),
+                sql_type,
+                null
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(format!("CREATE TABLE {} ({})", table, columns.join(", ")))
+}
+
+/// A multi-row `INSERT` for all rows of `batch`, with its parameters
+fn insert_sql(table: &str, batch: &RecordBatch) -> Result<(String, Vec<Value>)> {
+    let schema = batch.schema();
+    let columns: Vec<String> = schema
+        .fields()
+        .iter()
+        .map(|f| quote_ident(f.name()))
+        .collect();
+    let placeholders = format!("({})", vec!["?"; columns.len()].join(", "));
+    let sql = format!(
+        "INSERT INTO {} ({}) VALUES {}",
+        table,
+        columns.join(", "),
+        vec![placeholders; batch.num_rows()].join(", ")
+    );
+
+    let mut params = Vec::with_capacity(batch.num_rows() * columns.len());
+    for row in 0..batch.num_rows() {
+        for (field, array) in schema.fields().iter().zip(batch.columns()) {
+            params.push(
+                cell_value(array.as_ref(), row)
+                    .ok_or_else(|| unsupported(field.name(), field.data_type()))?,
+            );
+        }
+    }
+    Ok((sql, params))
+}
+
+/// The MySQL value of one cell, `None` for unsupported types
+fn cell_value(array: &dyn Array, row: usize) -> Option<Value> {
+    if array.is_null(row) {
+        return Some(Value::NULL);
+    }
+    let any = array.as_any();
+    let value = match array.data_type() {
+        DataType::Int32 => Value::Int(any.downcast_ref::<Int32Array>()?.value(row) as i64),
+        DataType::Int64 => Value::Int(any.downcast_ref::<Int64Array>()?.value(row)),
+        DataType::UInt64 => Value::UInt(any.downcast_ref::<UInt64Array>()?.value(row)),
+        DataType::Float64 => Value::Double(any.downcast_ref::<Float64Array>()?.value(row)),
+        DataType::Utf8 => Value::from(any.downcast_ref::<StringArray>()?.value(row)),
+        _ => return None,
+    };
+    Some(value)
+}
+
+fn unsupported(column: &str, data_type: &DataType) -> FusionLabError {
+    FusionLabError::DataFusion(format!(
+        "cannot load column {} of type {} into MySQL",
+        column, data_type
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::ArrayRef;
+    use datafusion::arrow::datatypes::{Field, Schema};
+    use std::sync::Arc;
+
+    fn sample_batch() -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("note", DataType::Utf8, true),
+            Field::new("price", DataType::Float64, false),
+        ]));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2])) as ArrayRef,
+                Arc::new(StringArray::from(vec![Some("naïve"), None])) as ArrayRef,
+                Arc::new(Float64Array::from(vec![1.5, 2.0])) as ArrayRef,
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_create_table_sql() {
+        assert_eq!(
+            create_table_sql("`db`.`t`", &sample_batch()).unwrap(),
+            "CREATE TABLE `db`.`t` (`id` BIGINT NOT NULL, `note` VARCHAR(5) NULL, \
+             `price` DOUBLE NOT NULL)"
+        );
+        assert_eq!(quote_ident("we`ird"), "`we``ird`");
+
+        // Every sample table maps to MySQL types
+        for (name, batch) in ssb_sample_tables().unwrap() {
+            create_table_sql(name, &batch).unwrap();
+            insert_sql(name, &batch).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_load_data_sql() {
+        assert_eq!(
+            load_data_sql(
+                "ssb.lineorder",
+                "/data/lineorder.tbl",
+                &LoadDataOptions::dbgen()
+            ),
+            "LOAD DATA LOCAL INFILE '/data/lineorder.tbl' INTO TABLE `ssb`.`lineorder` \
+             CHARACTER SET utf8mb4 FIELDS TERMINATED BY '|' LINES TERMINATED BY '|\\n'"
+        );
+
+        let opts = LoadDataOptions {
+            ignore_lines: 1,
+            null_marker: Some("NULL".to_string()),
+            columns: vec!["id".to_string(), "note".to_string()],
+            ..LoadDataOptions::default()
+        };
+        assert_eq!(
+            load_data_sql("t", "it's.csv", &opts),
+            "LOAD DATA LOCAL INFILE 'it\\'s.csv' INTO TABLE `t` CHARACTER SET utf8mb4 \
+             FIELDS TERMINATED BY ',' OPTIONALLY ENCLOSED BY '\"' LINES TERMINATED BY '\\n' \
+             IGNORE 1 LINES (@c0, @c1) SET `id` = NULLIF(@c0, 'NULL'), \
+             `note` = NULLIF(@c1, 'NULL')"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load_data_local() {
+        if std::env::var("FUSIONLAB_TEST_MYSQL").is_err() {
+            return;
+        }
+        let runner = crate::MySQLRunner::new(&crate::MySQLConfig::default()).unwrap();
+        runner
+            .run_query("DROP TABLE IF EXISTS fusionlab_load_data")
+            .await
+            .unwrap();
+        runner
+            .run_query("CREATE TABLE fusionlab_load_data (id INT, note VARCHAR(20))")
+            .await
+            .unwrap();
+        let path =
+            std::env::temp_dir().join(format!("fusionlab_test_{}_load.tbl", std::process::id()));
+        std::fs::write(&path, "1|naïve|\n2|NULL|\n3||\n").unwrap();
+
+        let opts = LoadDataOptions {
+            null_marker: Some("NULL".to_string()),
+            ..LoadDataOptions::dbgen()
+        };
+        let loaded = runner
+            .load_data_local("fusionlab_load_data", &path, opts)
+            .await;
+        std::fs::remove_file(&path).ok();
+        let result = match loaded {
+            Ok(stats) => {
+                assert_eq!(stats.rows, 3);
+                runner
+                    .run_query("SELECT id, note FROM fusionlab_load_data ORDER BY id")
+                    .await
+                    .unwrap()
+            }
+            // The test server may not allow LOAD DATA LOCAL
+            Err(FusionLabError::Unsupported { .. }) => {
+                runner.close().await;
+                return;
+            }
+            Err(e) => panic!("{}", e),
+        };
+        assert_eq!(
+            result.to_display_rows(),
+            vec![vec!["1", "naïve"], vec!["2", "NULL"], vec!["3", ""]]
+        );
+        runner
+            .run_query("DROP TABLE fusionlab_load_data")
+            .await
+            .unwrap();
+        runner.close().await;
+    }
+
+    #[test]
+    fn test_insert_sql() {
+        let (sql, params) = insert_sql("t", &sample_batch()).unwrap();
+        assert_eq!(
+            sql,
+            "INSERT INTO t (`id`, `note`, `price`) VALUES (?, ?, ?), (?, ?, ?)"
+        );
+        assert_eq!(
+            params,
+            vec![
+                Value::Int(1),
+                Value::from("naïve"),
+                Value::Double(1.5),
+                Value::Int(2),
+                Value::NULL,
+                Value::Double(2.0),
+            ]
+        );
+    }
+}
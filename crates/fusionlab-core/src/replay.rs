@@ -0,0 +1,333 @@
+//! Workload replay from a query log
+//!
+//! Reads statements from a plain file (one per line) or a MySQL slow log
+//! and runs them against either engine, optionally over several
+//! connections at once, recording how long each one took.
+
+use futures::stream::{self, StreamExt};
+use std::future::Future;
+use std::time::Instant;
+
+use crate::{format_table_with_options, QueryStats, Result, TableFormatOptions};
+
+/// Statements of a workload file, each with the line it starts on
+///
+/// A file with `# Query_time:` or `# Time:` lines is read as a MySQL slow
+/// log: statements run until a line ending in `;` or the next `#` header
+/// line, and the `SET timestamp` and `use` statements the log adds are
+/// dropped. Any other file holds one statement per line; blank lines and
+/// `--` or `#` comments are skipped. Trailing semicolons are removed either
+/// way. Line numbers start at 1.
+pub fn parse_workload(text: &str) -> Vec<(usize, String)> {
+    let is_slow_log = text
+        .lines()
+        .any(|line| line.starts_with("# Query_time:") || line.starts_with("# Time:"));
+    if !is_slow_log {
+        return text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim().trim_end_matches(';').trim_end()))
+            .filter(|(_, line)| {
+                !line.is_empty() && !line.starts_with("--") && !line.starts_with('#')
+            })
+            .map(|(line_no, line)| (line_no, line.to_string()))
+            .collect();
+    }
+
+    let mut statements = Vec::new();
+    let mut current = String::new();
+    let mut current_line = 0;
+    // Lines before the first entry are the server's log header
+    let mut in_entry = false;
+    for (i, line) in text.lines().enumerate() {
+        if line.starts_with('#') {
+            in_entry = true;
+            push_slow_log_statement(&mut statements, &mut current, current_line);
+            continue;
+        }
+        if !in_entry || (current.is_empty() && line.trim().is_empty()) {
+            continue;
+        }
+        if current.is_empty() {
+            current_line = i + 1;
+        } else {
+            current.push('\n');
+        }
+        current.push_str(line);
+        if line.trim_end().ends_with(';') {
+            push_slow_log_statement(&mut statements, &mut current, current_line);
+        }
+    }
+    push_slow_log_statement(&mut statements, &mut current, current_line);
+    statements
+}
+
+/// Move the pending slow-log statement into `statements`, unless it is
+/// empty or one of the `SET timestamp` / `use` lines the server adds
+fn push_slow_log_statement(
+    statements: &mut Vec<(usize, String)>,
+    current: &mut String,
+    line: usize,
+) {
+    let statement = current.trim().trim_end_matches(';').trim_end().to_string();
+    current.clear();
+    let lower = statement.to_ascii_lowercase();
+    if statement.is_empty() || lower.starts_with("set timestamp=") || lower.starts_with("use ") {
+        return;
+    }
+    statements.push((line, statement));
+}
+
+/// Outcome of one replayed statement
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayQuery {
+    /// Position of the statement in the workload
+    pub index: usize,
+    /// Line of the workload file the statement starts on
+    pub line: usize,
+    pub sql: String,
+    /// Time until the statement finished, including fetching its rows
+    pub duration_ms: f64,
+    pub row_count: usize,
+    /// Error message if the statement failed
+    pub error: Option<String>,
+}
+
+/// Outcome of `replay`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayReport {
+    /// One entry per statement, in workload order
+    pub queries: Vec<ReplayQuery>,
+    pub concurrency: usize,
+    /// Wall-clock time of the whole replay
+    pub wall_ms: f64,
+}
+
+impl ReplayReport {
+    /// Statements that failed
+    pub fn errors(&self) -> usize {
+        self.queries.iter().filter(|q| q.error.is_some()).count()
+    }
+
+    /// Statements finished per second of wall-clock time
+    pub fn queries_per_sec(&self) -> f64 {
+        if self.wall_ms > 0.0 {
+            self.queries.len() as f64 * 1000.0 / self.wall_ms
+        } else {
+            0.0
+        }
+    }
+
+    /// Latency statistics of the statements that succeeded
+    pub fn stats(&self) -> QueryStats {
+        let samples = self
+            .queries
+            .iter()
+            .filter(|q| q.error.is_none())
+            .map(|q| q.duration_ms)
+            .collect();
+        QueryStats::from_samples(samples, 0, 0)
+    }
+
+    /// One row per statement, with the SQL cut to `max_sql_width`
+    pub fn to_table(&self, max_sql_width: usize) -> String {
+        let columns: Vec<String> = ["#", "ms", "rows", "status", "query"]
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        let rows: Vec<Vec<String>> = self
+            .queries
+            .iter()
+            .map(|q| {
+                vec![
+                    (q.index + 1).to_string(),
+                    format!("{:.2}", q.duration_ms),
+                    q.row_count.to_string(),
+                    q.error.as_ref().map_or("ok", |_| "error").to_string(),
+                    q.sql.split_whitespace().collect::<Vec<_>>().join(" "),
+                ]
+            })
+            .collect();
+        let options = TableFormatOptions {
+            max_col_width: Some(max_sql_width),
+            ..TableFormatOptions::default()
+        };
+        format_table_with_options(&columns, &rows, &options)
+    }
+
+    /// `index,duration_ms,row_count,error,sql` CSV with a header row
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("index,duration_ms,row_count,error,sql\n");
+        for q in &self.queries {
+            out.push_str(&format!(
+                "{},{:.3},{},{},{}\n",
+                q.index + 1,
+                q.duration_ms,
+                q.row_count,
+                csv_field(q.error.as_deref().unwrap_or_default()),
+                csv_field(&q.sql)
+            ));
+        }
+        out
+    }
+}
+
+/// Quote a CSV field if it needs it
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Run `statements` through `run`, at most `concurrency` at a time
+///
+/// `statements` are `(line, sql)` pairs as returned by `parse_workload`;
+/// `run` gets both and returns the row count of the statement. Failures are recorded in
+/// the report and do not stop the replay. With a concurrency of 1 the
+/// statements run one after another in workload order.
+pub async fn replay<F, Fut>(
+    statements: Vec<(usize, String)>,
+    concurrency: usize,
+    run: F,
+) -> ReplayReport
+where
+    F: Fn(usize, String) -> Fut,
+    Fut: Future<Output = Result<usize>>,
+{
+    let concurrency = concurrency.max(1);
+    let start = Instant::now();
+    let run = &run;
+    let mut queries: Vec<ReplayQuery> = stream::iter(statements.into_iter().enumerate())
+        .map(|(index, (line, sql))| async move {
+            let query_start = Instant::now();
+            let outcome = run(line, sql.clone()).await;
+            let duration_ms = query_start.elapsed().as_secs_f64() * 1000.0;
+            let (row_count, error) = match outcome {
+                Ok(rows) => (rows, None),
+                Err(e) => (0, Some(e.to_string())),
+            };
+            ReplayQuery {
+                index,
+                line,
+                sql,
+                duration_ms,
+                row_count,
+                error,
+            }
+        })
+        .buffer_unordered(concurrency)
+        .collect()
+        .await;
+    queries.sort_by_key(|q| q.index);
+
+    ReplayReport {
+        queries,
+        concurrency,
+        wall_ms: start.elapsed().as_secs_f64() * 1000.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FusionLabError;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_plain_workload() {
+        let text = "SELECT 1;\n\n-- warmup done\nSELECT * FROM customer\n# note\n  SELECT 2 ;\n";
+        assert_eq!(
+            parse_workload(text),
+            vec![
+                (1, "SELECT 1".to_string()),
+                (4, "SELECT * FROM customer".to_string()),
+                (6, "SELECT 2".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_slow_log() {
+        let text = "\
+/usr/sbin/mysqld, Version: 8.0.36 (MySQL Community Server - GPL). started with:
+Tcp port: 3306  Unix socket: /var/run/mysqld/mysqld.sock
+Time                 Id Command    Argument
+# Time: 2024-05-01T10:00:00.123456Z
+# User@Host: root[root] @ localhost []  Id:     8
+# Query_time: 1.250000  Lock_time: 0.000010 Rows_sent: 1  Rows_examined: 600000
+use ssb;
+SET timestamp=1714557600;
+SELECT SUM(lo_revenue)
+FROM lineorder
+WHERE lo_discount BETWEEN 1 AND 3;
+# Time: 2024-05-01T10:00:02.000000Z
+# User@Host: root[root] @ localhost []  Id:     9
+# Query_time: 0.010000  Lock_time: 0.000000 Rows_sent: 5  Rows_examined: 5
+SET timestamp=1714557602;
+SELECT c_name FROM customer LIMIT 5;
+# Time: 2024-05-01T10:00:03.000000Z
+# Query_time: 0.020000  Lock_time: 0.000000 Rows_sent: 0  Rows_examined: 0
+SELECT 1
+# Time: 2024-05-01T10:00:04.000000Z
+# Query_time: 0.020000  Lock_time: 0.000000 Rows_sent: 0  Rows_examined: 0
+SELECT 2
+";
+        assert_eq!(
+            parse_workload(text),
+            vec![
+                (
+                    9,
+                    "SELECT SUM(lo_revenue)\nFROM lineorder\nWHERE lo_discount BETWEEN 1 AND 3"
+                        .to_string()
+                ),
+                (16, "SELECT c_name FROM customer LIMIT 5".to_string()),
+                (19, "SELECT 1".to_string()),
+                (22, "SELECT 2".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replay_concurrency_and_errors() {
+        let in_flight = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let statements: Vec<(usize, String)> = (0..8).map(|i| (i + 1, format!("q{}", i))).collect();
+
+        let report = replay(statements, 3, |_, sql| {
+            let (in_flight, peak) = (&in_flight, &peak);
+            async move {
+                let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                if sql == "q5" {
+                    Err(FusionLabError::Parse("bad query".to_string()))
+                } else {
+                    Ok(sql.len())
+                }
+            }
+        })
+        .await;
+
+        assert_eq!(peak.load(Ordering::SeqCst), 3);
+        assert_eq!(report.queries.len(), 8);
+        assert!(report
+            .queries
+            .iter()
+            .enumerate()
+            .all(|(i, q)| q.index == i && q.line == i + 1));
+        assert_eq!(report.errors(), 1);
+        assert_eq!(
+            report.queries[5].error.as_deref(),
+            Some("Parse error: bad query")
+        );
+        assert_eq!(report.stats().iterations(), 7);
+        assert!(report.queries_per_sec() > 0.0);
+        assert!(report
+            .to_csv()
+            .starts_with("index,duration_ms,row_count,error,sql\n1,"));
+    }
+}
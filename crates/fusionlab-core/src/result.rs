@@ -0,0 +1,567 @@
+//! Results of MySQL statements and the column types they carry
+
+use mysql_async::consts::{ColumnFlags, ColumnType as MySqlColumnType};
+use mysql_async::Column;
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::collections::HashMap;
+
+use crate::{format_table_with_kinds, CellValue, ColumnKind, Protocol, TableFormatOptions};
+
+/// How tables show SQL NULL, distinct from a string holding `NULL`
+pub const DEFAULT_NULL_MARKER: &str = "null";
+
+/// Result of running a query
+#[derive(Debug, Clone, Default)]
+pub struct QueryResult {
+    /// Number of rows returned
+    pub row_count: usize,
+    /// Rows changed by a DML statement, 0 for statements returning rows
+    pub affected_rows: u64,
+    /// Total query time in milliseconds (`exec_ms + fetch_ms`)
+    pub duration_ms: f64,
+    /// Time from sending the query until the first row arrived (or the
+    /// result completed, if it has no rows)
+    pub exec_ms: f64,
+    /// Time spent reading the remaining rows after the first one
+    pub fetch_ms: f64,
+    /// Typed rows; use `to_display_rows()` for strings
+    pub rows: Vec<Vec<CellValue>>,
+    /// Column names, from result set metadata so they are set even with no rows
+    pub columns: Vec<String>,
+    /// Column types, parallel to `columns`
+    pub column_types: Vec<ColumnType>,
+    /// Full metadata of each column, parallel to `columns`; empty for
+    /// results that did not come from MySQL
+    pub column_meta: Vec<ColumnMeta>,
+    /// Warnings reported by the server for this query; only collected when
+    /// `QueryOptions::collect_warnings` is set
+    pub warnings: Vec<MySQLWarning>,
+    /// Change of each numeric `SHOW SESSION STATUS` counter that the query
+    /// moved; only collected when `QueryOptions::collect_status_diff` is set
+    pub status_diff: HashMap<String, i64>,
+    /// The server's own account of the statement; only collected when
+    /// `QueryOptions::collect_server_stats` is set, and `None` when
+    /// performance_schema is disabled, its statement history consumers are
+    /// off, or it is not readable
+    pub server_stats: Option<ServerStats>,
+    /// The sanitized `QueryOptions::tag` the statement was sent with
+    pub tag: Option<String>,
+    /// Whether the connection used the compressed protocol
+    pub compressed: bool,
+    /// `rows` holds only the first rows of the result
+    /// (`QueryOptions::max_display_rows`); `row_count` still counts all
+    pub truncated: bool,
+    /// Execution stages of the statement, in order; only collected when
+    /// `QueryOptions::collect_stages` is set
+    pub stages: Vec<StageTiming>,
+    /// `collect_stages` was set but the server records no stage history
+    /// (performance_schema disabled, or the stage instruments or consumers off)
+    pub stages_unavailable: bool,
+    /// Protocol the statement was sent with
+    pub protocol: Protocol,
+    /// Text values in `rows` whose bytes were invalid in their column's
+    /// character set and were decoded with replacement characters; usually
+    /// a sign that the connection charset does not match the stored data
+    pub lossy_decodes: usize,
+}
+
+/// Outcome of a statement run with `MySQLRunner::run_exec`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExecResult {
+    /// Rows inserted, changed or deleted, as the server counts them
+    /// (`INSERT ... ON DUPLICATE KEY UPDATE` counts an update as 2)
+    pub affected_rows: u64,
+    /// `AUTO_INCREMENT` value generated for the first inserted row; `None`
+    /// when the statement generated none
+    pub last_insert_id: Option<u64>,
+    pub duration_ms: f64,
+}
+
+/// Statement metrics from `performance_schema.events_statements_history`
+///
+/// Unlike `QueryResult::duration_ms` these exclude network latency and
+/// client-side row decoding.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ServerStats {
+    /// Server execution time (`TIMER_WAIT`) in milliseconds
+    pub duration_ms: f64,
+    /// Rows the storage engines read (`ROWS_EXAMINED`)
+    pub rows_examined: u64,
+    /// Rows returned to the client (`ROWS_SENT`)
+    pub rows_sent: u64,
+}
+
+/// Time spent in one execution stage, from
+/// `performance_schema.events_stages_history_long`
+#[derive(Debug, Clone, PartialEq)]
+pub struct StageTiming {
+    /// Stage name without its `stage/sql/` prefix, e.g. `Sending data`
+    pub stage: String,
+    pub duration_ms: f64,
+}
+
+/// A single row of `SHOW WARNINGS`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MySQLWarning {
+    /// `Note`, `Warning` or `Error`
+    pub level: String,
+    pub code: u32,
+    pub message: String,
+}
+
+impl std::fmt::Display for MySQLWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({}): {}", self.level, self.code, self.message)
+    }
+}
+
+impl QueryResult {
+    /// Total time per stage name, longest first
+    pub fn stage_totals(&self) -> Vec<StageTiming> {
+        let mut totals: Vec<StageTiming> = Vec::new();
+        for timing in &self.stages {
+            match totals.iter_mut().find(|t| t.stage == timing.stage) {
+                Some(total) => total.duration_ms += timing.duration_ms,
+                None => totals.push(timing.clone()),
+            }
+        }
+        totals.sort_by(|a, b| b.duration_ms.total_cmp(&a.duration_ms));
+        totals
+    }
+
+    /// Rows formatted as display strings (NULL as `NULL`)
+    ///
+    /// NULL and the string `NULL` look the same here; check
+    /// `CellValue::is_null` on `rows`, or use `to_display_rows_with_null`.
+    pub fn to_display_rows(&self) -> Vec<Vec<String>> {
+        self.to_display_rows_with_null("NULL")
+    }
+
+    /// Rows formatted as display strings, with SQL NULL shown as `marker`
+    pub fn to_display_rows_with_null(&self, marker: &str) -> Vec<Vec<String>> {
+        self.rows
+            .iter()
+            .map(|row| row.iter().map(|v| v.display_with_null(marker)).collect())
+            .collect()
+    }
+
+    /// The first `limit` rows as a table, with SQL NULL shown as `null_marker`
+    ///
+    /// Numeric columns are right-aligned by their `column_types`; columns of
+    /// unknown type are checked for values that all parse as numbers.
+    pub fn to_table_with_options(
+        &self,
+        limit: usize,
+        null_marker: &str,
+        options: &TableFormatOptions,
+    ) -> String {
+        let rows: Vec<&Vec<CellValue>> = self.rows.iter().take(limit).collect();
+        let kinds: Vec<ColumnKind> = (0..self.columns.len())
+            .map(|i| match self.column_types.get(i) {
+                Some(ColumnType::Int | ColumnType::UInt) => ColumnKind::Integer,
+                Some(t) if t.is_numeric() => ColumnKind::Number,
+                Some(ColumnType::Unknown) | None => {
+                    let cells: Vec<String> = rows
+                        .iter()
+                        .filter_map(|row| row.get(i))
+                        .filter(|cell| !cell.is_null())
+                        .map(CellValue::format_value)
+                        .collect();
+                    ColumnKind::detect(cells.iter().map(String::as_str))
+                }
+                Some(_) => ColumnKind::Text,
+            })
+            .collect();
+        let display: Vec<Vec<String>> = rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|v| v.display_with_null(null_marker))
+                    .collect()
+            })
+            .collect();
+        format_table_with_kinds(&self.columns, &display, &kinds, options)
+    }
+
+    /// Serialize rows as a JSON array of objects keyed by column name
+    ///
+    /// Keys follow the order of `columns`; values are JSON strings, with
+    /// `NULL` cells rendered as JSON `null`. A repeated column name keeps
+    /// only one value per object; the rows of `to_json_document` keep them
+    /// all.
+    pub fn to_json(&self) -> String {
+        let objects: Vec<String> = self
+            .rows
+            .iter()
+            .map(|row| {
+                let fields: Vec<String> = self
+                    .columns
+                    .iter()
+                    .zip(row)
+                    .map(|(col, cell)| {
+                        let value = match cell {
+                            CellValue::Null => serde_json::Value::Null,
+                            v => serde_json::Value::String(v.format_value()),
+                        };
+                        format!("{}:{}", serde_json::Value::String(col.clone()), value)
+                    })
+                    .collect();
+                format!("{{{}}}", fields.join(","))
+            })
+            .collect();
+        format!("[{}]", objects.join(","))
+    }
+
+    /// Serialize the result as one JSON document with `columns`, `row_count`,
+    /// `duration_ms` and `rows`, keeping numbers as numbers
+    ///
+    /// Each row is an array parallel to `columns`. Every JSON document the
+    /// crate writes, for MySQL and DataFusion results alike, uses this shape.
+    pub fn to_json_document(&self) -> String {
+        serde_json::to_string(self).expect("QueryResult serializes to JSON")
+    }
+}
+
+/// Rows are arrays parallel to `columns`, so duplicate column names survive
+impl Serialize for QueryResult {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let fields = 4 + self.tag.is_some() as usize + self.compressed as usize;
+        let mut doc = serializer.serialize_struct("QueryResult", fields)?;
+        if let Some(tag) = &self.tag {
+            doc.serialize_field("tag", tag)?;
+        }
+        if self.compressed {
+            doc.serialize_field("compressed", &true)?;
+        }
+        doc.serialize_field("columns", &self.columns)?;
+        doc.serialize_field("row_count", &self.row_count)?;
+        doc.serialize_field("duration_ms", &self.duration_ms)?;
+        doc.serialize_field("rows", &self.rows)?;
+        doc.end()
+    }
+}
+
+/// Column type as reported by MySQL result set metadata
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    /// TINYINT, SMALLINT, MEDIUMINT, INT, BIGINT
+    Int,
+    /// The same integer types declared `UNSIGNED`
+    UInt,
+    /// YEAR; an integer, but not a quantity, so it is never grouped in
+    /// thousands
+    Year,
+    Float,
+    Double,
+    Decimal,
+    Date,
+    Time,
+    DateTime,
+    Timestamp,
+    /// CHAR, VARCHAR, TEXT, ENUM, SET
+    String,
+    /// BINARY, VARBINARY and BLOB (binary character set)
+    Blob,
+    Json,
+    Bit,
+    Geometry,
+    Null,
+    Unknown,
+}
+
+impl ColumnType {
+    /// Whether values of this type are numbers
+    pub fn is_numeric(&self) -> bool {
+        matches!(
+            self,
+            ColumnType::Int
+                | ColumnType::UInt
+                | ColumnType::Year
+                | ColumnType::Float
+                | ColumnType::Double
+                | ColumnType::Decimal
+        )
+    }
+}
+
+/// MySQL character set id of the `binary` pseudo-charset
+pub(crate) const BINARY_CHARSET: u16 = 63;
+
+impl ColumnType {
+    /// Column type from result set metadata
+    ///
+    /// MySQL reports TEXT as a BLOB type and VARBINARY as a string type; the
+    /// character set tells them apart. Integers are `UInt` when the column
+    /// has the `UNSIGNED` flag, which MySQL also sets on YEAR.
+    pub fn from_column(column: &Column) -> Self {
+        let binary = column.character_set() == BINARY_CHARSET;
+        let unsigned = column.flags().contains(ColumnFlags::UNSIGNED_FLAG);
+        match ColumnType::from(column.column_type()) {
+            ColumnType::Blob if !binary => ColumnType::String,
+            ColumnType::String if binary => ColumnType::Blob,
+            ColumnType::Int if unsigned => ColumnType::UInt,
+            other => other,
+        }
+    }
+}
+
+impl From<MySqlColumnType> for ColumnType {
+    fn from(t: MySqlColumnType) -> Self {
+        match t {
+            MySqlColumnType::MYSQL_TYPE_TINY
+            | MySqlColumnType::MYSQL_TYPE_SHORT
+            | MySqlColumnType::MYSQL_TYPE_INT24
+            | MySqlColumnType::MYSQL_TYPE_LONG
+            | MySqlColumnType::MYSQL_TYPE_LONGLONG => ColumnType::Int,
+            MySqlColumnType::MYSQL_TYPE_YEAR => ColumnType::Year,
+            MySqlColumnType::MYSQL_TYPE_FLOAT => ColumnType::Float,
+            MySqlColumnType::MYSQL_TYPE_DOUBLE => ColumnType::Double,
+            MySqlColumnType::MYSQL_TYPE_DECIMAL | MySqlColumnType::MYSQL_TYPE_NEWDECIMAL => {
+                ColumnType::Decimal
+            }
+            MySqlColumnType::MYSQL_TYPE_DATE | MySqlColumnType::MYSQL_TYPE_NEWDATE => {
+                ColumnType::Date
+            }
+            MySqlColumnType::MYSQL_TYPE_TIME | MySqlColumnType::MYSQL_TYPE_TIME2 => {
+                ColumnType::Time
+            }
+            MySqlColumnType::MYSQL_TYPE_DATETIME | MySqlColumnType::MYSQL_TYPE_DATETIME2 => {
+                ColumnType::DateTime
+            }
+            MySqlColumnType::MYSQL_TYPE_TIMESTAMP | MySqlColumnType::MYSQL_TYPE_TIMESTAMP2 => {
+                ColumnType::Timestamp
+            }
+            MySqlColumnType::MYSQL_TYPE_VARCHAR
+            | MySqlColumnType::MYSQL_TYPE_VAR_STRING
+            | MySqlColumnType::MYSQL_TYPE_STRING
+            | MySqlColumnType::MYSQL_TYPE_ENUM
+            | MySqlColumnType::MYSQL_TYPE_SET => ColumnType::String,
+            MySqlColumnType::MYSQL_TYPE_TINY_BLOB
+            | MySqlColumnType::MYSQL_TYPE_MEDIUM_BLOB
+            | MySqlColumnType::MYSQL_TYPE_LONG_BLOB
+            | MySqlColumnType::MYSQL_TYPE_BLOB => ColumnType::Blob,
+            MySqlColumnType::MYSQL_TYPE_JSON => ColumnType::Json,
+            MySqlColumnType::MYSQL_TYPE_BIT => ColumnType::Bit,
+            MySqlColumnType::MYSQL_TYPE_GEOMETRY => ColumnType::Geometry,
+            MySqlColumnType::MYSQL_TYPE_NULL => ColumnType::Null,
+            _ => ColumnType::Unknown,
+        }
+    }
+}
+
+/// Result set metadata of one column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColumnMeta {
+    /// Type as classified by `ColumnType::from_column`
+    pub column_type: ColumnType,
+    /// The column has the `UNSIGNED` flag
+    pub unsigned: bool,
+    /// Collation id of the values; 63 (`binary`) for binary data
+    pub charset: u16,
+    /// Digits after the decimal point for DECIMAL, FLOAT, DOUBLE and
+    /// fractional seconds
+    pub decimals: u8,
+    /// Maximum display width of a value
+    pub length: u32,
+}
+
+impl ColumnMeta {
+    pub fn from_column(column: &Column) -> Self {
+        ColumnMeta {
+            column_type: ColumnType::from_column(column),
+            unsigned: column.flags().contains(ColumnFlags::UNSIGNED_FLAG),
+            charset: column.character_set(),
+            decimals: column.decimals(),
+            length: column.column_length(),
+        }
+    }
+
+    /// Precision and scale of a DECIMAL column, or `None` for other types
+    ///
+    /// MySQL reports the display width, which counts the decimal point and
+    /// the sign of signed columns on top of the digits.
+    pub fn decimal_precision(&self) -> Option<(u8, u8)> {
+        if self.column_type != ColumnType::Decimal {
+            return None;
+        }
+        let extra = u32::from(self.decimals > 0) + u32::from(!self.unsigned);
+        let precision = self.length.checked_sub(extra)?;
+        (precision >= u32::from(self.decimals).max(1))
+            .then(|| (precision.min(u8::MAX.into()) as u8, self.decimals))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_result_to_json() {
+        let mut result = QueryResult {
+            row_count: 2,
+            duration_ms: 1.0,
+            exec_ms: 0.5,
+            fetch_ms: 0.5,
+            rows: vec![
+                vec![CellValue::Int(2), CellValue::Str("a\"b".to_string())],
+                vec![CellValue::Int(1), CellValue::Null],
+            ],
+            columns: vec!["z".to_string(), "a".to_string()],
+            column_types: vec![ColumnType::Int, ColumnType::String],
+            ..QueryResult::default()
+        };
+        assert_eq!(
+            result.to_json(),
+            r#"[{"z":"2","a":"a\"b"},{"z":"1","a":null}]"#
+        );
+
+        result.rows.clear();
+        assert_eq!(result.to_json(), "[]");
+    }
+
+    #[test]
+    fn test_stage_totals() {
+        let stage = |stage: &str, duration_ms| StageTiming {
+            stage: stage.to_string(),
+            duration_ms,
+        };
+        let result = QueryResult {
+            stages: vec![
+                stage("starting", 0.5),
+                stage("executing", 2.0),
+                stage("Creating sort index", 3.0),
+                stage("executing", 1.5),
+            ],
+            ..QueryResult::default()
+        };
+        assert_eq!(
+            result.stage_totals(),
+            vec![
+                stage("executing", 3.5),
+                stage("Creating sort index", 3.0),
+                stage("starting", 0.5),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_query_result_to_json_document() {
+        let result = QueryResult {
+            row_count: 2,
+            duration_ms: 1.5,
+            exec_ms: 1.0,
+            fetch_ms: 0.5,
+            rows: vec![
+                vec![
+                    CellValue::Int(-2),
+                    CellValue::Double(0.25),
+                    CellValue::Decimal("10.50".to_string()),
+                ],
+                vec![
+                    CellValue::UInt(7),
+                    CellValue::Null,
+                    CellValue::Str("x".to_string()),
+                ],
+            ],
+            columns: vec!["a".to_string(), "a".to_string(), "p".to_string()],
+            column_types: vec![ColumnType::Int, ColumnType::Double, ColumnType::Decimal],
+            ..QueryResult::default()
+        };
+        assert_eq!(
+            result.to_json_document(),
+            r#"{"columns":["a","a","p"],"row_count":2,"duration_ms":1.5,"rows":[[-2,0.25,"10.50"],[7,null,"x"]]}"#
+        );
+    }
+
+    #[test]
+    fn test_display_rows_null_marker() {
+        let result = QueryResult {
+            row_count: 2,
+            rows: vec![
+                vec![CellValue::Null],
+                vec![CellValue::Str("NULL".to_string())],
+            ],
+            columns: vec!["s".to_string()],
+            column_types: vec![ColumnType::String],
+            ..QueryResult::default()
+        };
+        assert_eq!(
+            result.to_display_rows_with_null(DEFAULT_NULL_MARKER),
+            vec![vec!["null"], vec!["NULL"]]
+        );
+        assert_eq!(result.to_display_rows_with_null("\\N")[0], vec!["\\N"]);
+        // The plain form keeps MySQL's spelling and cannot tell them apart
+        assert_eq!(result.to_display_rows(), vec![vec!["NULL"], vec!["NULL"]]);
+    }
+
+    #[test]
+    fn test_query_result_to_table_aligns_by_type() {
+        // YEAR is right-aligned like a number, but not grouped
+        let result = QueryResult {
+            row_count: 3,
+            rows: vec![
+                vec![
+                    CellValue::Str("CHINA".to_string()),
+                    CellValue::Int(1997),
+                    CellValue::Int(1234567),
+                    CellValue::Str("4172806.25".to_string()),
+                    CellValue::Str("12".to_string()),
+                ],
+                vec![
+                    CellValue::Str("PERU".to_string()),
+                    CellValue::Int(1998),
+                    CellValue::Int(42),
+                    CellValue::Null,
+                    CellValue::Str("7".to_string()),
+                ],
+                vec![
+                    CellValue::Str("ALGERIA".to_string()),
+                    CellValue::Int(10),
+                    CellValue::Int(-5000),
+                    CellValue::Str("-3.5".to_string()),
+                    CellValue::Str("x".to_string()),
+                ],
+            ],
+            columns: vec![
+                "nation".into(),
+                "year".into(),
+                "orders".into(),
+                "revenue".into(),
+                "code".into(),
+            ],
+            column_types: vec![
+                ColumnType::String,
+                ColumnType::Year,
+                ColumnType::Int,
+                ColumnType::Decimal,
+                ColumnType::String,
+            ],
+            ..QueryResult::default()
+        };
+        let options = TableFormatOptions {
+            thousands_separators: true,
+            ..TableFormatOptions::default()
+        };
+        assert_eq!(
+            result.to_table_with_options(10, DEFAULT_NULL_MARKER, &options),
+            "+---------+------+-----------+------------+------+\n\
+             | nation  | year |    orders |    revenue | code |\n\
+             +---------+------+-----------+------------+------+\n\
+             | CHINA   | 1997 | 1,234,567 | 4172806.25 | 12   |\n\
+             | PERU    | 1998 |        42 |       null | 7    |\n\
+             | ALGERIA |   10 |    -5,000 |       -3.5 | x    |\n\
+             +---------+------+-----------+------------+------+\n"
+        );
+        assert!(result
+            .to_table_with_options(10, "\\N", &options)
+            .contains("|         \\N |"));
+        // Only the first `limit` rows are shown
+        assert_eq!(
+            result
+                .to_table_with_options(1, DEFAULT_NULL_MARKER, &options)
+                .lines()
+                .count(),
+            5
+        );
+    }
+}
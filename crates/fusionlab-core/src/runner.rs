@@ -0,0 +1,2320 @@
+//! `MySQLRunner`: pooled connections, retries and timed query execution
+
+use mysql_async::{prelude::*, Conn, Opts, OptsBuilder, Params, Pool, Row, Statement};
+use std::collections::{HashMap, HashSet};
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::script::statement_line;
+use crate::statement::{classify, explain_sql, is_locking_read, tag_sql, StatementKind};
+use crate::stmt_cache::StmtCache;
+use crate::streaming::fetch_rows;
+use crate::{
+    ExecResult, FusionLabError, MySQLConfig, MySQLExplainPlan, MySQLWarning, Protocol,
+    QueryOptions, QueryResult, QueryStats, Result, ServerInfo, ServerStats, SslConfig, SslMode,
+    StageTiming, StmtCacheStats, TableFormatOptions, DEFAULT_NULL_MARKER,
+};
+
+/// Snapshot of a runner's connection usage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolStatus {
+    /// Connections currently checked out by running queries
+    pub active: usize,
+    /// Connections that could still be opened or reused before callers
+    /// have to wait (`max - active`)
+    pub idle: usize,
+    /// Configured upper bound (`PoolConfig::max`)
+    pub max: usize,
+}
+
+/// MySQL query runner with timing support
+pub struct MySQLRunner {
+    pool: Pool,
+    /// Plain-connection pool for `SslMode::Preferred`, used once the server
+    /// turns out not to support TLS
+    plain_pool: Option<Pool>,
+    tls_unavailable: AtomicBool,
+    /// Version string, number and flavor, queried on first use
+    server_info: tokio::sync::OnceCell<ServerInfo>,
+    /// Whether performance_schema statement history is readable, checked on
+    /// the first query with `QueryOptions::collect_server_stats`
+    statement_history: tokio::sync::OnceCell<bool>,
+    /// Whether stage history is recorded, checked on the first query with
+    /// `QueryOptions::collect_stages`
+    stage_history: tokio::sync::OnceCell<bool>,
+    /// Whether the server accepted compression, checked on the first
+    /// connection when `MySQLConfig::compress` is set
+    compression: tokio::sync::OnceCell<bool>,
+    compress: bool,
+    connect_timeout: Option<Duration>,
+    connect_retries: u32,
+    ssl: Option<SslConfig>,
+    acquire_timeout: Option<Duration>,
+    max_connections: usize,
+    /// Ids of the connections currently checked out through `get_conn`,
+    /// as `CONNECTION_ID()` reports them
+    active: Arc<Mutex<HashSet<u32>>>,
+    /// Options of the pool's connections, for connections opened outside it
+    opts: Opts,
+    /// `opts` without TLS, for `SslMode::Preferred`
+    plain_opts: Option<Opts>,
+    /// Connection opened outside the pool for `run_query_params`, so its
+    /// prepared statements survive between calls without taking a pool slot
+    stmt_conn: tokio::sync::Mutex<Option<Conn>>,
+    /// Id of `stmt_conn`, 0 when it is not open
+    stmt_conn_id: AtomicU32,
+    /// Statements prepared on `stmt_conn`
+    stmt_cache: Mutex<StmtCache<Statement>>,
+    retry_on_disconnect: bool,
+}
+
+impl MySQLRunner {
+    /// Create a new MySQL runner with the given configuration
+    pub fn new(config: &MySQLConfig) -> Result<Self> {
+        let opts = config.opts()?;
+        let plain_opts = match &config.ssl {
+            Some(ssl) if ssl.mode == SslMode::Preferred => {
+                let plain = MySQLConfig {
+                    ssl: None,
+                    ..config.clone()
+                };
+                Some(plain.opts()?)
+            }
+            _ => None,
+        };
+        Ok(Self {
+            pool: Pool::new(opts.clone()),
+            plain_pool: plain_opts.clone().map(Pool::new),
+            tls_unavailable: AtomicBool::new(false),
+            server_info: tokio::sync::OnceCell::new(),
+            statement_history: tokio::sync::OnceCell::new(),
+            stage_history: tokio::sync::OnceCell::new(),
+            compression: tokio::sync::OnceCell::new(),
+            compress: config.compress,
+            connect_timeout: config.connect_timeout_ms.map(Duration::from_millis),
+            connect_retries: config.connect_retries,
+            ssl: config.ssl.clone(),
+            acquire_timeout: (config.pool.acquire_timeout_ms > 0)
+                .then(|| Duration::from_millis(config.pool.acquire_timeout_ms)),
+            max_connections: config.pool.max,
+            active: Arc::new(Mutex::new(HashSet::new())),
+            opts,
+            plain_opts,
+            stmt_conn: tokio::sync::Mutex::new(None),
+            stmt_conn_id: AtomicU32::new(0),
+            stmt_cache: Mutex::new(StmtCache::new(config.stmt_cache_size)),
+            retry_on_disconnect: config.retry_on_disconnect,
+        })
+    }
+
+    /// Current connection usage, e.g. for logging pool saturation
+    ///
+    /// mysql_async does not expose its internal idle list, so `idle` is the
+    /// remaining headroom under `PoolConfig::max` rather than the number of
+    /// open idle sockets.
+    pub fn pool_status(&self) -> PoolStatus {
+        let active = self.active.lock().unwrap().len();
+        PoolStatus {
+            active,
+            idle: self.max_connections.saturating_sub(active),
+            max: self.max_connections,
+        }
+    }
+
+    /// Get a connection from the pool, bounded by the pool's acquire timeout
+    pub(crate) async fn get_conn(&self) -> Result<RunnerConn> {
+        let conn = match self.acquire_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, self.connect_with_retries())
+                .await
+                .map_err(|_| {
+                    let status = self.pool_status();
+                    FusionLabError::Connection(format!(
+                        "timed out after {}ms waiting for a pooled connection ({} of {} in use)",
+                        timeout.as_millis(),
+                        status.active,
+                        status.max
+                    ))
+                })??,
+            None => self.connect_with_retries().await?,
+        };
+        let mut conn = RunnerConn::new(conn, &self.active);
+        if self.compress {
+            self.compression
+                .get_or_init(|| compression_enabled(&mut conn))
+                .await;
+        }
+        Ok(conn)
+    }
+
+    /// Whether queries run over the compressed protocol; false until the
+    /// first connection is made
+    pub fn compressed(&self) -> bool {
+        self.compression.get().copied().unwrap_or(false)
+    }
+
+    /// Get a connection from the pool
+    ///
+    /// Connection errors and timeouts are retried up to `connect_retries`
+    /// times, doubling the delay between attempts. Other errors (e.g. access
+    /// denied) are returned immediately.
+    async fn connect_with_retries(&self) -> Result<Conn> {
+        let attempts = self.connect_retries + 1;
+        let mut backoff = CONNECT_BACKOFF_INITIAL;
+
+        for attempt in 1..=attempts {
+            let error = match self.try_get_conn().await {
+                Ok(conn) => return Ok(conn),
+                Err(ConnectError::Fatal(e)) => return Err(e),
+                Err(ConnectError::Retryable(msg)) => msg,
+            };
+
+            if attempt == attempts {
+                return Err(FusionLabError::Connection(format!(
+                    "giving up after {} attempt(s): {}",
+                    attempts, error
+                )));
+            }
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+        unreachable!("loop returns on the last attempt")
+    }
+
+    /// A single connection attempt, bounded by `connect_timeout`
+    async fn try_get_conn(&self) -> std::result::Result<Conn, ConnectError> {
+        let result = match &self.plain_pool {
+            Some(plain) if self.tls_unavailable.load(Ordering::Relaxed) => {
+                self.timed_get_conn(plain).await?
+            }
+            _ => self.timed_get_conn(&self.pool).await?,
+        };
+        let result = match (result, &self.plain_pool) {
+            (
+                Err(mysql_async::Error::Driver(
+                    mysql_async::DriverError::NoClientSslFlagFromServer,
+                )),
+                Some(plain),
+            ) => {
+                // SslMode::Preferred: stay on plain connections from now on
+                self.tls_unavailable.store(true, Ordering::Relaxed);
+                self.timed_get_conn(plain).await?
+            }
+            (result, _) => result,
+        };
+        result.map_err(|e| match e {
+            mysql_async::Error::Driver(mysql_async::DriverError::NoClientSslFlagFromServer) => {
+                ConnectError::Fatal(FusionLabError::Connection(
+                    "TLS is required but the server does not support it".to_string(),
+                ))
+            }
+            mysql_async::Error::Io(mysql_async::IoError::Tls(tls)) => {
+                let ca = self
+                    .ssl
+                    .as_ref()
+                    .map(SslConfig::ca_description)
+                    .unwrap_or_default();
+                ConnectError::Fatal(FusionLabError::Connection(format!(
+                    "TLS handshake failed (verifying with {}): {}",
+                    ca, tls
+                )))
+            }
+            mysql_async::Error::Io(_)
+            | mysql_async::Error::Driver(mysql_async::DriverError::ConnectionClosed) => {
+                ConnectError::Retryable(e.to_string())
+            }
+            e => ConnectError::Fatal(e.into()),
+        })
+    }
+
+    /// Open a connection outside the pool, so it never waits for a free
+    /// pool slot; gives up after `timeout`
+    ///
+    /// `stmt_cache_size` replaces the driver's statement cache size.
+    async fn open_unpooled(
+        &self,
+        stmt_cache_size: Option<usize>,
+        timeout: Option<Duration>,
+    ) -> Result<Conn> {
+        let connect = |opts: &Opts| {
+            let opts: Opts = match stmt_cache_size {
+                Some(size) => OptsBuilder::from_opts(opts.clone())
+                    .stmt_cache_size(size)
+                    .into(),
+                None => opts.clone(),
+            };
+            async move {
+                match timeout {
+                    Some(timeout) => tokio::time::timeout(timeout, Conn::new(opts))
+                        .await
+                        .map_err(|_| {
+                            FusionLabError::Connection(format!(
+                                "timed out after {}ms",
+                                timeout.as_millis()
+                            ))
+                        })?
+                        .map_err(FusionLabError::from),
+                    None => Ok(Conn::new(opts).await?),
+                }
+            }
+        };
+        let plain = match &self.plain_opts {
+            Some(plain) if self.tls_unavailable.load(Ordering::Relaxed) => {
+                return connect(plain).await
+            }
+            plain => plain,
+        };
+        match (connect(&self.opts).await, plain) {
+            (
+                Err(FusionLabError::MySQL(mysql_async::Error::Driver(
+                    mysql_async::DriverError::NoClientSslFlagFromServer,
+                ))),
+                Some(plain),
+            ) => {
+                self.tls_unavailable.store(true, Ordering::Relaxed);
+                connect(plain).await
+            }
+            (result, _) => result,
+        }
+    }
+
+    /// `pool.get_conn()` with the per-attempt `connect_timeout` applied
+    async fn timed_get_conn(
+        &self,
+        pool: &Pool,
+    ) -> std::result::Result<mysql_async::Result<Conn>, ConnectError> {
+        match self.connect_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, pool.get_conn())
+                .await
+                .map_err(|_| {
+                    ConnectError::Retryable(format!("timed out after {}ms", timeout.as_millis()))
+                }),
+            None => Ok(pool.get_conn().await),
+        }
+    }
+
+    /// Server version as (major, minor, patch), from the cached `server_info`
+    pub async fn server_version(&self) -> Result<(u16, u16, u16)> {
+        Ok(self.server_info().await?.number)
+    }
+
+    /// Server version string and flavor, queried once and then cached
+    pub async fn server_info(&self) -> Result<ServerInfo> {
+        let info = self
+            .server_info
+            .get_or_try_init(|| async {
+                let result = self
+                    .run_query("SELECT VERSION(), @@version_comment")
+                    .await?;
+                let row = result.rows.first().ok_or_else(|| {
+                    FusionLabError::Parse("SELECT VERSION() returned no rows".to_string())
+                })?;
+                let cell = |i: usize| row.get(i).map(|c| c.format_value()).unwrap_or_default();
+                Ok::<_, FusionLabError>(ServerInfo::parse(&cell(0), &cell(1)))
+            })
+            .await?;
+        Ok(info.clone())
+    }
+
+    /// Fail with `Unsupported` unless the server has a MySQL feature
+    /// introduced in version `min` (never true for MariaDB)
+    async fn require_version(&self, feature: &str, min: (u16, u16, u16)) -> Result<()> {
+        let info = self.server_info().await?;
+        if !info.supports_mysql_feature(min) {
+            return Err(FusionLabError::Unsupported {
+                feature: feature.to_string(),
+                server: info.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Run a query and return results with timing
+    pub async fn run_query(&self, sql: &str) -> Result<QueryResult> {
+        self.run_query_with_options(sql, &QueryOptions::default())
+            .await
+    }
+
+    /// Run a query with explicit `QueryOptions`
+    pub async fn run_query_with_options(
+        &self,
+        sql: &str,
+        options: &QueryOptions,
+    ) -> Result<QueryResult> {
+        self.run_query_streamed(sql, None, options, |_| {}).await
+    }
+
+    /// Run a statement that returns no rows, e.g. `INSERT` or `UPDATE`
+    ///
+    /// Sent over the text protocol, so statements that cannot be prepared
+    /// (most DDL, `LOAD DATA`) work too. Rows of a statement that does
+    /// return some are discarded. Never retried, since the statement may
+    /// have taken effect before a connection was lost.
+    pub async fn run_exec(&self, sql: &str) -> Result<ExecResult> {
+        let mut conn = self.get_conn().await?;
+        let start = Instant::now();
+        conn.query_drop(sql).await?;
+        Ok(ExecResult {
+            affected_rows: conn.affected_rows(),
+            last_insert_id: conn.last_insert_id(),
+            duration_ms: start.elapsed().as_secs_f64() * 1000.0,
+        })
+    }
+
+    /// Run a multi-statement script on a single connection
+    ///
+    /// The script is split with `split_statements`, which honours `DELIMITER`
+    /// lines, and the statements run in order, so session state (`SET`,
+    /// temporary tables) carries over. Returns one result per statement, each
+    /// with its own timing; statements without a result set report
+    /// `affected_rows`. Stops at the first failing statement.
+    pub async fn run_script(&self, sql: &str) -> Result<Vec<QueryResult>> {
+        self.run_script_inner(sql, None).await
+    }
+
+    /// Like `run_script`, but tag each statement with `<source>:<line>`,
+    /// the line of the script it starts on (see `QueryOptions::tag`)
+    pub async fn run_script_tagged(&self, sql: &str, source: &str) -> Result<Vec<QueryResult>> {
+        self.run_script_inner(sql, Some(source)).await
+    }
+
+    async fn run_script_inner(&self, sql: &str, source: Option<&str>) -> Result<Vec<QueryResult>> {
+        let mut conn = self.get_conn().await?;
+        let mut results = Vec::new();
+        for statement in split_statements(sql) {
+            let tagged = source.map(|source| {
+                let tag = format!("{}:{}", source, statement_line(sql, statement));
+                tag_sql(&tag, statement)
+            });
+            let start = Instant::now();
+            let result = match &tagged {
+                Some((_, tagged_sql)) => conn.query_iter(tagged_sql).await?,
+                None => conn.query_iter(statement).await?,
+            };
+            let fetched = fetch_rows(result, start, usize::MAX, |_| {}).await?;
+            let mut result = fetched.into_query_result(vec![], self.compressed(), Protocol::Text);
+            result.tag = tagged.map(|(tag, _)| tag);
+            results.push(result);
+        }
+        Ok(results)
+    }
+
+    /// Run a query `warmup + iterations` times and summarize the timings
+    ///
+    /// Warmup runs are executed but excluded from the statistics. Each
+    /// sample is the query's `duration_ms` (execution plus fetch).
+    pub async fn run_query_repeat(
+        &self,
+        sql: &str,
+        iterations: usize,
+        warmup: usize,
+    ) -> Result<QueryStats> {
+        for _ in 0..warmup {
+            self.run_query(sql).await?;
+        }
+        let mut samples = Vec::with_capacity(iterations);
+        let mut row_count = 0;
+        for _ in 0..iterations {
+            let result = self.run_query(sql).await?;
+            samples.push(result.duration_ms);
+            row_count = result.row_count;
+        }
+        Ok(QueryStats::from_samples(samples, warmup, row_count))
+    }
+
+    /// Run a query with `?` placeholders bound to `params`
+    ///
+    /// Uses the prepared statement protocol. Statements are cached by SQL text
+    /// (up to `MySQLConfig::stmt_cache_size`) on one connection the runner
+    /// opens outside the pool, since the pool resets connections, which
+    /// drops their statements. That connection runs one query at a time;
+    /// concurrent calls fall back to a pooled connection, prepare the
+    /// statement again and close it afterwards. Preparation counts towards
+    /// `exec_ms`.
+    pub async fn run_query_params(
+        &self,
+        sql: &str,
+        params: impl Into<Params> + Send,
+    ) -> Result<QueryResult> {
+        self.run_query_params_with_options(sql, params, &QueryOptions::default())
+            .await
+    }
+
+    /// Like `run_query_params`, with explicit `QueryOptions`
+    ///
+    /// `QueryOptions::protocol` is ignored. Queries that set
+    /// `QueryOptions::session_vars` always run on a pooled connection, so
+    /// the variables do not outlive the query; a tag makes the tagged text
+    /// the cache key.
+    pub async fn run_query_params_with_options(
+        &self,
+        sql: &str,
+        params: impl Into<Params> + Send,
+        options: &QueryOptions,
+    ) -> Result<QueryResult> {
+        let params = params.into();
+        let stmt_conn = if options.session_vars.is_empty()
+            && self.stmt_cache.lock().unwrap().stats().capacity > 0
+        {
+            self.stmt_conn.try_lock().ok()
+        } else {
+            None
+        };
+        if let Some(mut slot) = stmt_conn {
+            let result = self
+                .run_cached_statement(&mut slot, sql, params, options)
+                .await;
+            // Statements belong to the connection; drop both unless the server
+            // merely rejected the query
+            let keep = matches!(
+                &result,
+                Ok(_) | Err(FusionLabError::MySQL(mysql_async::Error::Server(_)))
+            );
+            if !keep {
+                *slot = None;
+                self.stmt_conn_id.store(0, Ordering::Relaxed);
+                self.stmt_cache.lock().unwrap().clear();
+            }
+            return result;
+        }
+
+        self.stmt_cache.lock().unwrap().record_miss();
+        let mut conn = self.get_conn().await?;
+        self.run_on_conn(
+            &mut conn,
+            sql,
+            Execute::Prepared(params),
+            None,
+            options,
+            |_| {},
+        )
+        .await
+    }
+
+    /// Execute on the statement connection, opening it first if needed
+    async fn run_cached_statement(
+        &self,
+        slot: &mut Option<Conn>,
+        sql: &str,
+        params: Params,
+        options: &QueryOptions,
+    ) -> Result<QueryResult> {
+        let conn = match slot {
+            Some(conn) => conn,
+            None => {
+                // The runner's cache closes these statements; the driver's
+                // would close them behind its back when it evicts them
+                let conn = self.open_unpooled(Some(0), self.connect_timeout).await?;
+                self.stmt_conn_id.store(conn.id(), Ordering::Relaxed);
+                slot.insert(conn)
+            }
+        };
+        self.run_on_conn(conn, sql, Execute::Cached(params), None, options, |_| {})
+            .await
+    }
+
+    /// Statement for `sql` from the cache, prepared on `conn` on a miss
+    async fn cached_statement(&self, conn: &mut Conn, sql: &str) -> Result<Statement> {
+        let cached = self.stmt_cache.lock().unwrap().get(sql);
+        if let Some(stmt) = cached {
+            return Ok(stmt);
+        }
+        let stmt = conn.prep(sql).await?;
+        let evicted = self
+            .stmt_cache
+            .lock()
+            .unwrap()
+            .insert(sql.to_string(), stmt.clone());
+        if let Some(evicted) = evicted {
+            conn.close(evicted).await?;
+        }
+        Ok(stmt)
+    }
+
+    /// Hit and miss counts of the prepared statement cache
+    pub fn cache_stats(&self) -> StmtCacheStats {
+        self.stmt_cache.lock().unwrap().stats()
+    }
+
+    /// Run a query, streaming rows from the server one at a time
+    ///
+    /// `on_row` is invoked for every row as it arrives. Only the first
+    /// `max_rows_retained` rows are kept in the returned `QueryResult.rows`
+    /// (all of them when `None`; `QueryOptions::max_display_rows` applies if
+    /// it is smaller), while `row_count` always counts every row. Use this
+    /// for large result sets that would not fit in memory.
+    ///
+    /// With `MySQLConfig::retry_on_disconnect`, a `SELECT` whose connection
+    /// dies before the first row arrives (`is_disconnect`) runs once more on
+    /// another connection. Other statements may have taken effect before the
+    /// connection was lost, so they are never retried, and neither are
+    /// locking reads (`FOR UPDATE`, `FOR SHARE`, `LOCK IN SHARE MODE`): their
+    /// locks belonged to the lost connection's transaction.
+    pub async fn run_query_streamed<F>(
+        &self,
+        sql: &str,
+        max_rows_retained: Option<usize>,
+        options: &QueryOptions,
+        mut on_row: F,
+    ) -> Result<QueryResult>
+    where
+        F: FnMut(&Row),
+    {
+        let retry = self.retry_on_disconnect
+            && classify(sql) == StatementKind::Select
+            && !is_locking_read(sql);
+        let mut rows_seen = 0usize;
+        let result = self
+            .run_query_streamed_once(sql, max_rows_retained, options, |row: &Row| {
+                rows_seen += 1;
+                on_row(row)
+            })
+            .await;
+        match result {
+            // Rows already passed to `on_row` cannot be taken back
+            Err(e) if retry && rows_seen == 0 && is_disconnect(&e) => {
+                self.run_query_streamed_once(sql, max_rows_retained, options, on_row)
+                    .await
+            }
+            result => result,
+        }
+    }
+
+    async fn run_query_streamed_once<F>(
+        &self,
+        sql: &str,
+        max_rows_retained: Option<usize>,
+        options: &QueryOptions,
+        on_row: F,
+    ) -> Result<QueryResult>
+    where
+        F: FnMut(&Row),
+    {
+        let mut conn = self.get_conn().await?;
+        self.run_on_conn(
+            &mut conn,
+            sql,
+            Execute::Query,
+            max_rows_retained,
+            options,
+            on_row,
+        )
+        .await
+    }
+
+    /// Run `sql` on `conn` with everything `options` asks for around it:
+    /// session variables, the tag, the timeout, warnings and the server's
+    /// statistics
+    async fn run_on_conn<F>(
+        &self,
+        conn: &mut Conn,
+        sql: &str,
+        execute: Execute,
+        max_rows_retained: Option<usize>,
+        options: &QueryOptions,
+        on_row: F,
+    ) -> Result<QueryResult>
+    where
+        F: FnMut(&Row),
+    {
+        let limit = max_rows_retained
+            .into_iter()
+            .chain(options.max_display_rows)
+            .min()
+            .unwrap_or(usize::MAX);
+        let conn_id = conn.id();
+
+        if let Some(set) = session_vars_sql(&options.session_vars)? {
+            conn.query_drop(set).await?;
+        }
+        // Checked before the marker, so its statements are not counted
+        let stages_enabled = options.collect_stages
+            && *self
+                .stage_history
+                .get_or_init(|| stage_history_enabled(conn))
+                .await;
+        let history_marker = if (options.collect_server_stats || stages_enabled)
+            && *self
+                .statement_history
+                .get_or_init(|| statement_history_enabled(conn))
+                .await
+        {
+            statement_marker(conn).await
+        } else {
+            None
+        };
+        let status_before = if options.collect_status_diff {
+            // Two snapshots measure what SHOW STATUS itself adds to the counters
+            let first = session_status(conn).await?;
+            Some((first, session_status(conn).await?))
+        } else {
+            None
+        };
+
+        let tagged = options.tag.as_deref().map(|tag| tag_sql(tag, sql));
+        let sql = tagged
+            .as_ref()
+            .map_or(sql, |(_, tagged_sql)| tagged_sql.as_str());
+
+        // The binary protocol without parameters is a prepared statement too
+        let execute = match (execute, options.protocol) {
+            (Execute::Query, Protocol::Binary) => Execute::Prepared(Params::Empty),
+            (execute, _) => execute,
+        };
+        let protocol = match execute {
+            Execute::Query => Protocol::Text,
+            Execute::Prepared(_) | Execute::Cached(_) => Protocol::Binary,
+        };
+        let start = Instant::now();
+        let fetch = async {
+            match execute {
+                Execute::Query => {
+                    let result = conn.query_iter(sql).await?;
+                    fetch_rows(result, start, limit, on_row).await
+                }
+                Execute::Prepared(params) => {
+                    let stmt = conn.prep(sql).await?;
+                    let result = conn.exec_iter(&stmt, params).await?;
+                    let fetched = fetch_rows(result, start, limit, on_row).await?;
+                    conn.close(stmt).await?;
+                    Ok(fetched)
+                }
+                Execute::Cached(params) => {
+                    let stmt = self.cached_statement(conn, sql).await?;
+                    let result = conn.exec_iter(&stmt, params).await?;
+                    fetch_rows(result, start, limit, on_row).await
+                }
+            }
+        };
+        let fetched = match options.timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, fetch).await {
+                Ok(fetched) => fetched?,
+                Err(_) => {
+                    let elapsed_ms = start.elapsed().as_millis() as u64;
+                    // The statement keeps running server-side until killed
+                    self.kill_query(conn_id).await;
+                    return Err(FusionLabError::Timeout { elapsed_ms });
+                }
+            },
+            None => fetch.await?,
+        };
+
+        // Must run on the same connection to see the warnings of this statement
+        let warnings = if options.collect_warnings && conn.get_warnings() > 0 {
+            fetch_warnings(conn).await
+        } else {
+            vec![]
+        };
+
+        let status_diff = match &status_before {
+            Some((first, second)) => {
+                let after = session_status(conn).await?;
+                status_diff(first, second, &after)
+            }
+            None => HashMap::new(),
+        };
+
+        // Read last, so that the statistics queries do not show up in the
+        // warnings or the status diff
+        // The two status snapshots ran between the marker and the query
+        let skip = if status_before.is_some() { 2 } else { 0 };
+        let server_stats = match history_marker {
+            Some(marker) if options.collect_server_stats => {
+                fetch_server_stats(conn, marker, skip).await
+            }
+            _ => None,
+        };
+        let stages = match history_marker {
+            Some(marker) if stages_enabled => fetch_stages(conn, marker, skip).await,
+            _ => None,
+        };
+
+        let mut result = fetched.into_query_result(warnings, self.compressed(), protocol);
+        result.status_diff = status_diff;
+        result.server_stats = server_stats;
+        result.stages_unavailable = options.collect_stages && stages.is_none();
+        result.stages = stages.unwrap_or_default();
+        result.tag = tagged.map(|(tag, _)| tag);
+        Ok(result)
+    }
+
+    /// Run a query, aborting it with `FusionLabError::Timeout` after `timeout`
+    pub async fn run_query_with_timeout(
+        &self,
+        sql: &str,
+        timeout: Duration,
+    ) -> Result<QueryResult> {
+        let options = QueryOptions {
+            timeout: Some(timeout),
+            ..QueryOptions::default()
+        };
+        self.run_query_with_options(sql, &options).await
+    }
+
+    /// Stop every statement this runner is executing, e.g. on ctrl-c
+    ///
+    /// Sends `KILL QUERY` for each checked-out connection from an auxiliary
+    /// connection opened outside the pool, so it works even when every pool
+    /// slot is busy; most interrupted statements fail with "Query execution
+    /// was interrupted" (`SLEEP()` returns 1 instead). Connection ids come
+    /// from the handshake, so tracking them costs no extra round trip.
+    /// Returns how many statements were killed.
+    pub async fn kill_running(&self) -> Result<u32> {
+        let mut ids: Vec<u32> = self.active.lock().unwrap().iter().copied().collect();
+        // `stmt_conn` is outside the pool; it is locked while it runs a query
+        let stmt_conn_id = self.stmt_conn_id.load(Ordering::Relaxed);
+        if stmt_conn_id != 0 && self.stmt_conn.try_lock().is_err() {
+            ids.push(stmt_conn_id);
+        }
+        if ids.is_empty() {
+            return Ok(0);
+        }
+
+        let mut conn = self.open_kill_conn().await?;
+        let mut killed = 0;
+        let mut outcome = Ok(());
+        for id in ids {
+            match conn.query_drop(format!("KILL QUERY {}", id)).await {
+                Ok(()) => killed += 1,
+                // Finished and disconnected in the meantime
+                Err(mysql_async::Error::Server(e)) if e.code == ER_NO_SUCH_THREAD => {}
+                Err(e) => {
+                    outcome = Err(e.into());
+                    break;
+                }
+            }
+        }
+        conn.disconnect().await.ok();
+        outcome.map(|()| killed)
+    }
+
+    /// Stop the statement running on connection `conn_id`, from a second connection
+    ///
+    /// Best effort: errors are ignored since the caller is already failing.
+    async fn kill_query(&self, conn_id: u32) {
+        if let Ok(mut conn) = self.open_kill_conn().await {
+            conn.query_drop(format!("KILL QUERY {}", conn_id))
+                .await
+                .ok();
+            conn.disconnect().await.ok();
+        }
+    }
+
+    /// Connection for `KILL QUERY`, opened outside the pool since the pool
+    /// may be exhausted by the very statements being killed
+    async fn open_kill_conn(&self) -> Result<Conn> {
+        let timeout = self
+            .connect_timeout
+            .map_or(KILL_CONNECT_TIMEOUT, |t| t.min(KILL_CONNECT_TIMEOUT));
+        self.open_unpooled(None, Some(timeout)).await
+    }
+
+    /// Run EXPLAIN on a query and return the output
+    pub async fn run_explain(&self, sql: &str) -> Result<String> {
+        let result = self.run_explain_rows(sql).await?;
+        Ok(result.to_table_with_options(
+            usize::MAX,
+            DEFAULT_NULL_MARKER,
+            &TableFormatOptions::default(),
+        ))
+    }
+
+    /// Run EXPLAIN on a query and return its rows unformatted
+    ///
+    /// One row per step of the plan, with the server's columns (`table`,
+    /// `type`, `key`, `rows`, `Extra`, ...), so tooling can check the chosen
+    /// index or row estimates.
+    pub async fn run_explain_rows(&self, sql: &str) -> Result<QueryResult> {
+        self.run_query(&explain_sql("EXPLAIN", sql)?).await
+    }
+
+    /// Run EXPLAIN FORMAT=JSON on a query and parse it into a structured plan
+    pub async fn run_explain_json(&self, sql: &str) -> Result<MySQLExplainPlan> {
+        let result = self
+            .run_query(&explain_sql("EXPLAIN FORMAT=JSON", sql)?)
+            .await?;
+
+        // FORMAT=JSON returns a single row with a single EXPLAIN column
+        let json = result
+            .rows
+            .first()
+            .and_then(|row| row.first())
+            .map(|cell| cell.format_value())
+            .ok_or_else(|| FusionLabError::Parse("EXPLAIN returned no rows".to_string()))?;
+
+        MySQLExplainPlan::parse(&json)
+    }
+
+    /// Run EXPLAIN FORMAT=TREE on a query (MySQL 8.0.16+)
+    ///
+    /// Returns `FusionLabError::Unsupported` on older servers and MariaDB.
+    pub async fn run_explain_tree(&self, sql: &str) -> Result<String> {
+        self.require_version("EXPLAIN FORMAT=TREE", (8, 0, 16))
+            .await?;
+
+        let result = self
+            .run_query(&explain_sql("EXPLAIN FORMAT=TREE", sql)?)
+            .await?;
+
+        Ok(result
+            .rows
+            .iter()
+            .map(|row| row.first().map(|c| c.format_value()).unwrap_or_default())
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+
+    /// Run EXPLAIN ANALYZE on a query (MySQL 8.0.18+)
+    ///
+    /// Returns `FusionLabError::Unsupported` on older servers and MariaDB.
+    pub async fn run_explain_analyze(&self, sql: &str) -> Result<String> {
+        self.require_version("EXPLAIN ANALYZE", (8, 0, 18)).await?;
+        let result = self
+            .run_query(&explain_sql("EXPLAIN ANALYZE", sql)?)
+            .await?;
+
+        // EXPLAIN ANALYZE returns a single column with the tree output
+        let output: String = result
+            .rows
+            .iter()
+            .map(|row| row.first().map(|c| c.format_value()).unwrap_or_default())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        Ok(output)
+    }
+
+    /// Close the connection pool
+    pub async fn close(self) {
+        if let Some(conn) = self.stmt_conn.into_inner() {
+            conn.disconnect().await.ok();
+        }
+        self.pool.disconnect().await.ok();
+        if let Some(plain) = self.plain_pool {
+            plain.disconnect().await.ok();
+        }
+    }
+}
+
+/// A pooled connection, counted as active in `pool_status` and killed by
+/// `kill_running` until dropped
+pub(crate) struct RunnerConn {
+    conn: Conn,
+    active: Arc<Mutex<HashSet<u32>>>,
+}
+
+impl RunnerConn {
+    fn new(conn: Conn, active: &Arc<Mutex<HashSet<u32>>>) -> Self {
+        active.lock().unwrap().insert(conn.id());
+        Self {
+            conn,
+            active: active.clone(),
+        }
+    }
+}
+
+impl Deref for RunnerConn {
+    type Target = Conn;
+
+    fn deref(&self) -> &Conn {
+        &self.conn
+    }
+}
+
+impl DerefMut for RunnerConn {
+    fn deref_mut(&mut self) -> &mut Conn {
+        &mut self.conn
+    }
+}
+
+impl Drop for RunnerConn {
+    fn drop(&mut self) {
+        self.active.lock().unwrap().remove(&self.conn.id());
+    }
+}
+
+/// Delay before the first connection retry; doubles on each further attempt
+const CONNECT_BACKOFF_INITIAL: Duration = Duration::from_millis(100);
+
+/// Server error for `KILL` of a connection id that no longer exists
+const ER_NO_SUCH_THREAD: u16 = 1094;
+
+/// Longest wait for the connection that sends `KILL QUERY`
+const KILL_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Client error codes for a connection lost between queries or during one
+const CR_SERVER_GONE_ERROR: u16 = 2006;
+const CR_SERVER_LOST: u16 = 2013;
+
+/// Whether `error` means the connection died, rather than the server
+/// rejecting the query
+///
+/// Covers broken pipes and resets, a connection the server closed
+/// (`wait_timeout`, restart), and the "server has gone away" and "lost
+/// connection" error codes.
+fn is_disconnect(error: &FusionLabError) -> bool {
+    match error {
+        FusionLabError::MySQL(mysql_async::Error::Io(_))
+        | FusionLabError::MySQL(mysql_async::Error::Driver(
+            mysql_async::DriverError::ConnectionClosed,
+        )) => true,
+        FusionLabError::MySQL(mysql_async::Error::Server(e)) => {
+            matches!(e.code, CR_SERVER_GONE_ERROR | CR_SERVER_LOST)
+        }
+        _ => false,
+    }
+}
+
+/// Outcome of a failed connection attempt
+enum ConnectError {
+    /// The server could not be reached; worth trying again
+    Retryable(String),
+    /// The server answered with an error or TLS failed; retrying won't help
+    Fatal(FusionLabError),
+}
+
+/// How `MySQLRunner::run_on_conn` sends a statement
+enum Execute {
+    /// As given, over `QueryOptions::protocol`
+    Query,
+    /// Prepared, bound to the parameters and closed again
+    Prepared(Params),
+    /// Bound to the runner's cached statement, prepared on a miss
+    Cached(Params),
+}
+
+/// Build a single `SET SESSION` statement for `vars`, or `None` if empty
+///
+/// The pool resets connections when they are returned, so the variables
+/// only apply to the query that requested them.
+fn session_vars_sql(vars: &[(String, String)]) -> Result<Option<String>> {
+    if vars.is_empty() {
+        return Ok(None);
+    }
+    let assignments = vars
+        .iter()
+        .map(|(name, value)| {
+            let valid_name = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !valid_name {
+                return Err(FusionLabError::Parse(format!(
+                    "Invalid session variable name: {:?}",
+                    name
+                )));
+            }
+            let numeric = value.parse::<f64>().is_ok()
+                && value
+                    .chars()
+                    .all(|c| c.is_ascii_digit() || matches!(c, '.' | '-' | '+' | 'e' | 'E'));
+            let value = if numeric || value.eq_ignore_ascii_case("DEFAULT") {
+                value.clone()
+            } else {
+                format!("'{}'", value.replace('\\', "\\\\").replace('\'', "''"))
+            };
+            Ok(format!("{} = {}", name, value))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Some(format!("SET SESSION {}", assignments.join(", "))))
+}
+
+/// Fetch the warnings left by the last statement on `conn`
+///
+/// Errors are ignored so that a failed `SHOW WARNINGS` never fails the query itself.
+async fn fetch_warnings(conn: &mut Conn) -> Vec<MySQLWarning> {
+    let rows: Vec<(String, u32, String)> = match conn.query("SHOW WARNINGS").await {
+        Ok(rows) => rows,
+        Err(_) => return vec![],
+    };
+
+    rows.into_iter()
+        .map(|(level, code, message)| MySQLWarning {
+            level,
+            code,
+            message,
+        })
+        .collect()
+}
+
+/// Whether statement history is recorded; false if performance_schema is
+/// disabled or its statement consumers are off
+///
+/// The consumers are a server-wide setting (on by default since MySQL 5.7),
+/// so they are only checked, never switched on.
+async fn statement_history_enabled(conn: &mut Conn) -> bool {
+    if !performance_schema_enabled(conn).await {
+        return false;
+    }
+    let active: Option<i64> = conn
+        .query_first(
+            "SELECT COUNT(*) FROM performance_schema.setup_consumers \
+             WHERE NAME IN ('events_statements_current', 'events_statements_history') \
+             AND ENABLED = 'YES'",
+        )
+        .await
+        .ok()
+        .flatten();
+    active == Some(2)
+}
+
+/// Whether stage events are timed and kept in the long history; false if
+/// performance_schema is disabled or the stage instruments or consumers
+/// are off
+///
+/// Stage instruments are off by default. They are a server-wide setting, so
+/// they are only checked, never switched on; see `QueryOptions::collect_stages`.
+async fn stage_history_enabled(conn: &mut Conn) -> bool {
+    if !performance_schema_enabled(conn).await {
+        return false;
+    }
+    let active: Option<(i64, i64)> = conn
+        .query_first(
+            "SELECT (SELECT COUNT(*) FROM performance_schema.setup_consumers \
+             WHERE NAME IN ('events_stages_current', 'events_stages_history_long') \
+             AND ENABLED = 'YES'), \
+             (SELECT COUNT(*) FROM performance_schema.setup_instruments \
+             WHERE NAME LIKE 'stage/%' AND ENABLED = 'YES' AND TIMED = 'YES')",
+        )
+        .await
+        .ok()
+        .flatten();
+    matches!(active, Some((2, instruments)) if instruments > 0)
+}
+
+/// Whether `@@performance_schema` is on
+async fn performance_schema_enabled(conn: &mut Conn) -> bool {
+    let enabled: Option<i64> = conn
+        .query_first("SELECT @@performance_schema")
+        .await
+        .ok()
+        .flatten();
+    enabled == Some(1)
+}
+
+/// Thread and event id of a statement on `conn`, marking where the
+/// statements after it start in the history
+async fn statement_marker(conn: &mut Conn) -> Option<(u64, u64)> {
+    conn.query_first(
+        "SELECT THREAD_ID, EVENT_ID FROM performance_schema.events_statements_current \
+         WHERE THREAD_ID = (SELECT THREAD_ID FROM performance_schema.threads \
+         WHERE PROCESSLIST_ID = CONNECTION_ID())",
+    )
+    .await
+    .ok()
+    .flatten()
+}
+
+/// History entry of the statement that ran `skip` top-level statements
+/// after `marker` on the same thread
+///
+/// Errors and missing entries (e.g. evicted from a small history) give
+/// `None`, since the query itself already succeeded.
+async fn fetch_server_stats(
+    conn: &mut Conn,
+    (thread_id, event_id): (u64, u64),
+    skip: usize,
+) -> Option<ServerStats> {
+    let row: Option<(Option<u64>, u64, u64)> = conn
+        .query_first(format!(
+            "SELECT TIMER_WAIT, ROWS_EXAMINED, ROWS_SENT \
+             FROM performance_schema.events_statements_history \
+             WHERE THREAD_ID = {} AND EVENT_ID > {} AND NESTING_EVENT_ID IS NULL \
+             ORDER BY EVENT_ID LIMIT {}, 1",
+            thread_id, event_id, skip
+        ))
+        .await
+        .ok()
+        .flatten();
+    let (timer_wait, rows_examined, rows_sent) = row?;
+    Some(ServerStats {
+        // TIMER_WAIT is in picoseconds; NULL when the statement was not timed
+        duration_ms: timer_wait? as f64 / 1e9,
+        rows_examined,
+        rows_sent,
+    })
+}
+
+/// Whether `conn` negotiated the compressed protocol, from the
+/// `Compression` session status
+async fn compression_enabled(conn: &mut Conn) -> bool {
+    let row: Option<(String, String)> = conn
+        .query_first("SHOW SESSION STATUS LIKE 'Compression'")
+        .await
+        .ok()
+        .flatten();
+    row.is_some_and(|(_, value)| value.eq_ignore_ascii_case("ON"))
+}
+
+/// Stages of the statement located like in `fetch_server_stats`
+///
+/// `None` if the history cannot be read; stages evicted from the long
+/// history are simply missing.
+async fn fetch_stages(
+    conn: &mut Conn,
+    (thread_id, event_id): (u64, u64),
+    skip: usize,
+) -> Option<Vec<StageTiming>> {
+    let rows: Vec<(String, Option<u64>)> = conn
+        .query(format!(
+            "SELECT EVENT_NAME, TIMER_WAIT \
+             FROM performance_schema.events_stages_history_long \
+             WHERE THREAD_ID = {0} AND NESTING_EVENT_ID = ( \
+             SELECT EVENT_ID FROM performance_schema.events_statements_history \
+             WHERE THREAD_ID = {0} AND EVENT_ID > {1} AND NESTING_EVENT_ID IS NULL \
+             ORDER BY EVENT_ID LIMIT {2}, 1) \
+             ORDER BY EVENT_ID",
+            thread_id, event_id, skip
+        ))
+        .await
+        .ok()?;
+    Some(
+        rows.into_iter()
+            .map(|(name, timer_wait)| StageTiming {
+                stage: name.rsplit('/').next().unwrap_or(&name).to_string(),
+                // TIMER_WAIT is in picoseconds
+                duration_ms: timer_wait.unwrap_or(0) as f64 / 1e9,
+            })
+            .collect(),
+    )
+}
+
+/// Numeric counters of `SHOW SESSION STATUS` on `conn`
+async fn session_status(conn: &mut Conn) -> Result<HashMap<String, i64>> {
+    let rows: Vec<(String, String)> = conn.query("SHOW SESSION STATUS").await?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, value.parse().ok()?)))
+        .collect())
+}
+
+/// Counters that changed between `second` and `after`, corrected for the
+/// cost of one `SHOW STATUS` (the change from `first` to `second`)
+fn status_diff(
+    first: &HashMap<String, i64>,
+    second: &HashMap<String, i64>,
+    after: &HashMap<String, i64>,
+) -> HashMap<String, i64> {
+    after
+        .iter()
+        .filter_map(|(name, &value)| {
+            let before = *second.get(name)?;
+            let overhead = before - first.get(name).copied().unwrap_or(before);
+            let delta = value - before - overhead;
+            (delta != 0).then(|| (name.clone(), delta))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CellValue, ColumnType, DataFusionRunner, PoolConfig};
+
+    /// Live MySQL tests only run when `FUSIONLAB_TEST_MYSQL` is set
+    /// (they use `MySQLConfig::default()`, i.e. the docker/ container).
+    fn mysql_available() -> bool {
+        std::env::var("FUSIONLAB_TEST_MYSQL").is_ok()
+    }
+
+    #[tokio::test]
+    async fn test_connect_retries_exhausted() {
+        // Nothing listens on port 1, so every attempt is refused
+        let config = MySQLConfig {
+            port: 1,
+            connect_timeout_ms: Some(1000),
+            connect_retries: 2,
+            ..MySQLConfig::default()
+        };
+        let runner = MySQLRunner::new(&config).unwrap();
+
+        match runner.run_query("SELECT 1").await {
+            Err(FusionLabError::Connection(msg)) => assert!(msg.contains("3 attempt"), "{}", msg),
+            other => panic!("expected a connection error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_is_disconnect() {
+        let server_error = |code| {
+            FusionLabError::MySQL(mysql_async::Error::Server(mysql_async::ServerError {
+                code,
+                message: String::new(),
+                state: "HY000".to_string(),
+            }))
+        };
+        assert!(is_disconnect(&server_error(CR_SERVER_GONE_ERROR)));
+        assert!(is_disconnect(&server_error(CR_SERVER_LOST)));
+        assert!(!is_disconnect(&server_error(1146)));
+
+        let broken_pipe = std::io::Error::from(std::io::ErrorKind::BrokenPipe);
+        assert!(is_disconnect(&FusionLabError::MySQL(
+            mysql_async::Error::Io(broken_pipe.into())
+        )));
+        assert!(is_disconnect(&FusionLabError::MySQL(
+            mysql_async::DriverError::ConnectionClosed.into()
+        )));
+        assert!(!is_disconnect(&FusionLabError::Timeout { elapsed_ms: 5 }));
+        assert!(!is_disconnect(&FusionLabError::Connection(
+            "refused".to_string()
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_retry_on_disconnect() {
+        if !mysql_available() {
+            return;
+        }
+        // One connection, so the next query gets the killed one from the pool
+        let config = |retry_on_disconnect| MySQLConfig {
+            pool: PoolConfig {
+                min: 1,
+                max: 1,
+                ..PoolConfig::default()
+            },
+            retry_on_disconnect,
+            ..MySQLConfig::default()
+        };
+        let killer = MySQLRunner::new(&MySQLConfig::default()).unwrap();
+        async fn kill_idle_connection(runner: &MySQLRunner, killer: &MySQLRunner) {
+            let result = runner.run_query("SELECT CONNECTION_ID()").await.unwrap();
+            let id = result.rows[0][0].format_value();
+            killer
+                .run_query(&format!("KILL CONNECTION {}", id))
+                .await
+                .unwrap();
+        }
+
+        let runner = MySQLRunner::new(&config(true)).unwrap();
+        kill_idle_connection(&runner, &killer).await;
+        let result = runner.run_query("SELECT 1").await.unwrap();
+        assert_eq!(result.row_count, 1);
+        runner.close().await;
+
+        let runner = MySQLRunner::new(&config(false)).unwrap();
+        kill_idle_connection(&runner, &killer).await;
+        let err = runner.run_query("SELECT 1").await.unwrap_err();
+        assert!(is_disconnect(&err), "{:?}", err);
+        runner.close().await;
+
+        killer.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_latin1_round_trip() {
+        if !mysql_available() {
+            return;
+        }
+        let runner = MySQLRunner::new(&MySQLConfig {
+            charset: Some("latin1".to_string()),
+            ..MySQLConfig::default()
+        })
+        .unwrap();
+
+        let results = runner
+            .run_script(
+                "CREATE TEMPORARY TABLE latin1_text (name VARCHAR(20)) CHARACTER SET latin1; \
+                 INSERT INTO latin1_text VALUES ('caf\u{e9}'); \
+                 SELECT name, CHARSET(name) FROM latin1_text",
+            )
+            .await
+            .unwrap();
+        let select = results.last().unwrap();
+        assert_eq!(select.rows[0][0], CellValue::Str("caf\u{e9}".to_string()));
+        assert_eq!(select.rows[0][1].format_value(), "latin1");
+        assert_eq!(select.lossy_decodes, 0);
+
+        runner.close().await;
+    }
+
+    #[test]
+    fn test_session_vars_sql() {
+        assert_eq!(session_vars_sql(&[]).unwrap(), None);
+
+        let vars = [
+            ("join_buffer_size", "262144"),
+            ("long_query_time", "0.5"),
+            ("optimizer_switch", "mrr=on,mrr_cost_based=off"),
+            ("sql_mode", "default"),
+            ("sql_select_limit", "inf"),
+        ]
+        .map(|(n, v)| (n.to_string(), v.to_string()));
+        assert_eq!(
+            session_vars_sql(&vars).unwrap().unwrap(),
+            "SET SESSION join_buffer_size = 262144, long_query_time = 0.5, \
+             optimizer_switch = 'mrr=on,mrr_cost_based=off', sql_mode = default, \
+             sql_select_limit = 'inf'"
+        );
+
+        // Quotes and backslashes cannot close the literal
+        let tricky = [("init".to_string(), r"x\'; DROP TABLE t; --".to_string())];
+        assert_eq!(
+            session_vars_sql(&tricky).unwrap().unwrap(),
+            r"SET SESSION init = 'x\\''; DROP TABLE t; --'"
+        );
+
+        for name in ["a = 1; DROP TABLE t; --", "", "1abc", "`x`", "@@global.x"] {
+            let vars = [(name.to_string(), "1".to_string())];
+            assert!(
+                matches!(session_vars_sql(&vars), Err(FusionLabError::Parse(_))),
+                "{:?} accepted",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_status_diff() {
+        let snapshot = |pairs: &[(&str, i64)]| -> HashMap<String, i64> {
+            pairs.iter().map(|(n, v)| (n.to_string(), *v)).collect()
+        };
+        let first = snapshot(&[("Questions", 10), ("Handler_read_next", 0), ("Uptime", 5)]);
+        let second = snapshot(&[("Questions", 11), ("Handler_read_next", 0), ("Uptime", 5)]);
+        let after = snapshot(&[
+            ("Questions", 13),
+            ("Handler_read_next", 42),
+            ("Uptime", 5),
+            ("Innodb_rows_read", 7),
+        ]);
+
+        // One Questions increment per SHOW STATUS is subtracted
+        assert_eq!(
+            status_diff(&first, &second, &after),
+            snapshot(&[("Questions", 1), ("Handler_read_next", 42)])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_query_params() {
+        if !mysql_available() {
+            return;
+        }
+        let runner = MySQLRunner::new(&MySQLConfig::default()).unwrap();
+
+        let params = (
+            mysql_async::Value::NULL,
+            "it's \"quoted\"",
+            mysql_async::Value::Bytes(vec![0x00, 0xff]),
+        );
+        let result = runner
+            .run_query_params("SELECT ? AS n, ? AS s, HEX(?) AS b", params)
+            .await
+            .unwrap();
+        assert_eq!(result.row_count, 1);
+        assert_eq!(
+            result.to_display_rows()[0],
+            vec!["NULL", "it's \"quoted\"", "00FF"]
+        );
+
+        // Same SQL again hits the statement cache
+        let result = runner
+            .run_query_params("SELECT ? AS n, ? AS s, HEX(?) AS b", (1, "x", "y"))
+            .await
+            .unwrap();
+        assert_eq!(result.to_display_rows()[0], vec!["1", "x", "79"]);
+        let stats = runner.cache_stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 1, 1));
+
+        runner.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_binary_columns_display_as_hex() {
+        if !mysql_available() {
+            return;
+        }
+        let runner = MySQLRunner::new(&MySQLConfig::default()).unwrap();
+        runner
+            .run_query("DROP TABLE IF EXISTS fusionlab_binary_test")
+            .await
+            .unwrap();
+        runner
+            .run_query("CREATE TABLE fusionlab_binary_test (notes TEXT, payload BLOB)")
+            .await
+            .unwrap();
+        runner
+            .run_query("INSERT INTO fusionlab_binary_test VALUES ('héllo', 0x00FF41)")
+            .await
+            .unwrap();
+
+        let result = runner
+            .run_query("SELECT notes, payload FROM fusionlab_binary_test")
+            .await
+            .unwrap();
+        assert_eq!(
+            result.column_types,
+            vec![ColumnType::String, ColumnType::Blob]
+        );
+        assert_eq!(result.rows[0][0], CellValue::Str("héllo".to_string()));
+        assert_eq!(result.rows[0][1], CellValue::Bytes(vec![0x00, 0xff, 0x41]));
+        assert_eq!(result.to_display_rows()[0], vec!["héllo", "0x00ff41"]);
+
+        runner
+            .run_query("DROP TABLE fusionlab_binary_test")
+            .await
+            .unwrap();
+        runner.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_null_differs_from_null_string() {
+        if !mysql_available() {
+            return;
+        }
+        let runner = MySQLRunner::new(&MySQLConfig::default()).unwrap();
+        runner
+            .run_query("DROP TABLE IF EXISTS fusionlab_null_test")
+            .await
+            .unwrap();
+        runner
+            .run_query("CREATE TABLE fusionlab_null_test (id INT, s VARCHAR(8))")
+            .await
+            .unwrap();
+        runner
+            .run_query("INSERT INTO fusionlab_null_test VALUES (1, NULL), (2, 'NULL')")
+            .await
+            .unwrap();
+
+        let result = runner
+            .run_query("SELECT s FROM fusionlab_null_test ORDER BY id")
+            .await
+            .unwrap();
+        assert_eq!(result.rows[0][0], CellValue::Null);
+        assert_eq!(result.rows[1][0], CellValue::Str("NULL".to_string()));
+        assert_eq!(
+            result.to_display_rows_with_null(DEFAULT_NULL_MARKER),
+            vec![vec!["null"], vec!["NULL"]]
+        );
+
+        runner
+            .run_query("DROP TABLE fusionlab_null_test")
+            .await
+            .unwrap();
+        runner.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_server_info_gates_explain_analyze() {
+        if !mysql_available() {
+            return;
+        }
+        let runner = MySQLRunner::new(&MySQLConfig::default()).unwrap();
+        let info = runner.server_info().await.unwrap();
+        println!("Server: {}", info);
+        assert_eq!(runner.server_info().await.unwrap(), info);
+        assert_eq!(info.number, runner.server_version().await.unwrap());
+
+        let analyze = runner.run_explain_analyze("SELECT 1").await;
+        if info.supports_mysql_feature((8, 0, 18)) {
+            assert!(analyze.is_ok());
+        } else {
+            assert!(matches!(analyze, Err(FusionLabError::Unsupported { .. })));
+        }
+        runner.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_run_explain_rows() {
+        if !mysql_available() {
+            return;
+        }
+        let runner = MySQLRunner::new(&MySQLConfig::default()).unwrap();
+        runner
+            .run_query("DROP TABLE IF EXISTS fusionlab_explain_test")
+            .await
+            .unwrap();
+        runner
+            .run_query(
+                "CREATE TABLE fusionlab_explain_test (id INT PRIMARY KEY, k INT, KEY idx_k (k))",
+            )
+            .await
+            .unwrap();
+
+        let result = runner
+            .run_explain_rows("SELECT id FROM fusionlab_explain_test WHERE k = 1")
+            .await
+            .unwrap();
+        for name in ["table", "type", "key", "rows", "Extra"] {
+            assert!(
+                result.columns.iter().any(|c| c == name),
+                "{:?}",
+                result.columns
+            );
+        }
+        let column = |name: &str| result.columns.iter().position(|c| c == name).unwrap();
+        assert_eq!(result.row_count, 1);
+        assert_eq!(result.rows[0][column("key")].format_value(), "idx_k");
+        assert_eq!(result.rows[0][column("type")].format_value(), "ref");
+
+        runner
+            .run_query("DROP TABLE fusionlab_explain_test")
+            .await
+            .unwrap();
+        runner.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_empty_result_keeps_columns() {
+        if !mysql_available() {
+            return;
+        }
+        let runner = MySQLRunner::new(&MySQLConfig::default()).unwrap();
+
+        let result = runner
+            .run_query("SELECT CAST(1 AS DECIMAL(10,3)) AS a WHERE 1=0")
+            .await
+            .unwrap();
+        assert_eq!(result.row_count, 0);
+        assert_eq!(result.columns, vec!["a"]);
+        assert_eq!(result.column_types, vec![ColumnType::Decimal]);
+        assert_eq!(result.column_meta[0].decimal_precision(), Some((10, 3)));
+
+        // The binary protocol takes the same path
+        let result = runner
+            .run_query_params("SELECT ? AS a WHERE 1=0", (1,))
+            .await
+            .unwrap();
+        assert_eq!(result.row_count, 0);
+        assert_eq!(result.columns, vec!["a"]);
+        runner.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_stmt_cache_eviction_and_errors() {
+        if !mysql_available() {
+            return;
+        }
+        let config = MySQLConfig {
+            stmt_cache_size: 2,
+            ..MySQLConfig::default()
+        };
+        let runner = MySQLRunner::new(&config).unwrap();
+        for sql in ["SELECT ?", "SELECT ? + 1", "SELECT ? + 2", "SELECT ?"] {
+            runner.run_query_params(sql, (1,)).await.unwrap();
+        }
+        let stats = runner.cache_stats();
+        assert_eq!((stats.hits, stats.misses), (0, 4));
+        assert_eq!((stats.evictions, stats.entries), (2, 2));
+
+        // A server-side error keeps the statement connection and its statements
+        assert!(runner
+            .run_query_params("SELECT * FROM no_such_table WHERE id = ?", (1,))
+            .await
+            .is_err());
+        runner.run_query_params("SELECT ?", (1,)).await.unwrap();
+        assert_eq!(runner.cache_stats().hits, 1);
+        runner.close().await;
+
+        let uncached = MySQLRunner::new(&MySQLConfig {
+            stmt_cache_size: 0,
+            ..MySQLConfig::default()
+        })
+        .unwrap();
+        for _ in 0..2 {
+            uncached.run_query_params("SELECT ?", (1,)).await.unwrap();
+        }
+        let stats = uncached.cache_stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (0, 2, 0));
+        uncached.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_stmt_conn_outside_pool() {
+        if !mysql_available() {
+            return;
+        }
+        let config = MySQLConfig {
+            pool: PoolConfig {
+                min: 0,
+                max: 1,
+                acquire_timeout_ms: 5000,
+                ..PoolConfig::default()
+            },
+            ..MySQLConfig::default()
+        };
+        let runner = MySQLRunner::new(&config).unwrap();
+        runner.run_query_params("SELECT ?", (1,)).await.unwrap();
+        // The statement connection holds no pool slot between calls
+        assert_eq!(runner.pool_status().active, 0);
+        runner.run_query("SELECT 1").await.unwrap();
+        runner.run_query_params("SELECT ?", (2,)).await.unwrap();
+        assert_eq!(runner.cache_stats().hits, 1);
+        runner.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_run_query_params_value_vec() {
+        if !mysql_available() {
+            return;
+        }
+        let runner = MySQLRunner::new(&MySQLConfig::default()).unwrap();
+
+        runner
+            .run_query("DROP TABLE IF EXISTS fusionlab_params_test")
+            .await
+            .unwrap();
+        runner
+            .run_query("CREATE TABLE fusionlab_params_test (id INT, name VARCHAR(16))")
+            .await
+            .unwrap();
+        runner
+            .run_query("INSERT INTO fusionlab_params_test VALUES (1, 'a'), (2, 'b'), (3, 'b')")
+            .await
+            .unwrap();
+
+        let params = vec![mysql_async::Value::Int(1), mysql_async::Value::from("b")];
+        let result = runner
+            .run_query_params(
+                "SELECT id FROM fusionlab_params_test WHERE id > ? AND name = ?",
+                params,
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.row_count, 2);
+        assert_eq!(result.columns, vec!["id"]);
+
+        runner
+            .run_query("DROP TABLE fusionlab_params_test")
+            .await
+            .unwrap();
+        runner.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_collect_warnings_opt_in() {
+        if !mysql_available() {
+            return;
+        }
+        let runner = MySQLRunner::new(&MySQLConfig::default()).unwrap();
+        // Division by zero yields NULL plus warning 1365
+        let sql = "SELECT 1 / 0 AS x";
+
+        let result = runner.run_query(sql).await.unwrap();
+        assert!(result.warnings.is_empty());
+
+        let options = QueryOptions {
+            collect_warnings: true,
+            ..QueryOptions::default()
+        };
+        let result = runner.run_query_with_options(sql, &options).await.unwrap();
+        assert_eq!(result.warnings.len(), 1);
+        assert_eq!(result.warnings[0].code, 1365);
+        assert_eq!(result.warnings[0].level, "Warning");
+
+        runner.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_run_query_params_with_options() {
+        if !mysql_available() {
+            return;
+        }
+        let runner = MySQLRunner::new(&MySQLConfig::default()).unwrap();
+
+        let options = QueryOptions {
+            collect_warnings: true,
+            tag: Some("params".to_string()),
+            ..QueryOptions::default()
+        };
+        let result = runner
+            .run_query_params_with_options("SELECT ? / 0 AS x", (1,), &options)
+            .await
+            .unwrap();
+        assert_eq!(result.warnings.len(), 1);
+        assert_eq!(result.warnings[0].code, 1365);
+        assert_eq!(result.tag.as_deref(), Some("params"));
+        assert_eq!(result.protocol, Protocol::Binary);
+
+        // Session variables stay off the statement connection
+        let options = QueryOptions {
+            session_vars: vec![("sql_select_limit".to_string(), "1".to_string())],
+            ..QueryOptions::default()
+        };
+        let sql = "SELECT ? FROM information_schema.CHARACTER_SETS";
+        let result = runner
+            .run_query_params_with_options(sql, (1,), &options)
+            .await
+            .unwrap();
+        assert_eq!(result.row_count, 1);
+        let result = runner.run_query_params(sql, (1,)).await.unwrap();
+        assert!(result.row_count > 1);
+
+        let options = QueryOptions {
+            timeout: Some(Duration::from_millis(200)),
+            ..QueryOptions::default()
+        };
+        let result = runner
+            .run_query_params_with_options("SELECT SLEEP(?)", (30,), &options)
+            .await;
+        assert!(matches!(result, Err(FusionLabError::Timeout { .. })));
+
+        runner.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_run_query_with_timeout() {
+        if !mysql_available() {
+            return;
+        }
+        let runner = MySQLRunner::new(&MySQLConfig::default()).unwrap();
+
+        let started = Instant::now();
+        let result = runner
+            .run_query_with_timeout("SELECT SLEEP(30)", Duration::from_millis(200))
+            .await;
+        assert!(matches!(result, Err(FusionLabError::Timeout { .. })));
+        assert!(started.elapsed() < Duration::from_secs(10));
+
+        // The killed connection goes back to the pool in a usable state
+        let result = runner.run_query("SELECT 1").await.unwrap();
+        assert_eq!(result.row_count, 1);
+
+        runner.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_kill_running() {
+        if !mysql_available() {
+            return;
+        }
+        // The sleeping query holds the only pool slot; the kill must not
+        // wait for it
+        let config = MySQLConfig {
+            pool: PoolConfig {
+                min: 0,
+                max: 1,
+                acquire_timeout_ms: 5000,
+                ..PoolConfig::default()
+            },
+            ..MySQLConfig::default()
+        };
+        let runner = MySQLRunner::new(&config).unwrap();
+        assert_eq!(runner.kill_running().await.unwrap(), 0);
+
+        let started = Instant::now();
+        let (result, killed) = tokio::join!(runner.run_query("SELECT SLEEP(30)"), async {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            runner.kill_running().await.unwrap()
+        });
+        assert_eq!(killed, 1);
+        // An interrupted SLEEP returns 1 instead of failing
+        assert_eq!(result.unwrap().rows[0][0], CellValue::Int(1));
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert_eq!(runner.pool_status().active, 0);
+
+        runner.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_ssl_mode_required() {
+        if !mysql_available() {
+            return;
+        }
+        let config = MySQLConfig {
+            ssl: Some(SslConfig {
+                mode: SslMode::Required,
+                ..SslConfig::default()
+            }),
+            ..MySQLConfig::default()
+        };
+        let runner = MySQLRunner::new(&config).unwrap();
+        let result = runner
+            .run_query("SHOW SESSION STATUS LIKE 'Ssl_cipher'")
+            .await
+            .unwrap();
+        assert_ne!(result.rows[0][1].format_value(), "");
+        let secure_only = runner
+            .run_query("SELECT @@require_secure_transport")
+            .await
+            .unwrap();
+        runner.close().await;
+
+        // Plaintext is rejected when the server insists on TLS
+        if secure_only.rows[0][0].format_value() == "1" {
+            let plain = MySQLRunner::new(&MySQLConfig::default()).unwrap();
+            assert!(plain.run_query("SELECT 1").await.is_err());
+            plain.close().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_query_streamed_retains_prefix() {
+        if !mysql_available() {
+            return;
+        }
+        let runner = MySQLRunner::new(&MySQLConfig::default()).unwrap();
+
+        let mut seen = 0;
+        let result = runner
+            .run_query_streamed(
+                "SELECT 1 AS n UNION ALL SELECT 2 UNION ALL SELECT 3",
+                Some(2),
+                &QueryOptions::default(),
+                |_| seen += 1,
+            )
+            .await
+            .unwrap();
+        assert_eq!(seen, 3);
+        assert_eq!(result.row_count, 3);
+        assert_eq!(result.rows.len(), 2);
+        assert!(result.truncated);
+        assert!(result.exec_ms >= 0.0 && result.fetch_ms >= 0.0);
+        assert_eq!(result.duration_ms, result.exec_ms + result.fetch_ms);
+
+        // The same cap through QueryOptions
+        let sql = "WITH RECURSIVE seq (n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM seq \
+                   WHERE n < 500) SELECT n FROM seq";
+        let options = QueryOptions {
+            max_display_rows: Some(50),
+            ..QueryOptions::default()
+        };
+        let result = runner.run_query_with_options(sql, &options).await.unwrap();
+        assert_eq!(result.row_count, 500);
+        assert_eq!(result.rows.len(), 50);
+        assert!(result.truncated);
+        assert_eq!(result.rows[49], vec![CellValue::Int(50)]);
+
+        let result = runner.run_query(sql).await.unwrap();
+        assert_eq!(result.rows.len(), 500);
+        assert!(!result.truncated);
+
+        runner.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_run_exec() {
+        if !mysql_available() {
+            return;
+        }
+        let runner = MySQLRunner::new(&MySQLConfig::default()).unwrap();
+
+        runner
+            .run_exec("DROP TABLE IF EXISTS fusionlab_exec_test")
+            .await
+            .unwrap();
+        runner
+            .run_exec(
+                "CREATE TABLE fusionlab_exec_test \
+                 (id INT AUTO_INCREMENT PRIMARY KEY, v VARCHAR(10))",
+            )
+            .await
+            .unwrap();
+
+        let insert = runner
+            .run_exec("INSERT INTO fusionlab_exec_test (v) VALUES ('a'), ('b'), ('c')")
+            .await
+            .unwrap();
+        assert_eq!(insert.affected_rows, 3);
+        // The id of the first row of a multi-row insert
+        assert_eq!(insert.last_insert_id, Some(1));
+        assert!(insert.duration_ms >= 0.0);
+
+        let update = runner
+            .run_exec("UPDATE fusionlab_exec_test SET v = 'z' WHERE id > 1")
+            .await
+            .unwrap();
+        assert_eq!(update.affected_rows, 2);
+        assert_eq!(update.last_insert_id, None);
+
+        let delete = runner
+            .run_exec("DELETE FROM fusionlab_exec_test WHERE v = 'nope'")
+            .await
+            .unwrap();
+        assert_eq!(delete.affected_rows, 0);
+
+        runner
+            .run_exec("DROP TABLE fusionlab_exec_test")
+            .await
+            .unwrap();
+        runner.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_run_script() {
+        if !mysql_available() {
+            return;
+        }
+        let runner = MySQLRunner::new(&MySQLConfig::default()).unwrap();
+
+        let results = runner
+            .run_script(
+                "CREATE TEMPORARY TABLE script_t (s VARCHAR(10));\n\
+                 -- the temporary table is only visible on this connection\n\
+                 INSERT INTO script_t VALUES ('a;b'), ('c');\n\
+                 SELECT s FROM script_t ORDER BY s; /* done */",
+            )
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[1].affected_rows, 2);
+        assert_eq!(results[2].row_count, 2);
+        assert_eq!(results[2].rows[0][0], CellValue::Str("a;b".to_string()));
+
+        runner.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_run_script_delimiter() {
+        if !mysql_available() {
+            return;
+        }
+        let runner = MySQLRunner::new(&MySQLConfig::default()).unwrap();
+
+        let results = runner
+            .run_script(
+                "DROP PROCEDURE IF EXISTS fusionlab_script_p;\n\
+                 DELIMITER $$\n\
+                 CREATE PROCEDURE fusionlab_script_p(IN n INT)\n\
+                 BEGIN\n\
+                   DECLARE doubled INT;\n\
+                   SET doubled = n * 2;\n\
+                   SELECT doubled, 'x;y' AS s;\n\
+                 END$$\n\
+                 DELIMITER ;\n\
+                 CALL fusionlab_script_p(21);\n\
+                 DROP PROCEDURE fusionlab_script_p;",
+            )
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[2].columns, vec!["doubled", "s"]);
+        assert_eq!(results[2].rows[0][1], CellValue::Str("x;y".to_string()));
+        assert_eq!(results[2].rows[0][0], CellValue::Int(42));
+
+        runner.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_unsigned_bigint() {
+        if !mysql_available() {
+            return;
+        }
+        let runner = MySQLRunner::new(&MySQLConfig::default()).unwrap();
+
+        let results = runner
+            .run_script(
+                "CREATE TEMPORARY TABLE unsigned_t (id INT, big BIGINT UNSIGNED);\n\
+                 INSERT INTO unsigned_t VALUES (1, 18446744073709551615), (2, 5), (3, NULL);\n\
+                 SELECT id, big FROM unsigned_t ORDER BY id",
+            )
+            .await
+            .unwrap();
+        let result = &results[2];
+        assert_eq!(result.column_types, vec![ColumnType::Int, ColumnType::UInt]);
+        assert_eq!(result.rows[0][1], CellValue::UInt(u64::MAX));
+        assert_eq!(result.rows[1][1], CellValue::UInt(5));
+        assert_eq!(result.rows[2][1], CellValue::Null);
+        assert_eq!(result.rows[0][1].format_value(), "18446744073709551615");
+
+        let batch = result.to_record_batch().unwrap();
+        assert_eq!(
+            batch.schema().field(1).data_type(),
+            &::datafusion::arrow::datatypes::DataType::UInt64
+        );
+
+        runner.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_run_query_repeat() {
+        if !mysql_available() {
+            return;
+        }
+        let runner = MySQLRunner::new(&MySQLConfig::default()).unwrap();
+
+        let stats = runner.run_query_repeat("SELECT 1", 5, 2).await.unwrap();
+        assert_eq!(stats.iterations(), 5);
+        assert_eq!(stats.warmup, 2);
+        assert_eq!(stats.row_count, 1);
+        assert!(stats.min_ms <= stats.median_ms && stats.median_ms <= stats.max_ms);
+
+        runner.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_session_vars_applied() {
+        if !mysql_available() {
+            return;
+        }
+        let runner = MySQLRunner::new(&MySQLConfig::default()).unwrap();
+
+        let options = QueryOptions {
+            session_vars: vec![
+                ("join_buffer_size".to_string(), "1048576".to_string()),
+                ("optimizer_switch".to_string(), "mrr=off".to_string()),
+            ],
+            ..QueryOptions::default()
+        };
+        let result = runner
+            .run_query_with_options(
+                "SELECT @@session.join_buffer_size, @@session.optimizer_switch LIKE '%mrr=off%'",
+                &options,
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.rows[0][0].format_value(), "1048576");
+        assert_eq!(result.rows[0][1].format_value(), "1");
+
+        runner.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_collect_status_diff() {
+        if !mysql_available() {
+            return;
+        }
+        let runner = MySQLRunner::new(&MySQLConfig::default()).unwrap();
+
+        let options = QueryOptions {
+            collect_status_diff: true,
+            ..QueryOptions::default()
+        };
+        let result = runner
+            .run_query_with_options(
+                "SELECT COUNT(*) FROM information_schema.COLLATIONS",
+                &options,
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.status_diff.get("Questions"), Some(&1));
+        assert!(result.status_diff.values().all(|delta| *delta != 0));
+
+        let plain = runner.run_query("SELECT 1").await.unwrap();
+        assert!(plain.status_diff.is_empty());
+
+        runner.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_collect_server_stats() {
+        if !mysql_available() {
+            return;
+        }
+        let runner = MySQLRunner::new(&MySQLConfig::default()).unwrap();
+        let pfs = runner
+            .run_query("SELECT @@performance_schema")
+            .await
+            .unwrap();
+        let pfs_enabled = pfs.rows[0][0].format_value() == "1";
+
+        let sql = "SELECT CHARACTER_SET_NAME FROM information_schema.CHARACTER_SETS LIMIT 3";
+        let options = QueryOptions {
+            collect_server_stats: true,
+            ..QueryOptions::default()
+        };
+        let result = runner.run_query_with_options(sql, &options).await.unwrap();
+        let Some(stats) = result.server_stats else {
+            assert!(!pfs_enabled, "performance_schema is on but no stats");
+            runner.close().await;
+            return;
+        };
+        assert_eq!(stats.rows_sent, 3);
+        assert!(stats.duration_ms > 0.0);
+        assert!(stats.duration_ms <= result.duration_ms);
+
+        // Status snapshots between the marker and the query are skipped,
+        // and the stats queries do not leak into the status diff
+        let options = QueryOptions {
+            collect_server_stats: true,
+            collect_status_diff: true,
+            ..QueryOptions::default()
+        };
+        let result = runner.run_query_with_options(sql, &options).await.unwrap();
+        assert_eq!(result.server_stats.unwrap().rows_sent, 3);
+        assert_eq!(result.status_diff.get("Questions"), Some(&1));
+
+        assert!(runner.run_query(sql).await.unwrap().server_stats.is_none());
+        runner.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_collect_stages() {
+        if !mysql_available() {
+            return;
+        }
+        let runner = MySQLRunner::new(&MySQLConfig::default()).unwrap();
+
+        let sql = "SELECT CHARACTER_SET_NAME FROM information_schema.CHARACTER_SETS \
+                   ORDER BY MAXLEN, CHARACTER_SET_NAME LIMIT 3";
+        let options = QueryOptions {
+            collect_stages: true,
+            collect_status_diff: true,
+            ..QueryOptions::default()
+        };
+        let result = runner.run_query_with_options(sql, &options).await.unwrap();
+        assert_eq!(result.row_count, 3);
+        if result.stages_unavailable {
+            assert!(result.stages.is_empty());
+            runner.close().await;
+            return;
+        }
+        assert!(!result.stages.is_empty());
+        assert!(result.stages.iter().all(|s| !s.stage.contains('/')));
+        assert_eq!(result.status_diff.get("Questions"), Some(&1));
+
+        let result = runner.run_query(sql).await.unwrap();
+        assert!(result.stages.is_empty() && !result.stages_unavailable);
+        runner.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_query_tag() {
+        if !mysql_available() {
+            return;
+        }
+        let runner = MySQLRunner::new(&MySQLConfig::default()).unwrap();
+
+        // The running statement sees its own text, comment included
+        let sql = "SELECT INFO FROM information_schema.PROCESSLIST WHERE ID = CONNECTION_ID()";
+        let options = QueryOptions {
+            tag: Some("replay.sql:7 */ SELECT 2".to_string()),
+            ..QueryOptions::default()
+        };
+        let result = runner.run_query_with_options(sql, &options).await.unwrap();
+        assert_eq!(result.tag.as_deref(), Some("replay.sql:7 *\\/ SELECT 2"));
+        assert_eq!(result.row_count, 1);
+        assert_eq!(
+            result.rows[0][0].format_value(),
+            format!("/* fusionlab:tag=replay.sql:7 *\\/ SELECT 2 */ {}", sql)
+        );
+        assert!(result.to_json_document().starts_with("{\"tag\":"));
+        assert_eq!(runner.run_query(sql).await.unwrap().tag, None);
+
+        let results = runner
+            .run_script_tagged("SELECT 1;\n\nSELECT 2;", "setup.sql")
+            .await
+            .unwrap();
+        assert_eq!(results[0].tag.as_deref(), Some("setup.sql:1"));
+        assert_eq!(results[1].tag.as_deref(), Some("setup.sql:3"));
+
+        runner.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_compressed_protocol() {
+        if !mysql_available() {
+            return;
+        }
+        // Repetitive rows well above the 50-byte compression threshold
+        let sql = "WITH RECURSIVE seq (n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM seq \
+                   WHERE n < 1000) SELECT n, REPEAT(CHAR(65 + n % 26), 200) AS s FROM seq";
+
+        let plain = MySQLRunner::new(&MySQLConfig::default()).unwrap();
+        let compressed = MySQLRunner::new(&MySQLConfig {
+            compress: true,
+            ..MySQLConfig::default()
+        })
+        .unwrap();
+        let expected = plain.run_query(sql).await.unwrap();
+        let result = compressed.run_query(sql).await.unwrap();
+
+        assert!(!expected.compressed);
+        assert!(result.compressed);
+        assert!(compressed.compressed());
+        assert_eq!(result.row_count, 1000);
+        assert_eq!(result.columns, expected.columns);
+        assert_eq!(result.rows, expected.rows);
+        assert!(result
+            .to_json_document()
+            .starts_with("{\"compressed\":true,"));
+
+        plain.close().await;
+        compressed.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_binary_protocol_matches_text() {
+        if !mysql_available() {
+            return;
+        }
+        let runner = MySQLRunner::new(&MySQLConfig::default()).unwrap();
+        let sql = "SELECT 1 AS i, CAST(18446744073709551615 AS UNSIGNED) AS u, \
+                   CAST(12.50 AS DECIMAL(10,2)) AS d, 1.5e0 AS f, 'abc' AS s, \
+                   X'00FF' AS b, NULL AS n, DATE '2024-02-29' AS dt, \
+                   TIMESTAMP '2024-02-29 12:34:56.125' AS ts, TIME '-12:00:01' AS t";
+
+        let text = runner.run_query(sql).await.unwrap();
+        let binary = runner
+            .run_query_with_options(
+                sql,
+                &QueryOptions {
+                    protocol: Protocol::Binary,
+                    ..QueryOptions::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(text.protocol, Protocol::Text);
+        assert_eq!(binary.protocol, Protocol::Binary);
+        assert_eq!(binary.columns, text.columns);
+        assert_eq!(binary.column_types, text.column_types);
+        assert_eq!(binary.rows, text.rows);
+        runner.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_register_mysql_query_union() {
+        if !mysql_available() {
+            return;
+        }
+        let mysql = MySQLRunner::new(&MySQLConfig::default()).unwrap();
+        let df = DataFusionRunner::new();
+        df.register_ssb_sample().unwrap();
+
+        let live = df
+            .register_mysql_query(
+                "live_customer",
+                &mysql,
+                "SELECT 9001 AS c_custkey, 'Customer#000009001' AS c_name",
+            )
+            .await
+            .unwrap();
+        assert_eq!(live.row_count, 1);
+
+        let archived = df
+            .run_query_collect("SELECT c_custkey FROM customer")
+            .await
+            .unwrap();
+        let union = df
+            .run_query_collect(
+                "SELECT c_custkey, c_name FROM customer \
+                 UNION ALL SELECT c_custkey, c_name FROM live_customer \
+                 ORDER BY c_custkey DESC",
+            )
+            .await
+            .unwrap();
+        assert_eq!(union.row_count, archived.row_count + 1);
+        assert_eq!(
+            union.rows().next().unwrap(),
+            vec!["9001".to_string(), "Customer#000009001".to_string()]
+        );
+
+        mysql.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_register_mysql_query_again_replaces() {
+        if !mysql_available() {
+            return;
+        }
+        let mysql = MySQLRunner::new(&MySQLConfig::default()).unwrap();
+        let df = DataFusionRunner::new();
+
+        df.register_mysql_query("live", &mysql, "SELECT 1 AS n")
+            .await
+            .unwrap();
+        df.register_mysql_query("live", &mysql, "SELECT 2 AS n UNION ALL SELECT 3")
+            .await
+            .unwrap();
+        let result = df
+            .run_query_collect("SELECT n FROM live ORDER BY n")
+            .await
+            .unwrap();
+        assert_eq!(
+            result.rows().collect::<Vec<_>>(),
+            vec![vec!["2".to_string()], vec!["3".to_string()]]
+        );
+
+        mysql.close().await;
+    }
+}
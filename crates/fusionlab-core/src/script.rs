@@ -0,0 +1,309 @@
+//! Splitting of multi-statement SQL scripts
+//!
+//! The MySQL text protocol runs one statement per `COM_QUERY` unless
+//! multi-statements are enabled, so scripts are split client-side on `;`.
+//! Semicolons inside string literals, quoted identifiers and comments do not
+//! end a statement. Like the `mysql` client, `DELIMITER` lines switch the
+//! terminator, so procedure and trigger bodies can contain semicolons.
+
+use crate::lexer::{self, PieceKind};
+
+/// Client directive that changes the statement terminator
+const DELIMITER: &[u8] = b"delimiter";
+
+/// Split a script into statements on top-level semicolons
+///
+/// Statements are trimmed and keep their comments (optimizer hints and
+/// versioned `/*! */` comments are meaningful to the server). Pieces that
+/// contain only whitespace and comments are dropped, so a trailing comment
+/// does not become an empty query.
+///
+/// A `DELIMITER <terminator>` line at the start of a statement makes
+/// `<terminator>` end statements instead of `;`, until the next `DELIMITER`
+/// line. The directives themselves are not returned, and each call starts
+/// out with `;`.
+pub fn split_statements(sql: &str) -> Vec<&str> {
+    let scan = scan(sql, ";");
+    let mut statements = scan.statements;
+    if scan.has_code {
+        statements.push(sql[scan.rest..].trim());
+    }
+    statements
+}
+
+/// Outcome of `split_terminated`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Terminated<'a> {
+    /// Statements ended by a terminator, as `split_statements` returns them
+    pub statements: Vec<&'a str>,
+    /// Text after the last terminator that is still part of a statement:
+    /// code, or a literal or comment left open. `None` when only whitespace
+    /// and finished comments follow.
+    pub rest: Option<&'a str>,
+    /// Terminator in effect at the end of `sql`
+    pub delimiter: String,
+}
+
+/// Like `split_statements`, but keep the text after the last terminator
+/// apart, for input that is still being typed
+///
+/// Scanning starts with `delimiter` (not empty) as the terminator, so a
+/// `DELIMITER` set by earlier input can carry over.
+pub fn split_terminated<'a>(sql: &'a str, delimiter: &str) -> Terminated<'a> {
+    let scan = scan(sql, delimiter);
+    let open = scan.has_code || scan.open;
+    Terminated {
+        statements: scan.statements,
+        rest: open.then(|| &sql[scan.rest..]),
+        delimiter: scan.delimiter,
+    }
+}
+
+/// State at the end of a script
+struct Scan<'a> {
+    /// Statements ended by a terminator
+    statements: Vec<&'a str>,
+    /// Offset of the text after the last terminator
+    rest: usize,
+    /// That text contains code
+    has_code: bool,
+    /// A literal, identifier or block comment is left open at the end
+    open: bool,
+    delimiter: String,
+}
+
+fn scan<'a>(sql: &'a str, delimiter: &str) -> Scan<'a> {
+    let bytes = sql.as_bytes();
+    let mut statements = Vec::new();
+    let mut delimiter = delimiter.to_string();
+    let mut start = 0;
+    let mut has_code = false;
+    let mut open = false;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if !has_code && is_delimiter_directive(&bytes[i..]) {
+            // The new terminator is the first word after the keyword;
+            // the directive runs to the end of its line
+            let end = sql[i..].find('\n').map_or(sql.len(), |n| i + n);
+            if let Some(word) = sql[i + DELIMITER.len()..end].split_whitespace().next() {
+                delimiter = word.to_string();
+            }
+            start = end;
+            i = end;
+            continue;
+        }
+        if bytes[i..].starts_with(delimiter.as_bytes()) {
+            if has_code {
+                statements.push(sql[start..i].trim());
+            }
+            i += delimiter.len();
+            start = i;
+            has_code = false;
+            continue;
+        }
+
+        let piece = lexer::piece(sql, i);
+        match piece.kind {
+            PieceKind::Whitespace | PieceKind::LineComment => {}
+            // `/*! */` and `/*+ */` are executed by the server
+            PieceKind::BlockComment { executable } => has_code |= executable,
+            PieceKind::Quoted | PieceKind::Other => has_code = true,
+        }
+        open = !piece.closed;
+        i = piece.end;
+    }
+
+    Scan {
+        statements,
+        rest: start,
+        has_code,
+        open,
+        delimiter,
+    }
+}
+
+/// Whether `rest` starts with a `DELIMITER` directive
+fn is_delimiter_directive(rest: &[u8]) -> bool {
+    rest.len() > DELIMITER.len()
+        && rest[..DELIMITER.len()].eq_ignore_ascii_case(DELIMITER)
+        && matches!(rest[DELIMITER.len()], b' ' | b'\t')
+}
+
+/// 1-based line of `sql` on which `statement`, one of the slices returned by
+/// `split_statements(sql)`, starts
+pub(crate) fn statement_line(sql: &str, statement: &str) -> usize {
+    let offset = (statement.as_ptr() as usize)
+        .checked_sub(sql.as_ptr() as usize)
+        .filter(|&offset| offset <= sql.len())
+        .expect("statement is a slice of sql");
+    sql[..offset].matches('\n').count() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_simple() {
+        assert_eq!(
+            split_statements("SET @a = 1; SELECT @a;\n"),
+            vec!["SET @a = 1", "SELECT @a"]
+        );
+        // A final statement without a semicolon is kept
+        assert_eq!(
+            split_statements("SELECT 1;SELECT 2"),
+            vec!["SELECT 1", "SELECT 2"]
+        );
+        assert!(split_statements("").is_empty());
+        assert!(split_statements(" ;;\n ; ").is_empty());
+    }
+
+    #[test]
+    fn test_split_ignores_semicolons_in_strings() {
+        assert_eq!(
+            split_statements("SELECT 'a;b', \"c;d\"; SELECT 2"),
+            vec!["SELECT 'a;b', \"c;d\"", "SELECT 2"]
+        );
+        // Backslash-escaped and doubled quotes do not end the literal
+        assert_eq!(
+            split_statements(r"SELECT 'it\'s;' ; SELECT 'x''y;z'"),
+            vec![r"SELECT 'it\'s;'", "SELECT 'x''y;z'"]
+        );
+        assert_eq!(
+            split_statements(r#"SELECT "a\";b"; SELECT 1"#),
+            vec![r#"SELECT "a\";b""#, "SELECT 1"]
+        );
+        // An unterminated literal swallows the rest of the script
+        assert_eq!(
+            split_statements("SELECT 'a; SELECT 2"),
+            vec!["SELECT 'a; SELECT 2"]
+        );
+    }
+
+    #[test]
+    fn test_split_ignores_semicolons_in_identifiers() {
+        assert_eq!(
+            split_statements("SELECT `a;b` FROM `t``;`; SELECT 2"),
+            vec!["SELECT `a;b` FROM `t``;`", "SELECT 2"]
+        );
+    }
+
+    #[test]
+    fn test_split_line_comments() {
+        assert_eq!(
+            split_statements("-- setup; not a split\nSET @a = 1; # also; a comment\nSELECT @a"),
+            vec![
+                "-- setup; not a split\nSET @a = 1",
+                "# also; a comment\nSELECT @a"
+            ]
+        );
+        // `--` without trailing whitespace is an operator, not a comment
+        assert_eq!(
+            split_statements("SELECT 1--1; SELECT 2"),
+            vec!["SELECT 1--1", "SELECT 2"]
+        );
+        // A comment at the very end of the script is not an extra statement
+        assert_eq!(split_statements("SELECT 1; -- done\n"), vec!["SELECT 1"]);
+        assert_eq!(split_statements("SELECT 1; --"), vec!["SELECT 1"]);
+    }
+
+    #[test]
+    fn test_split_block_comments() {
+        assert_eq!(
+            split_statements("/* header; with semicolons */ SELECT 1; /* a */ SELECT /* ; */ 2;"),
+            vec![
+                "/* header; with semicolons */ SELECT 1",
+                "/* a */ SELECT /* ; */ 2"
+            ]
+        );
+        assert!(split_statements("/* only; a comment */").is_empty());
+        // Quotes inside comments do not open a literal
+        assert_eq!(
+            split_statements("/* don't */ SELECT 1; SELECT 2"),
+            vec!["/* don't */ SELECT 1", "SELECT 2"]
+        );
+        assert_eq!(
+            split_statements("-- it's\nSELECT 1; SELECT 2"),
+            vec!["-- it's\nSELECT 1", "SELECT 2"]
+        );
+    }
+
+    #[test]
+    fn test_split_executable_comments() {
+        // Versioned comments and optimizer hints are statements on their own
+        assert_eq!(
+            split_statements("/*!40101 SET NAMES utf8mb4 */; SELECT /*+ NO_ICP(t) */ 1 FROM t"),
+            vec![
+                "/*!40101 SET NAMES utf8mb4 */",
+                "SELECT /*+ NO_ICP(t) */ 1 FROM t"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_multibyte_text() {
+        assert_eq!(
+            split_statements("SELECT 'ação;é'; SELECT '日本'"),
+            vec!["SELECT 'ação;é'", "SELECT '日本'"]
+        );
+    }
+
+    #[test]
+    fn test_split_delimiter_directive() {
+        let sql = "DROP PROCEDURE IF EXISTS p;\n\
+                   DELIMITER $$\n\
+                   CREATE PROCEDURE p()\nBEGIN\n  SELECT 1;\n  SELECT 2;\nEND$$\n\
+                   delimiter ;\n\
+                   CALL p();";
+        assert_eq!(
+            split_statements(sql),
+            vec![
+                "DROP PROCEDURE IF EXISTS p",
+                "CREATE PROCEDURE p()\nBEGIN\n  SELECT 1;\n  SELECT 2;\nEND",
+                "CALL p()",
+            ]
+        );
+        // Multi-character terminators inside literals and comments do not
+        // split, and a script without a closing `DELIMITER ;` still ends
+        assert_eq!(
+            split_statements("DELIMITER //\nSELECT '//' /* // */; SELECT 2 //\nSELECT 3"),
+            vec!["SELECT '//' /* // */; SELECT 2", "SELECT 3"]
+        );
+        // Only a directive at the start of a statement counts
+        assert_eq!(
+            split_statements("SELECT 1 AS delimiter ; SELECT delimiter FROM t"),
+            vec!["SELECT 1 AS delimiter", "SELECT delimiter FROM t"]
+        );
+    }
+
+    #[test]
+    fn test_split_terminated() {
+        let split = split_terminated("SELECT 'a;b'; SELECT\n  2", ";");
+        assert_eq!(split.statements, vec!["SELECT 'a;b'"]);
+        assert_eq!(split.rest, Some(" SELECT\n  2"));
+        assert_eq!(split.delimiter, ";");
+
+        // Open literals and comments keep the input pending
+        assert_eq!(split_terminated("SELECT 'a;", ";").rest, Some("SELECT 'a;"));
+        assert_eq!(split_terminated("/* a; b", ";").rest, Some("/* a; b"));
+        assert_eq!(split_terminated("SELECT 1; -- done", ";").rest, None);
+
+        // The terminator carries over from earlier input
+        let split = split_terminated("DELIMITER $$", ";");
+        assert!(split.statements.is_empty() && split.rest.is_none());
+        let split = split_terminated("BEGIN SELECT 1; END$$", &split.delimiter);
+        assert_eq!(split.statements, vec!["BEGIN SELECT 1; END"]);
+        assert_eq!(split.delimiter, "$$");
+    }
+
+    #[test]
+    fn test_statement_line() {
+        let sql = "SET @a = 1;\n\n-- setup done\nSELECT @a;  SELECT 2;\n\nSELECT\n  3";
+        let lines: Vec<usize> = split_statements(sql)
+            .into_iter()
+            .map(|statement| statement_line(sql, statement))
+            .collect();
+        assert_eq!(lines, vec![1, 3, 4, 6]);
+    }
+}
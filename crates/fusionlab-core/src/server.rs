@@ -0,0 +1,127 @@
+//! Server identification for version-gated features
+//!
+//! MariaDB reuses MySQL's version scheme loosely (10.x, 11.x) but lacks
+//! several MySQL 8.0 features, so gating needs the flavor as well as the
+//! version number.
+
+use std::fmt;
+
+/// Server product, from `VERSION()` and `@@version_comment`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerFlavor {
+    MySQL,
+    /// Percona Server for MySQL, feature-compatible with the same MySQL version
+    Percona,
+    MariaDB,
+}
+
+impl fmt::Display for ServerFlavor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServerFlavor::MySQL => write!(f, "MySQL"),
+            ServerFlavor::Percona => write!(f, "Percona Server"),
+            ServerFlavor::MariaDB => write!(f, "MariaDB"),
+        }
+    }
+}
+
+/// Version and flavor of the connected server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerInfo {
+    /// Full `VERSION()` string, e.g. `8.0.36-28` or `10.11.6-MariaDB-log`
+    pub version: String,
+    /// Numeric version as (major, minor, patch)
+    pub number: (u16, u16, u16),
+    pub flavor: ServerFlavor,
+}
+
+impl ServerInfo {
+    /// Build from the results of `SELECT VERSION(), @@version_comment`
+    ///
+    /// Missing or malformed version components are read as 0.
+    pub fn parse(version: &str, version_comment: &str) -> Self {
+        let contains = |s: &str, needle: &str| s.to_ascii_lowercase().contains(needle);
+        let flavor = if contains(version, "mariadb") || contains(version_comment, "mariadb") {
+            ServerFlavor::MariaDB
+        } else if contains(version_comment, "percona") {
+            ServerFlavor::Percona
+        } else {
+            ServerFlavor::MySQL
+        };
+
+        // Older MariaDB releases report a `5.5.5-` prefix for old clients
+        let bare = match flavor {
+            ServerFlavor::MariaDB => version.strip_prefix("5.5.5-").unwrap_or(version),
+            _ => version,
+        };
+        let mut parts = bare
+            .split(|c: char| !c.is_ascii_digit())
+            .map(|p| p.parse::<u16>().unwrap_or(0));
+        let number = (
+            parts.next().unwrap_or(0),
+            parts.next().unwrap_or(0),
+            parts.next().unwrap_or(0),
+        );
+
+        Self {
+            version: version.to_string(),
+            number,
+            flavor,
+        }
+    }
+
+    /// Whether the server has a MySQL feature introduced in version `min`
+    ///
+    /// Always false on MariaDB, whose 10.x versions do not track MySQL's.
+    pub fn supports_mysql_feature(&self, min: (u16, u16, u16)) -> bool {
+        self.flavor != ServerFlavor::MariaDB && self.number >= min
+    }
+}
+
+impl fmt::Display for ServerInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.flavor, self.version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_flavors() {
+        let mysql = ServerInfo::parse("8.0.36", "MySQL Community Server - GPL");
+        assert_eq!(mysql.number, (8, 0, 36));
+        assert_eq!(mysql.flavor, ServerFlavor::MySQL);
+        assert_eq!(mysql.to_string(), "MySQL 8.0.36");
+
+        let percona = ServerInfo::parse(
+            "8.0.36-28",
+            "Percona Server (GPL), Release 28, Revision 47601f19",
+        );
+        assert_eq!(percona.number, (8, 0, 36));
+        assert_eq!(percona.flavor, ServerFlavor::Percona);
+
+        let mariadb = ServerInfo::parse("10.11.6-MariaDB-log", "MariaDB Server");
+        assert_eq!(mariadb.number, (10, 11, 6));
+        assert_eq!(mariadb.flavor, ServerFlavor::MariaDB);
+
+        let prefixed = ServerInfo::parse("5.5.5-10.5.22-MariaDB", "");
+        assert_eq!(prefixed.number, (10, 5, 22));
+        assert_eq!(prefixed.flavor, ServerFlavor::MariaDB);
+
+        assert_eq!(ServerInfo::parse("garbage", "").number, (0, 0, 0));
+        assert_eq!(ServerInfo::parse("5.7", "").number, (5, 7, 0));
+        assert_eq!(ServerInfo::parse("5.5.5-log", "").number, (5, 5, 5));
+    }
+
+    #[test]
+    fn test_supports_mysql_feature() {
+        let explain_analyze = (8, 0, 18);
+        assert!(ServerInfo::parse("8.0.18", "").supports_mysql_feature(explain_analyze));
+        assert!(ServerInfo::parse("8.4.0", "").supports_mysql_feature(explain_analyze));
+        assert!(!ServerInfo::parse("8.0.17", "").supports_mysql_feature(explain_analyze));
+        assert!(!ServerInfo::parse("5.7.44-log", "").supports_mysql_feature(explain_analyze));
+        assert!(!ServerInfo::parse("10.11.6-MariaDB", "").supports_mysql_feature(explain_analyze));
+    }
+}
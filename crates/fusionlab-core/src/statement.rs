@@ -0,0 +1,395 @@
+//! Classification of single SQL statements
+//!
+//! `MySQLRunner`'s EXPLAIN helpers prefix the user's query, which only works
+//! for statements MySQL can explain. Statements are classified by their
+//! leading keyword, looking past comments and `WITH` clauses.
+//!
+//! Query tags are added the same way, as a leading comment.
+
+use crate::lexer::{self, PieceKind};
+use crate::{FusionLabError, Result};
+
+/// Kind of a statement, from its leading keyword
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum StatementKind {
+    /// `SELECT`, or a parenthesized query expression
+    Select,
+    Table,
+    Insert,
+    Replace,
+    Update,
+    Delete,
+    /// `EXPLAIN`, `DESCRIBE` or `DESC`
+    Explain,
+    /// Any other statement, with its leading keyword upper-cased
+    Other(String),
+    /// Only whitespace and comments
+    Empty,
+}
+
+impl StatementKind {
+    /// Whether MySQL accepts the statement after `EXPLAIN`
+    pub(crate) fn is_explainable(&self) -> bool {
+        matches!(
+            self,
+            StatementKind::Select
+                | StatementKind::Table
+                | StatementKind::Insert
+                | StatementKind::Replace
+                | StatementKind::Update
+                | StatementKind::Delete
+        )
+    }
+
+    fn from_keyword(word: &str) -> Self {
+        match word.to_ascii_uppercase().as_str() {
+            "SELECT" | "(" => StatementKind::Select,
+            "TABLE" => StatementKind::Table,
+            "INSERT" => StatementKind::Insert,
+            "REPLACE" => StatementKind::Replace,
+            "UPDATE" => StatementKind::Update,
+            "DELETE" => StatementKind::Delete,
+            "EXPLAIN" | "DESCRIBE" | "DESC" => StatementKind::Explain,
+            other => StatementKind::Other(other.to_string()),
+        }
+    }
+}
+
+/// Tokens of a statement, skipping whitespace and comments
+///
+/// A token is a word, a quoted string or identifier, or a single other
+/// character.
+struct Tokens<'a> {
+    sql: &'a str,
+    pos: usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(sql: &'a str) -> Self {
+        Self { sql, pos: 0 }
+    }
+
+    /// Advance past whitespace and comments; returns the new position
+    ///
+    /// With `keep_executable`, stops at `/*!` and `/*+` comments, which the
+    /// server executes.
+    fn skip_trivia(&mut self, keep_executable: bool) -> usize {
+        while self.pos < self.sql.len() {
+            let piece = lexer::piece(self.sql, self.pos);
+            match piece.kind {
+                PieceKind::Whitespace | PieceKind::LineComment => {}
+                PieceKind::BlockComment { executable } if !(keep_executable && executable) => {}
+                _ => break,
+            }
+            self.pos = piece.end;
+        }
+        self.pos
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let start = self.skip_trivia(false);
+        let bytes = self.sql.as_bytes();
+        let first = *bytes.get(start)?;
+        let is_word = |c: u8| c.is_ascii_alphanumeric() || c == b'_' || c == b'$' || c >= 0x80;
+
+        let mut end = start + 1;
+        if is_word(first) {
+            while bytes.get(end).is_some_and(|&c| is_word(c)) {
+                end += 1;
+            }
+        } else if matches!(first, b'\'' | b'"' | b'`') {
+            end = lexer::piece(self.sql, start).end;
+        }
+        self.pos = end;
+        Some(&self.sql[start..end])
+    }
+}
+
+/// Classify a single statement by its leading keyword
+///
+/// For `WITH` the kind is that of the statement following the common table
+/// expressions, e.g. `WITH x AS (...) DELETE ...` is a `Delete`.
+pub(crate) fn classify(sql: &str) -> StatementKind {
+    let mut tokens = Tokens::new(sql);
+    let Some(first) = tokens.next() else {
+        return StatementKind::Empty;
+    };
+    if !first.eq_ignore_ascii_case("WITH") {
+        return StatementKind::from_keyword(first);
+    }
+
+    let mut depth = 0usize;
+    for token in tokens {
+        match token {
+            "(" => depth += 1,
+            ")" => depth = depth.saturating_sub(1),
+            word if depth == 0 => {
+                let kind = StatementKind::from_keyword(word);
+                if kind.is_explainable() {
+                    return kind;
+                }
+            }
+            _ => {}
+        }
+    }
+    StatementKind::Other("WITH".to_string())
+}
+
+/// Whether a statement takes row locks with `FOR UPDATE`, `FOR SHARE` or
+/// `LOCK IN SHARE MODE`, in the outer query or a subquery
+pub(crate) fn is_locking_read(sql: &str) -> bool {
+    let tokens: Vec<&str> = Tokens::new(sql).collect();
+    let has = |words: &[&str]| {
+        tokens.windows(words.len()).any(|window| {
+            window
+                .iter()
+                .zip(words)
+                .all(|(token, word)| token.eq_ignore_ascii_case(word))
+        })
+    };
+    has(&["FOR", "UPDATE"]) || has(&["FOR", "SHARE"]) || has(&["LOCK", "IN", "SHARE", "MODE"])
+}
+
+/// Build `<prefix> <statement>`, e.g. `EXPLAIN FORMAT=TREE SELECT ...`
+///
+/// Leading comments are dropped, and a query that is already an EXPLAIN has
+/// its own `EXPLAIN [ANALYZE] [FORMAT=...]` replaced by `prefix` rather than
+/// doubled. Statements MySQL cannot explain, such as `SET` or `SHOW`, are
+/// rejected with `FusionLabError::Parse`.
+pub(crate) fn explain_sql(prefix: &str, sql: &str) -> Result<String> {
+    let mut body = skip_leading_comments(sql);
+    if classify(body) == StatementKind::Explain {
+        body = strip_explain(body);
+    }
+
+    match classify(body) {
+        kind if kind.is_explainable() => Ok(format!("{} {}", prefix, body)),
+        StatementKind::Empty => Err(FusionLabError::Parse(
+            "nothing to EXPLAIN: the query is empty".to_string(),
+        )),
+        StatementKind::Explain => Err(FusionLabError::Parse(format!(
+            "cannot EXPLAIN {:?}; expected a query after EXPLAIN",
+            sql.trim()
+        ))),
+        StatementKind::Other(keyword) => Err(FusionLabError::Parse(format!(
+            "cannot EXPLAIN a {} statement; only SELECT, TABLE, INSERT, REPLACE, \
+             UPDATE and DELETE can be explained",
+            keyword
+        ))),
+        _ => unreachable!("explainable kinds are handled above"),
+    }
+}
+
+/// Prefix `sql` with a `/* fusionlab:tag=... */` comment
+///
+/// The comment is kept in the slow log, the general log and
+/// performance_schema, so statements can be traced back to their origin.
+/// Returns the sanitized tag along with the statement.
+pub(crate) fn tag_sql(tag: &str, sql: &str) -> (String, String) {
+    let tag = sanitize_tag(tag);
+    let sql = format!("/* fusionlab:tag={} */ {}", tag, sql);
+    (tag, sql)
+}
+
+/// Make `tag` safe to embed in a block comment
+///
+/// A `\` is inserted between `*` and `/` in either order, so the tag can
+/// neither end the comment early nor open a nested one. Control characters,
+/// including newlines, become spaces to keep the statement on one line in
+/// the logs.
+fn sanitize_tag(tag: &str) -> String {
+    let mut sanitized = String::with_capacity(tag.len());
+    let mut prev = None;
+    for c in tag.chars() {
+        let c = if c.is_control() { ' ' } else { c };
+        if matches!((prev, c), (Some('*'), '/') | (Some('/'), '*')) {
+            sanitized.push('\\');
+        }
+        sanitized.push(c);
+        prev = Some(c);
+    }
+    sanitized
+}
+
+/// Drop leading whitespace and comments, keeping `/*!` and `/*+` comments
+/// that the server executes
+fn skip_leading_comments(sql: &str) -> &str {
+    let start = Tokens::new(sql).skip_trivia(true);
+    &sql[start..]
+}
+
+/// Remove a leading `EXPLAIN`/`DESCRIBE` keyword and its options
+fn strip_explain(sql: &str) -> &str {
+    let mut tokens = Tokens::new(sql);
+    tokens.next();
+    loop {
+        let before = tokens.pos;
+        match tokens.next() {
+            Some(word)
+                if ["ANALYZE", "EXTENDED", "PARTITIONS"]
+                    .iter()
+                    .any(|opt| word.eq_ignore_ascii_case(opt)) => {}
+            Some(word) if word.eq_ignore_ascii_case("FORMAT") => {
+                // FORMAT = TRADITIONAL | JSON | TREE
+                if tokens.next() != Some("=") {
+                    return skip_leading_comments(&sql[before..]);
+                }
+                tokens.next();
+            }
+            _ => return skip_leading_comments(&sql[before..]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify("select 1"), StatementKind::Select);
+        assert_eq!(
+            classify("(SELECT 1) UNION (SELECT 2)"),
+            StatementKind::Select
+        );
+        assert_eq!(classify("/* hint */ SELECT 1"), StatementKind::Select);
+        assert_eq!(classify("-- note\n# more\nTABLE t"), StatementKind::Table);
+        assert_eq!(classify("EXPLAIN SELECT 1"), StatementKind::Explain);
+        assert_eq!(classify("desc t"), StatementKind::Explain);
+        assert_eq!(
+            classify("SET @a = 1"),
+            StatementKind::Other("SET".to_string())
+        );
+        assert_eq!(
+            classify("SHOW TABLES"),
+            StatementKind::Other("SHOW".to_string())
+        );
+        assert_eq!(classify(" /* only a comment */ "), StatementKind::Empty);
+        assert_eq!(classify(""), StatementKind::Empty);
+    }
+
+    #[test]
+    fn test_classify_with() {
+        assert_eq!(
+            classify("WITH x AS (SELECT 1 AS a) SELECT a FROM x"),
+            StatementKind::Select
+        );
+        assert_eq!(
+            classify(
+                "WITH RECURSIVE r (n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM r WHERE n < 3), \
+                 d AS (SELECT ')' AS p) DELETE FROM t WHERE id IN (SELECT n FROM r)"
+            ),
+            StatementKind::Delete
+        );
+        assert_eq!(
+            classify("WITH x AS (SELECT 1) UPDATE t, x SET t.a = 1"),
+            StatementKind::Update
+        );
+        // A TABLE inside a CTE body does not decide the kind
+        assert_eq!(
+            classify("WITH x AS (TABLE t) SELECT * FROM x"),
+            StatementKind::Select
+        );
+        assert_eq!(
+            classify("WITH x AS (SELECT 1)"),
+            StatementKind::Other("WITH".to_string())
+        );
+    }
+
+    #[test]
+    fn test_is_locking_read() {
+        assert!(is_locking_read("SELECT * FROM t WHERE id = 1 FOR UPDATE"));
+        assert!(is_locking_read("select * from t for share nowait"));
+        assert!(is_locking_read("SELECT * FROM t LOCK IN SHARE MODE"));
+        assert!(is_locking_read(
+            "SELECT * FROM t WHERE id IN (SELECT id FROM u FOR UPDATE)"
+        ));
+        assert!(!is_locking_read("SELECT * FROM t"));
+        assert!(!is_locking_read("SELECT 'FOR UPDATE' AS note"));
+        assert!(!is_locking_read("SELECT 1 /* FOR UPDATE */"));
+        assert!(!is_locking_read("SELECT `for`, `update` FROM t"));
+    }
+
+    #[test]
+    fn test_explain_sql() {
+        assert_eq!(
+            explain_sql("EXPLAIN", "/* hint */ SELECT 1").unwrap(),
+            "EXPLAIN SELECT 1"
+        );
+        assert_eq!(
+            explain_sql("EXPLAIN", "-- why\n  SELECT 1").unwrap(),
+            "EXPLAIN SELECT 1"
+        );
+        assert_eq!(
+            explain_sql(
+                "EXPLAIN FORMAT=TREE",
+                "WITH x AS (SELECT 1 AS a) SELECT a FROM x"
+            )
+            .unwrap(),
+            "EXPLAIN FORMAT=TREE WITH x AS (SELECT 1 AS a) SELECT a FROM x"
+        );
+        // Executable comments are kept
+        assert_eq!(
+            explain_sql("EXPLAIN", "/*+ MAX_EXECUTION_TIME(10) */ SELECT 1").unwrap(),
+            "EXPLAIN /*+ MAX_EXECUTION_TIME(10) */ SELECT 1"
+        );
+        assert_eq!(
+            explain_sql("EXPLAIN", "/* just a note */")
+                .unwrap_err()
+                .to_string(),
+            "Parse error: nothing to EXPLAIN: the query is empty"
+        );
+    }
+
+    #[test]
+    fn test_explain_sql_already_explained() {
+        assert_eq!(
+            explain_sql("EXPLAIN", "EXPLAIN SELECT 1").unwrap(),
+            "EXPLAIN SELECT 1"
+        );
+        assert_eq!(
+            explain_sql("EXPLAIN FORMAT=JSON", "explain analyze /* x */ SELECT 1").unwrap(),
+            "EXPLAIN FORMAT=JSON SELECT 1"
+        );
+        assert_eq!(
+            explain_sql("EXPLAIN ANALYZE", "EXPLAIN FORMAT = TREE DELETE FROM t").unwrap(),
+            "EXPLAIN ANALYZE DELETE FROM t"
+        );
+        // DESCRIBE of a table is not a query
+        assert!(explain_sql("EXPLAIN", "DESCRIBE t").is_err());
+        assert!(explain_sql("EXPLAIN", "EXPLAIN").is_err());
+    }
+
+    #[test]
+    fn test_explain_sql_rejects_other_statements() {
+        let err = explain_sql("EXPLAIN", "SET @a = 1").unwrap_err();
+        assert!(matches!(err, FusionLabError::Parse(_)));
+        assert!(err.to_string().contains("cannot EXPLAIN a SET statement"));
+        assert!(explain_sql("EXPLAIN", "show tables").is_err());
+        assert!(explain_sql("EXPLAIN", "  ").is_err());
+    }
+
+    #[test]
+    fn test_tag_sql() {
+        let (tag, sql) = tag_sql("replay.sql:12", "SELECT 1");
+        assert_eq!(tag, "replay.sql:12");
+        assert_eq!(sql, "/* fusionlab:tag=replay.sql:12 */ SELECT 1");
+        assert_eq!(classify(&sql), StatementKind::Select);
+
+        // Attempts to close the comment and smuggle in SQL
+        let (tag, sql) = tag_sql("x */ DROP TABLE t; /*", "SELECT 1");
+        assert_eq!(tag, "x *\\/ DROP TABLE t; /\\*");
+        assert_eq!(sql.matches("*/").count(), 1);
+        assert_eq!(classify(&sql), StatementKind::Select);
+
+        let (tag, _) = tag_sql("a**//b\n/*!c", "SELECT 1");
+        assert_eq!(tag, "a**\\//b /\\*!c");
+        let (tag, _) = tag_sql("ends with *", "SELECT 1");
+        assert_eq!(tag, "ends with *");
+        assert_eq!(tag_sql("", "SELECT 1").1, "/* fusionlab:tag= */ SELECT 1");
+    }
+}
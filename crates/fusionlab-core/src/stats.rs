@@ -0,0 +1,145 @@
+//! Timing statistics for repeated query runs
+//!
+//! Shared by the MySQL and DataFusion runners so benchmark output from both
+//! engines can be compared directly.
+
+use std::fmt;
+
+/// Summary of the measured iterations of a repeated query
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryStats {
+    /// Warmup iterations that ran but are not part of the statistics
+    pub warmup: usize,
+    /// Duration of each measured iteration in milliseconds, in run order
+    pub samples_ms: Vec<f64>,
+    pub min_ms: f64,
+    pub max_ms: f64,
+    pub mean_ms: f64,
+    pub median_ms: f64,
+    /// 90th percentile (nearest rank)
+    pub p90_ms: f64,
+    /// 95th percentile (nearest rank)
+    pub p95_ms: f64,
+    /// 99th percentile (nearest rank)
+    pub p99_ms: f64,
+    /// Sample standard deviation; 0 with fewer than two samples
+    pub stddev_ms: f64,
+    /// Row count of the last iteration, to check runs returned the same data
+    pub row_count: usize,
+}
+
+impl QueryStats {
+    /// Compute statistics over measured samples; all fields are 0 when
+    /// `samples_ms` is empty
+    pub fn from_samples(samples_ms: Vec<f64>, warmup: usize, row_count: usize) -> Self {
+        let mut sorted = samples_ms.clone();
+        sorted.sort_by(f64::total_cmp);
+        let n = sorted.len();
+
+        let (min_ms, max_ms, mean_ms, median_ms, stddev_ms) = if n == 0 {
+            (0.0, 0.0, 0.0, 0.0, 0.0)
+        } else {
+            let mean = sorted.iter().sum::<f64>() / n as f64;
+            let median = if n.is_multiple_of(2) {
+                (sorted[n / 2 - 1] + sorted[n / 2]) / 2.0
+            } else {
+                sorted[n / 2]
+            };
+            let stddev = if n < 2 {
+                0.0
+            } else {
+                let variance =
+                    sorted.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / (n - 1) as f64;
+                variance.sqrt()
+            };
+            (sorted[0], sorted[n - 1], mean, median, stddev)
+        };
+        let nearest_rank = |pct| {
+            if n == 0 {
+                0.0
+            } else {
+                percentile(&sorted, pct)
+            }
+        };
+
+        Self {
+            warmup,
+            samples_ms,
+            min_ms,
+            max_ms,
+            mean_ms,
+            median_ms,
+            p90_ms: nearest_rank(90.0),
+            p95_ms: nearest_rank(95.0),
+            p99_ms: nearest_rank(99.0),
+            stddev_ms,
+            row_count,
+        }
+    }
+
+    /// Number of measured iterations
+    pub fn iterations(&self) -> usize {
+        self.samples_ms.len()
+    }
+}
+
+impl fmt::Display for QueryStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "min {:.2}ms, median {:.2}ms, mean {:.2}ms, p95 {:.2}ms, max {:.2}ms, stddev {:.2}ms",
+            self.min_ms, self.median_ms, self.mean_ms, self.p95_ms, self.max_ms, self.stddev_ms
+        )
+    }
+}
+
+/// Nearest-rank percentile of ascending, non-empty `sorted`
+fn percentile(sorted: &[f64], pct: f64) -> f64 {
+    let rank = (pct / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_samples() {
+        let stats = QueryStats::from_samples(vec![4.0, 2.0, 8.0, 6.0], 1, 10);
+        assert_eq!(stats.iterations(), 4);
+        assert_eq!(stats.samples_ms, vec![4.0, 2.0, 8.0, 6.0]);
+        assert_eq!(stats.min_ms, 2.0);
+        assert_eq!(stats.max_ms, 8.0);
+        assert_eq!(stats.mean_ms, 5.0);
+        assert_eq!(stats.median_ms, 5.0);
+        assert_eq!(stats.p95_ms, 8.0);
+        assert!((stats.stddev_ms - (20.0f64 / 3.0).sqrt()).abs() < 1e-9);
+        assert_eq!(stats.warmup, 1);
+        assert_eq!(stats.row_count, 10);
+    }
+
+    #[test]
+    fn test_percentile_nearest_rank() {
+        let samples: Vec<f64> = (1..=100).map(f64::from).collect();
+        let stats = QueryStats::from_samples(samples, 0, 0);
+        assert_eq!(stats.median_ms, 50.5);
+        assert_eq!(stats.p90_ms, 90.0);
+        assert_eq!(stats.p95_ms, 95.0);
+        assert_eq!(stats.p99_ms, 99.0);
+
+        let single = QueryStats::from_samples(vec![3.0], 0, 0);
+        assert_eq!(single.median_ms, 3.0);
+        assert_eq!(single.p95_ms, 3.0);
+        assert_eq!(single.p99_ms, 3.0);
+        assert_eq!(single.stddev_ms, 0.0);
+    }
+
+    #[test]
+    fn test_empty_samples() {
+        let stats = QueryStats::from_samples(vec![], 2, 0);
+        assert_eq!(stats.iterations(), 0);
+        assert_eq!(stats.mean_ms, 0.0);
+        assert_eq!(stats.p95_ms, 0.0);
+        assert_eq!(stats.p99_ms, 0.0);
+    }
+}
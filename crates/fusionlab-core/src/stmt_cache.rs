@@ -0,0 +1,160 @@
+//! Bounded cache of prepared statements keyed by SQL text
+//!
+//! mysql_async's own per-connection cache is cleared whenever a connection
+//! is reset on its way back to the pool, so `MySQLRunner` keeps statements
+//! for the prepared-statement connection it opens outside the pool here
+//! instead.
+
+use std::collections::HashMap;
+
+/// Default number of statements kept by `MySQLRunner::run_query_params`
+pub const DEFAULT_STMT_CACHE_SIZE: usize = 256;
+
+/// Counters of the prepared statement cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StmtCacheStats {
+    /// Executions that reused a cached statement
+    pub hits: u64,
+    /// Executions that had to prepare the statement
+    pub misses: u64,
+    /// Statements closed to make room for new ones
+    pub evictions: u64,
+    /// Statements currently cached
+    pub entries: usize,
+    /// Upper bound on `entries`
+    pub capacity: usize,
+}
+
+/// Least-recently-used map from SQL text to a statement handle
+#[derive(Debug)]
+pub(crate) struct StmtCache<S> {
+    capacity: usize,
+    /// Statement and the clock value of its last use
+    entries: HashMap<String, (S, u64)>,
+    clock: u64,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+impl<S: Clone> StmtCache<S> {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            clock: 0,
+            hits: 0,
+            misses: 0,
+            evictions: 0,
+        }
+    }
+
+    /// Look up `sql`, counting a hit or a miss
+    pub(crate) fn get(&mut self, sql: &str) -> Option<S> {
+        self.clock += 1;
+        match self.entries.get_mut(sql) {
+            Some((stmt, last_used)) => {
+                *last_used = self.clock;
+                self.hits += 1;
+                Some(stmt.clone())
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Count a miss for an execution that bypassed the cache
+    pub(crate) fn record_miss(&mut self) {
+        self.misses += 1;
+    }
+
+    /// Cache `stmt`, returning the statement the caller must now close: the
+    /// least recently used one when full, or `stmt` itself with capacity 0
+    pub(crate) fn insert(&mut self, sql: String, stmt: S) -> Option<S> {
+        if self.capacity == 0 {
+            return Some(stmt);
+        }
+        let evicted = if self.entries.len() >= self.capacity && !self.entries.contains_key(&sql) {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(key, _)| key.clone());
+            oldest.and_then(|key| self.entries.remove(&key))
+        } else {
+            None
+        };
+        self.clock += 1;
+        self.entries.insert(sql, (stmt, self.clock));
+        evicted.map(|(stmt, _)| {
+            self.evictions += 1;
+            stmt
+        })
+    }
+
+    /// Forget all statements, e.g. after their connection was lost;
+    /// counters are kept
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub(crate) fn stats(&self) -> StmtCacheStats {
+        StmtCacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            evictions: self.evictions,
+            entries: self.entries.len(),
+            capacity: self.capacity,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hits_and_misses() {
+        let mut cache = StmtCache::new(2);
+        assert_eq!(cache.get("SELECT ?"), None);
+        assert_eq!(cache.insert("SELECT ?".to_string(), 1), None);
+        assert_eq!(cache.get("SELECT ?"), Some(1));
+        cache.record_miss();
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (1, 2));
+        assert_eq!((stats.entries, stats.capacity), (1, 2));
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let mut cache = StmtCache::new(2);
+        cache.insert("a".to_string(), 1);
+        cache.insert("b".to_string(), 2);
+        // Touching "a" makes "b" the oldest
+        assert_eq!(cache.get("a"), Some(1));
+        assert_eq!(cache.insert("c".to_string(), 3), Some(2));
+        assert_eq!(cache.get("b"), None);
+        assert_eq!(cache.get("a"), Some(1));
+        assert_eq!(cache.get("c"), Some(3));
+
+        // Replacing an existing key does not evict
+        assert_eq!(cache.insert("c".to_string(), 4), None);
+        assert_eq!(cache.stats().evictions, 1);
+        assert_eq!(cache.stats().entries, 2);
+
+        cache.clear();
+        assert_eq!(cache.stats().entries, 0);
+        assert_eq!(cache.stats().evictions, 1);
+    }
+
+    #[test]
+    fn test_zero_capacity_keeps_nothing() {
+        let mut cache = StmtCache::new(0);
+        assert_eq!(cache.insert("a".to_string(), 1), Some(1));
+        assert_eq!(cache.get("a"), None);
+        assert_eq!(cache.stats().evictions, 0);
+    }
+}
@@ -0,0 +1,179 @@
+//! Reading a result set row by row as it arrives, with timing
+
+use mysql_async::{Column, Row};
+use std::collections::HashMap;
+use std::time::Instant;
+
+use crate::{CellValue, ColumnMeta, ColumnType, MySQLWarning, Protocol, QueryResult, Result};
+
+/// Rows and timings read from a single result set
+pub(crate) struct FetchedRows {
+    row_count: usize,
+    affected_rows: u64,
+    rows: Vec<Vec<CellValue>>,
+    columns: Vec<String>,
+    column_types: Vec<ColumnType>,
+    column_meta: Vec<ColumnMeta>,
+    lossy_decodes: usize,
+    exec_ms: f64,
+    fetch_ms: f64,
+}
+
+impl FetchedRows {
+    pub(crate) fn into_query_result(
+        self,
+        warnings: Vec<MySQLWarning>,
+        compressed: bool,
+        protocol: Protocol,
+    ) -> QueryResult {
+        QueryResult {
+            row_count: self.row_count,
+            affected_rows: self.affected_rows,
+            duration_ms: self.exec_ms + self.fetch_ms,
+            exec_ms: self.exec_ms,
+            fetch_ms: self.fetch_ms,
+            rows: self.rows,
+            columns: self.columns,
+            column_types: self.column_types,
+            column_meta: self.column_meta,
+            warnings,
+            status_diff: HashMap::new(),
+            server_stats: None,
+            tag: None,
+            compressed,
+            truncated: self.rows.len() < self.row_count,
+            stages: vec![],
+            stages_unavailable: false,
+            protocol,
+            lossy_decodes: self.lossy_decodes,
+        }
+    }
+}
+
+/// Drain a result set, timing the first row separately from the rest
+///
+/// `start` is when the statement was sent. Everything up to the first row
+/// (or the end of an empty result) counts as execution time; reading the
+/// remaining rows counts as fetch time.
+pub(crate) async fn fetch_rows<P, F>(
+    mut result: mysql_async::QueryResult<'_, '_, P>,
+    start: Instant,
+    limit: usize,
+    mut on_row: F,
+) -> Result<FetchedRows>
+where
+    P: mysql_async::prelude::Protocol,
+    F: FnMut(&Row),
+{
+    let (columns, column_meta) = column_info(result.columns_ref());
+    let column_types = column_meta.iter().map(|m| m.column_type).collect();
+
+    let mut row_count = 0usize;
+    let mut rows = Vec::new();
+    let mut lossy_decodes = 0;
+    let mut first_row_at = None;
+    while let Some(row) = result.next().await? {
+        first_row_at.get_or_insert_with(Instant::now);
+        on_row(&row);
+        if rows.len() < limit {
+            rows.push(row_to_cells(row, &mut lossy_decodes));
+        }
+        row_count += 1;
+    }
+    let affected_rows = result.affected_rows();
+    // Drain any trailing result sets so the connection is reusable
+    result.drop_result().await?;
+    let end = Instant::now();
+    let first_row_at = first_row_at.unwrap_or(end);
+
+    Ok(FetchedRows {
+        row_count,
+        affected_rows,
+        rows,
+        columns,
+        column_types,
+        column_meta,
+        lossy_decodes,
+        exec_ms: (first_row_at - start).as_secs_f64() * 1000.0,
+        fetch_ms: (end - first_row_at).as_secs_f64() * 1000.0,
+    })
+}
+
+/// Extract column names and metadata from result set metadata
+fn column_info(columns: &[Column]) -> (Vec<String>, Vec<ColumnMeta>) {
+    columns
+        .iter()
+        .map(|c| (c.name_str().to_string(), ColumnMeta::from_column(c)))
+        .unzip()
+}
+
+/// Convert a single MySQL row to typed cells, adding the text values that
+/// were decoded lossily to `lossy_decodes`
+fn row_to_cells(row: Row, lossy_decodes: &mut usize) -> Vec<CellValue> {
+    let columns = row.columns();
+    row.unwrap_raw()
+        .into_iter()
+        .zip(columns.iter())
+        .map(|(value, column)| match value {
+            Some(v) => {
+                let (cell, lossy) = CellValue::decode(v, column);
+                *lossy_decodes += usize::from(lossy);
+                cell
+            }
+            None => CellValue::Null,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mysql_async::consts::ColumnType as MySqlColumnType;
+
+    use crate::result::BINARY_CHARSET;
+
+    #[test]
+    fn test_column_info() {
+        let columns = vec![
+            Column::new(MySqlColumnType::MYSQL_TYPE_LONGLONG).with_name(b"id"),
+            Column::new(MySqlColumnType::MYSQL_TYPE_DATETIME2).with_name(b"ts"),
+            Column::new(MySqlColumnType::MYSQL_TYPE_BLOB)
+                .with_name(b"payload")
+                .with_character_set(BINARY_CHARSET),
+            Column::new(MySqlColumnType::MYSQL_TYPE_BLOB)
+                .with_name(b"notes")
+                .with_character_set(255),
+            Column::new(MySqlColumnType::MYSQL_TYPE_NEWDECIMAL)
+                .with_name(b"price")
+                .with_column_length(17)
+                .with_decimals(2),
+        ];
+        let (names, meta) = column_info(&columns);
+        assert_eq!(names, vec!["id", "ts", "payload", "notes", "price"]);
+        let types: Vec<ColumnType> = meta.iter().map(|m| m.column_type).collect();
+        assert_eq!(
+            types,
+            vec![
+                ColumnType::Int,
+                ColumnType::DateTime,
+                ColumnType::Blob,
+                ColumnType::String,
+                ColumnType::Decimal
+            ]
+        );
+        assert!(types[4].is_numeric());
+        assert!(!types[1].is_numeric());
+
+        // DECIMAL(15,2): sign and decimal point on top of 15 digits
+        assert_eq!(meta[4].decimal_precision(), Some((15, 2)));
+        assert_eq!(meta[2].charset, BINARY_CHARSET);
+        assert_eq!(meta[0].decimal_precision(), None);
+        let unsigned = ColumnMeta {
+            unsigned: true,
+            length: 11,
+            decimals: 0,
+            ..meta[4]
+        };
+        assert_eq!(unsigned.decimal_precision(), Some((11, 0)));
+    }
+}
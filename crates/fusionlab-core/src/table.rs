@@ -0,0 +1,441 @@
+//! ASCII table rendering for result rows
+//!
+//! Widths are measured in terminal columns rather than bytes, so wide (CJK)
+//! and combining characters keep the borders aligned. Numeric columns are
+//! right-aligned and text is left-aligned; headers follow their column.
+
+use std::borrow::Cow;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// How the cells of a column are aligned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnKind {
+    /// Left-aligned
+    Text,
+    /// Right-aligned, and grouped by `TableFormatOptions::thousands_separators`
+    Integer,
+    /// Right-aligned
+    Number,
+}
+
+impl ColumnKind {
+    /// Kind of a column holding `cells`, NULLs left out
+    ///
+    /// Numeric only if there is at least one cell and every cell parses as
+    /// a number.
+    pub fn detect<'a>(cells: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut kind = None;
+        for cell in cells {
+            let cell_kind = if is_integer(cell) {
+                ColumnKind::Integer
+            } else if is_number(cell) {
+                ColumnKind::Number
+            } else {
+                return ColumnKind::Text;
+            };
+            kind = Some(match kind {
+                Some(ColumnKind::Number) => ColumnKind::Number,
+                _ => cell_kind,
+            });
+        }
+        kind.unwrap_or(ColumnKind::Text)
+    }
+
+    fn is_numeric(self) -> bool {
+        self != ColumnKind::Text
+    }
+}
+
+/// Width limits and number layout for `format_table_with_options`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableFormatOptions {
+    /// Longest a column may be, in terminal columns; longer cells are cut
+    pub max_col_width: Option<usize>,
+    /// Longest a line may be, borders included; columns shrink in proportion
+    /// to their width until the table fits
+    pub max_total_width: Option<usize>,
+    /// Appended to cut cells
+    pub ellipsis: String,
+    /// Group the digits of integer columns in threes, as in `1,234,567`
+    pub thousands_separators: bool,
+}
+
+impl Default for TableFormatOptions {
+    fn default() -> Self {
+        Self {
+            max_col_width: None,
+            max_total_width: None,
+            ellipsis: "…".to_string(),
+            thousands_separators: false,
+        }
+    }
+}
+
+/// Format rows as an ASCII table, without width limits
+pub fn format_table(columns: &[String], rows: &[Vec<String>]) -> String {
+    format_table_with_options(columns, rows, &TableFormatOptions::default())
+}
+
+/// Format rows as an ASCII table, cutting cells to the widths in `options`
+///
+/// Columns whose cells all parse as numbers are right-aligned; cells
+/// spelled `NULL` do not count. Callers that know the column types should
+/// use `format_table_with_kinds` instead.
+pub fn format_table_with_options(
+    columns: &[String],
+    rows: &[Vec<String>],
+    options: &TableFormatOptions,
+) -> String {
+    let kinds: Vec<ColumnKind> = (0..columns.len())
+        .map(|i| {
+            ColumnKind::detect(
+                rows.iter()
+                    .filter_map(|row| row.get(i))
+                    .map(String::as_str)
+                    .filter(|cell| *cell != "NULL"),
+            )
+        })
+        .collect();
+    format_table_with_kinds(columns, rows, &kinds, options)
+}
+
+/// Format rows as an ASCII table, aligning column `i` as `kinds[i]`
+///
+/// Columns without a kind are left-aligned. With `thousands_separators`,
+/// only cells of `Integer` columns that are integers get separators, so
+/// NULL markers pass through unchanged.
+pub fn format_table_with_kinds(
+    columns: &[String],
+    rows: &[Vec<String>],
+    kinds: &[ColumnKind],
+    options: &TableFormatOptions,
+) -> String {
+    if columns.is_empty() {
+        return String::new();
+    }
+    let kinds: Vec<ColumnKind> = (0..columns.len())
+        .map(|i| kinds.get(i).copied().unwrap_or(ColumnKind::Text))
+        .collect();
+    let rows: Vec<Vec<Cow<str>>> = rows
+        .iter()
+        .map(|row| {
+            row.iter()
+                .zip(&kinds)
+                .map(|(cell, kind)| {
+                    if options.thousands_separators
+                        && *kind == ColumnKind::Integer
+                        && is_integer(cell)
+                    {
+                        Cow::Owned(group_thousands(cell))
+                    } else {
+                        Cow::Borrowed(cell.as_str())
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    // Calculate column widths
+    let mut widths: Vec<usize> = columns.iter().map(|c| c.width()).collect();
+    for row in &rows {
+        for (i, cell) in row.iter().enumerate() {
+            if i < widths.len() {
+                widths[i] = widths[i].max(cell.width());
+            }
+        }
+    }
+    let min_width = options.ellipsis.width().max(1);
+    if let Some(max) = options.max_col_width {
+        let max = max.max(min_width);
+        for width in &mut widths {
+            *width = (*width).min(max);
+        }
+    }
+    if let Some(max_total) = options.max_total_width {
+        shrink_to_fit(&mut widths, max_total, min_width);
+    }
+
+    let mut output = String::new();
+
+    // Header separator
+    let separator: String = widths
+        .iter()
+        .map(|w| "-".repeat(*w + 2))
+        .collect::<Vec<_>>()
+        .join("+");
+    let separator = format!("+{}+\n", separator);
+
+    output.push_str(&separator);
+    let aligns: Vec<Align> = kinds
+        .iter()
+        .map(|kind| {
+            if kind.is_numeric() {
+                Align::Right
+            } else {
+                Align::Left
+            }
+        })
+        .collect();
+    let header: Vec<(&str, Align)> = columns
+        .iter()
+        .zip(&aligns)
+        .map(|(c, &align)| (c.as_str(), align))
+        .collect();
+    output.push_str(&format_line(&header, &widths, &options.ellipsis));
+    output.push_str(&separator);
+
+    // Data rows
+    for row in &rows {
+        let cells: Vec<(&str, Align)> = row
+            .iter()
+            .zip(&aligns)
+            .map(|(cell, &align)| (cell.as_ref(), align))
+            .collect();
+        output.push_str(&format_line(&cells, &widths, &options.ellipsis));
+    }
+
+    output.push_str(&separator);
+
+    output
+}
+
+/// Placement of a cell within its column
+#[derive(Clone, Copy)]
+enum Align {
+    Left,
+    Right,
+}
+
+/// One `| a | b |` line, each cell cut and padded to its column width
+fn format_line(cells: &[(&str, Align)], widths: &[usize], ellipsis: &str) -> String {
+    let line: String = cells
+        .iter()
+        .zip(widths)
+        .map(|(&(cell, align), &width)| {
+            let cell = truncate(cell, width, ellipsis);
+            let padding = width.saturating_sub(cell.width());
+            let left = match align {
+                Align::Left => 0,
+                Align::Right => padding,
+            };
+            format!(
+                " {}{}{} ",
+                " ".repeat(left),
+                cell,
+                " ".repeat(padding - left)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("|");
+    format!("|{}|\n", line)
+}
+
+/// `cell` if it fits in `width` columns, else its longest prefix that fits
+/// with `ellipsis` appended
+fn truncate(cell: &str, width: usize, ellipsis: &str) -> String {
+    if cell.width() <= width {
+        return cell.to_string();
+    }
+    let budget = width.saturating_sub(ellipsis.width());
+    let mut used = 0;
+    let mut cut = String::new();
+    for c in cell.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w > budget {
+            break;
+        }
+        used += w;
+        cut.push(c);
+    }
+    cut.push_str(ellipsis);
+    cut
+}
+
+/// `-?[0-9]+`, with an optional sign
+fn is_integer(cell: &str) -> bool {
+    let digits = cell.strip_prefix(['-', '+']).unwrap_or(cell);
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// A decimal or scientific number, e.g. `-12.50` or `1e-3`
+///
+/// Unlike `f64::from_str`, words such as `inf` and `NaN` are text.
+fn is_number(cell: &str) -> bool {
+    cell.bytes().any(|b| b.is_ascii_digit())
+        && cell
+            .bytes()
+            .all(|b| b.is_ascii_digit() || matches!(b, b'-' | b'+' | b'.' | b'e' | b'E'))
+        && cell.parse::<f64>().is_ok()
+}
+
+/// `1234567` as `1,234,567`; `cell` must satisfy `is_integer`
+fn group_thousands(cell: &str) -> String {
+    let (sign, digits) = match cell.strip_prefix(['-', '+']) {
+        Some(digits) => (&cell[..1], digits),
+        None => ("", cell),
+    };
+    let mut grouped = String::with_capacity(cell.len() + digits.len() / 3);
+    grouped.push_str(sign);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    grouped
+}
+
+/// Scale `widths` down so a line, borders included, is at most `max_total`
+///
+/// Each column keeps at least `min_width`, so very narrow limits can still
+/// overflow.
+fn shrink_to_fit(widths: &mut [usize], max_total: usize, min_width: usize) {
+    // "| " before each cell, " " after it and the closing "|"
+    let borders = 3 * widths.len() + 1;
+    let content: usize = widths.iter().sum();
+    let available = max_total.saturating_sub(borders);
+    if content <= available {
+        return;
+    }
+    for width in widths.iter_mut() {
+        *width = (*width * available / content).max(min_width.min(*width));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn test_format_table() {
+        let columns = strings(&["id", "name"]);
+        let rows = vec![strings(&["1", "Alice"]), strings(&["2", "Bob"])];
+        let table = format_table(&columns, &rows);
+        assert!(table.contains("id"));
+        assert!(table.contains("Alice"));
+    }
+
+    #[test]
+    fn test_max_col_width_truncates_cells() {
+        let columns = strings(&["id", "comment"]);
+        let rows = vec![strings(&["1", "a rather long comment"])];
+        let options = TableFormatOptions {
+            max_col_width: Some(8),
+            ..TableFormatOptions::default()
+        };
+        let table = format_table_with_options(&columns, &rows, &options);
+        assert_eq!(
+            table,
+            "+----+----------+\n\
+             | id | comment  |\n\
+             +----+----------+\n\
+             |  1 | a rathe… |\n\
+             +----+----------+\n"
+        );
+    }
+
+    #[test]
+    fn test_mixed_types_alignment() {
+        let columns = strings(&["nation", "orders", "revenue", "share"]);
+        let rows = vec![
+            strings(&["CHINA", "1520", "4172806.25", "0.5"]),
+            strings(&["UNITED STATES", "87", "91.5", "NULL"]),
+            strings(&["PERU", "-3", "-12", "1e-3"]),
+        ];
+        let table = format_table(&columns, &rows);
+        assert_eq!(
+            table,
+            "+---------------+--------+------------+-------+\n\
+             | nation        | orders |    revenue | share |\n\
+             +---------------+--------+------------+-------+\n\
+             | CHINA         |   1520 | 4172806.25 |   0.5 |\n\
+             | UNITED STATES |     87 |       91.5 |  NULL |\n\
+             | PERU          |     -3 |        -12 |  1e-3 |\n\
+             +---------------+--------+------------+-------+\n"
+        );
+    }
+
+    #[test]
+    fn test_thousands_separators() {
+        let columns = strings(&["id", "amount", "code"]);
+        let rows = vec![
+            strings(&["1234567", "1234.5", "0042"]),
+            strings(&["-1000", "999", "x1"]),
+            strings(&["null", "7", "12"]),
+        ];
+        let kinds = [ColumnKind::Integer, ColumnKind::Number, ColumnKind::Text];
+        let options = TableFormatOptions {
+            thousands_separators: true,
+            ..TableFormatOptions::default()
+        };
+        let table = format_table_with_kinds(&columns, &rows, &kinds, &options);
+        assert_eq!(
+            table,
+            "+-----------+--------+------+\n\
+             |        id | amount | code |\n\
+             +-----------+--------+------+\n\
+             | 1,234,567 | 1234.5 | 0042 |\n\
+             |    -1,000 |    999 | x1   |\n\
+             |      null |      7 | 12   |\n\
+             +-----------+--------+------+\n"
+        );
+    }
+
+    #[test]
+    fn test_column_kind_detect() {
+        assert_eq!(ColumnKind::detect(["1", "-2", "+3"]), ColumnKind::Integer);
+        assert_eq!(ColumnKind::detect(["1", "2.5"]), ColumnKind::Number);
+        assert_eq!(ColumnKind::detect(["1", "two"]), ColumnKind::Text);
+        assert_eq!(ColumnKind::detect(["inf", "NaN"]), ColumnKind::Text);
+        assert_eq!(ColumnKind::detect(["-", "."]), ColumnKind::Text);
+        assert_eq!(ColumnKind::detect([]), ColumnKind::Text);
+    }
+
+    #[test]
+    fn test_wide_characters_keep_borders_aligned() {
+        let columns = strings(&["name"]);
+        let rows = vec![strings(&["東京都"]), strings(&["Paris"])];
+        let table = format_table(&columns, &rows);
+        let widths: Vec<usize> = table.lines().map(|line| line.width()).collect();
+        assert!(widths.iter().all(|&w| w == widths[0]), "{}", table);
+        assert!(table.contains("| 東京都 |"));
+
+        // A cut never splits a double-width character
+        let options = TableFormatOptions {
+            max_col_width: Some(4),
+            ..TableFormatOptions::default()
+        };
+        let table = format_table_with_options(&columns, &rows, &options);
+        assert!(table.contains("| 東…  |"), "{}", table);
+    }
+
+    #[test]
+    fn test_max_total_width_shrinks_columns() {
+        let columns = strings(&["a", "b"]);
+        let rows = vec![strings(&[&"x".repeat(40), &"y".repeat(20)])];
+        let options = TableFormatOptions {
+            max_total_width: Some(37),
+            ellipsis: "...".to_string(),
+            ..TableFormatOptions::default()
+        };
+        let table = format_table_with_options(&columns, &rows, &options);
+        for line in table.lines() {
+            assert!(line.width() <= 37, "{}", line);
+        }
+        // Columns keep their 2:1 proportion
+        assert!(
+            table.contains(&format!("| {}... |", "x".repeat(17))),
+            "{}",
+            table
+        );
+        assert!(
+            table.contains(&format!("| {}... |", "y".repeat(7))),
+            "{}",
+            table
+        );
+    }
+}
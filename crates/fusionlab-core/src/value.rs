@@ -0,0 +1,570 @@
+//! Typed cell values for MySQL query results
+//!
+//! The text protocol returns every value as bytes, so cells are decoded using
+//! the column metadata into a `CellValue` that keeps numbers as numbers.
+//! Text is decoded from the column's character set, which follows the
+//! connection's `SET NAMES`.
+
+use encoding_rs::Encoding;
+use mysql_async::{Column, Value};
+use mysql_common::collations::{Collation, CollationId};
+use serde::{Serialize, Serializer};
+
+use crate::ColumnType;
+
+/// A single typed value from a MySQL result row
+#[derive(Debug, Clone, PartialEq)]
+pub enum CellValue {
+    Null,
+    Int(i64),
+    UInt(u64),
+    Float(f32),
+    Double(f64),
+    /// Raw bytes of a binary column (BINARY, VARBINARY, BLOB, BIT,
+    /// GEOMETRY); displayed as `0x` followed by lowercase hex
+    Bytes(Vec<u8>),
+    Str(String),
+    Date {
+        year: u16,
+        month: u8,
+        day: u8,
+    },
+    DateTime {
+        year: u16,
+        month: u8,
+        day: u8,
+        hour: u8,
+        minute: u8,
+        second: u8,
+        micros: u32,
+    },
+    Time {
+        negative: bool,
+        days: u32,
+        hours: u8,
+        minutes: u8,
+        seconds: u8,
+        micros: u32,
+    },
+    /// Exact decimal, kept as the server's textual representation
+    Decimal(String),
+}
+
+impl CellValue {
+    /// Decode a MySQL value using its column metadata
+    pub fn from_mysql(value: Value, column: &Column) -> Self {
+        Self::decode(value, column).0
+    }
+
+    /// Like `from_mysql`, also telling whether the value was text with bytes
+    /// invalid in its column's character set, decoded with replacement
+    /// characters
+    pub(crate) fn decode(value: Value, column: &Column) -> (Self, bool) {
+        let col_type = ColumnType::from_column(column);
+        let cell = match value {
+            Value::NULL => CellValue::Null,
+            // Keep unsigned columns uniformly `UInt`
+            Value::Int(i) if col_type == ColumnType::UInt && i >= 0 => CellValue::UInt(i as u64),
+            Value::Int(i) => CellValue::Int(i),
+            Value::UInt(u) => CellValue::UInt(u),
+            Value::Float(f) => CellValue::Float(f),
+            Value::Double(d) => CellValue::Double(d),
+            Value::Date(year, month, day, hour, minute, second, micros) => match col_type {
+                ColumnType::Date => CellValue::Date { year, month, day },
+                _ => CellValue::DateTime {
+                    year,
+                    month,
+                    day,
+                    hour,
+                    minute,
+                    second,
+                    micros,
+                },
+            },
+            Value::Time(negative, days, hours, minutes, seconds, micros) => CellValue::Time {
+                negative,
+                days,
+                hours,
+                minutes,
+                seconds,
+                micros,
+            },
+            Value::Bytes(bytes) => return decode_text(bytes, col_type, column.character_set()),
+        };
+        (cell, false)
+    }
+
+    /// Whether this is SQL NULL
+    pub fn is_null(&self) -> bool {
+        matches!(self, CellValue::Null)
+    }
+
+    /// Format the value, showing SQL NULL as `null_marker`
+    pub fn display_with_null(&self, null_marker: &str) -> String {
+        match self {
+            CellValue::Null => null_marker.to_string(),
+            other => other.format_value(),
+        }
+    }
+
+    /// Format the value as a display string
+    pub fn format_value(&self) -> String {
+        match self {
+            CellValue::Null => "NULL".to_string(),
+            CellValue::Int(i) => i.to_string(),
+            CellValue::UInt(u) => u.to_string(),
+            CellValue::Float(f) => f.to_string(),
+            CellValue::Double(d) => d.to_string(),
+            CellValue::Bytes(b) => {
+                let hex: String = b.iter().map(|byte| format!("{:02x}", byte)).collect();
+                format!("0x{}", hex)
+            }
+            CellValue::Str(s) | CellValue::Decimal(s) => s.clone(),
+            CellValue::Date { year, month, day } => {
+                format!("{:04}-{:02}-{:02}", year, month, day)
+            }
+            CellValue::DateTime {
+                year,
+                month,
+                day,
+                hour,
+                minute,
+                second,
+                micros,
+            } => format!(
+                "{:04}-{:02}-{:02} {:02}:{:02}:{:02}{}",
+                year,
+                month,
+                day,
+                hour,
+                minute,
+                second,
+                format_micros(*micros)
+            ),
+            CellValue::Time {
+                negative,
+                days,
+                hours,
+                minutes,
+                seconds,
+                micros,
+            } => {
+                // MySQL pads hours to two digits, so "-00:30:00" keeps its sign
+                let sign = if *negative { "-" } else { "" };
+                format!(
+                    "{}{:02}:{:02}:{:02}{}",
+                    sign,
+                    days * 24 + (*hours as u32),
+                    minutes,
+                    seconds,
+                    format_micros(*micros)
+                )
+            }
+        }
+    }
+}
+
+/// Numbers serialize as JSON numbers and NULL as `null`; every other value,
+/// including DECIMAL so no digits are lost, as its `format_value` string
+impl Serialize for CellValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            CellValue::Null => serializer.serialize_none(),
+            CellValue::Int(i) => serializer.serialize_i64(*i),
+            CellValue::UInt(u) => serializer.serialize_u64(*u),
+            CellValue::Float(f) => serializer.serialize_f32(*f),
+            CellValue::Double(d) => serializer.serialize_f64(*d),
+            other => serializer.serialize_str(&other.format_value()),
+        }
+    }
+}
+
+/// Decode a text-protocol value according to its column type
+///
+/// Binary columns keep their bytes even when they happen to be valid UTF-8.
+/// Text is decoded from the character set of collation `charset_id`; invalid
+/// bytes become U+FFFD, which the returned flag reports. Anything that fails
+/// to parse as its column type is kept as a string.
+fn decode_text(bytes: Vec<u8>, col_type: ColumnType, charset_id: u16) -> (CellValue, bool) {
+    if matches!(
+        col_type,
+        ColumnType::Blob | ColumnType::Bit | ColumnType::Geometry
+    ) {
+        return (CellValue::Bytes(bytes), false);
+    }
+    let (text, lossy) = match charset_encoding(charset_id) {
+        Some(encoding) => {
+            let (text, had_errors) = encoding.decode_without_bom_handling(&bytes);
+            (text.into_owned(), had_errors)
+        }
+        None => match String::from_utf8(bytes) {
+            Ok(text) => (text, false),
+            Err(e) => (String::from_utf8_lossy(e.as_bytes()).into_owned(), true),
+        },
+    };
+
+    let parsed = match col_type {
+        ColumnType::Int | ColumnType::Year => text
+            .parse()
+            .map(CellValue::Int)
+            .or_else(|_| text.parse().map(CellValue::UInt))
+            .ok(),
+        ColumnType::UInt => text.parse().map(CellValue::UInt).ok(),
+        ColumnType::Float => text.parse().map(CellValue::Float).ok(),
+        ColumnType::Double => text.parse().map(CellValue::Double).ok(),
+        ColumnType::Decimal => Some(CellValue::Decimal(text.clone())),
+        ColumnType::Date => parse_date(&text),
+        ColumnType::DateTime | ColumnType::Timestamp => parse_datetime(&text),
+        ColumnType::Time => parse_time(&text),
+        _ => None,
+    };
+
+    (parsed.unwrap_or(CellValue::Str(text)), lossy)
+}
+
+/// Parse `YYYY-MM-DD`
+pub(crate) fn parse_date(text: &str) -> Option<CellValue> {
+    let mut parts = text.splitn(3, '-');
+    Some(CellValue::Date {
+        year: parts.next()?.parse().ok()?,
+        month: parts.next()?.parse().ok()?,
+        day: parts.next()?.parse().ok()?,
+    })
+}
+
+/// Parse `YYYY-MM-DD HH:MM:SS[.ffffff]`
+pub(crate) fn parse_datetime(text: &str) -> Option<CellValue> {
+    let (date, time) = text.split_once(' ')?;
+    let CellValue::Date { year, month, day } = parse_date(date)? else {
+        return None;
+    };
+    let (hms, micros) = split_micros(time)?;
+    let mut parts = hms.splitn(3, ':');
+    Some(CellValue::DateTime {
+        year,
+        month,
+        day,
+        hour: parts.next()?.parse().ok()?,
+        minute: parts.next()?.parse().ok()?,
+        second: parts.next()?.parse().ok()?,
+        micros,
+    })
+}
+
+/// Parse `[-]HHH:MM:SS[.ffffff]`
+pub(crate) fn parse_time(text: &str) -> Option<CellValue> {
+    let (negative, rest) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let (hms, micros) = split_micros(rest)?;
+    let mut parts = hms.splitn(3, ':');
+    let total_hours: u32 = parts.next()?.parse().ok()?;
+    Some(CellValue::Time {
+        negative,
+        days: total_hours / 24,
+        hours: (total_hours % 24) as u8,
+        minutes: parts.next()?.parse().ok()?,
+        seconds: parts.next()?.parse().ok()?,
+        micros,
+    })
+}
+
+/// Split `HH:MM:SS.ffffff` into the time part and microseconds
+fn split_micros(text: &str) -> Option<(&str, u32)> {
+    match text.split_once('.') {
+        Some((hms, frac)) => {
+            // Right-pad to 6 digits: ".5" means 500000 microseconds
+            let digits = format!("{:0<6}", frac);
+            Some((hms, digits.get(..6)?.parse().ok()?))
+        }
+        None => Some((text, 0)),
+    }
+}
+
+/// Fractional seconds suffix, omitted when zero
+fn format_micros(micros: u32) -> String {
+    if micros == 0 {
+        String::new()
+    } else {
+        format!(".{:06}", micros)
+    }
+}
+
+/// Encoding of a non-UTF-8 MySQL character set, by collation id
+///
+/// `None` for the UTF-8 character sets and ASCII, and for character sets
+/// without a decoder here (e.g. `ucs2`, `utf32`), which are read as UTF-8.
+/// MySQL's `latin1` is Windows-1252 rather than ISO 8859-1.
+fn charset_encoding(collation_id: u16) -> Option<&'static Encoding> {
+    let collation = Collation::from(CollationId::from(collation_id));
+    let encoding = match collation.charset() {
+        "latin1" => encoding_rs::WINDOWS_1252,
+        "latin2" => encoding_rs::ISO_8859_2,
+        "latin5" => encoding_rs::WINDOWS_1254,
+        "latin7" => encoding_rs::ISO_8859_13,
+        "cp1250" => encoding_rs::WINDOWS_1250,
+        "cp1251" => encoding_rs::WINDOWS_1251,
+        "cp1256" => encoding_rs::WINDOWS_1256,
+        "cp1257" => encoding_rs::WINDOWS_1257,
+        "cp866" => encoding_rs::IBM866,
+        "greek" => encoding_rs::ISO_8859_7,
+        "hebrew" => encoding_rs::ISO_8859_8,
+        "koi8r" => encoding_rs::KOI8_R,
+        "koi8u" => encoding_rs::KOI8_U,
+        "sjis" | "cp932" => encoding_rs::SHIFT_JIS,
+        "ujis" | "eucjpms" => encoding_rs::EUC_JP,
+        "euckr" => encoding_rs::EUC_KR,
+        "gb2312" | "gbk" => encoding_rs::GBK,
+        "gb18030" => encoding_rs::GB18030,
+        "big5" => encoding_rs::BIG5,
+        "utf16" => encoding_rs::UTF_16BE,
+        "utf16le" => encoding_rs::UTF_16LE,
+        _ => return None,
+    };
+    Some(encoding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mysql_async::consts::ColumnType as MySqlColumnType;
+
+    fn column(t: MySqlColumnType) -> Column {
+        Column::new(t)
+    }
+
+    #[test]
+    fn test_decode_text_protocol() {
+        let int = column(MySqlColumnType::MYSQL_TYPE_LONGLONG);
+        assert_eq!(
+            CellValue::from_mysql(Value::Bytes(b"-42".to_vec()), &int),
+            CellValue::Int(-42)
+        );
+
+        let double = column(MySqlColumnType::MYSQL_TYPE_DOUBLE);
+        assert_eq!(
+            CellValue::from_mysql(Value::Bytes(b"0.5".to_vec()), &double),
+            CellValue::Double(0.5)
+        );
+
+        let decimal = column(MySqlColumnType::MYSQL_TYPE_NEWDECIMAL);
+        assert_eq!(
+            CellValue::from_mysql(Value::Bytes(b"12.30".to_vec()), &decimal),
+            CellValue::Decimal("12.30".to_string())
+        );
+
+        let text = column(MySqlColumnType::MYSQL_TYPE_VAR_STRING);
+        assert_eq!(
+            CellValue::from_mysql(Value::Bytes(b"NULL".to_vec()), &text),
+            CellValue::Str("NULL".to_string())
+        );
+        assert_eq!(CellValue::from_mysql(Value::NULL, &text), CellValue::Null);
+    }
+
+    #[test]
+    fn test_decode_unsigned() {
+        use mysql_async::consts::ColumnFlags;
+
+        let unsigned = column(MySqlColumnType::MYSQL_TYPE_LONGLONG)
+            .with_flags(ColumnFlags::UNSIGNED_FLAG | ColumnFlags::NOT_NULL_FLAG);
+        assert_eq!(ColumnType::from_column(&unsigned), ColumnType::UInt);
+        // Above i64::MAX, from the text protocol
+        assert_eq!(
+            CellValue::from_mysql(Value::Bytes(b"18446744073709551615".to_vec()), &unsigned),
+            CellValue::UInt(u64::MAX)
+        );
+        assert_eq!(
+            CellValue::from_mysql(Value::Bytes(b"7".to_vec()), &unsigned),
+            CellValue::UInt(7)
+        );
+        assert_eq!(
+            CellValue::from_mysql(Value::Int(7), &unsigned),
+            CellValue::UInt(7)
+        );
+
+        let signed = column(MySqlColumnType::MYSQL_TYPE_LONGLONG);
+        assert_eq!(ColumnType::from_column(&signed), ColumnType::Int);
+        assert_eq!(
+            CellValue::from_mysql(Value::Bytes(b"7".to_vec()), &signed),
+            CellValue::Int(7)
+        );
+
+        // MySQL flags YEAR as unsigned too
+        let year = column(MySqlColumnType::MYSQL_TYPE_YEAR).with_flags(ColumnFlags::UNSIGNED_FLAG);
+        assert_eq!(ColumnType::from_column(&year), ColumnType::Year);
+        assert_eq!(
+            CellValue::from_mysql(Value::Bytes(b"1997".to_vec()), &year),
+            CellValue::Int(1997)
+        );
+    }
+
+    #[test]
+    fn test_decode_binary_columns_as_hex() {
+        let varbinary = column(MySqlColumnType::MYSQL_TYPE_VAR_STRING)
+            .with_character_set(crate::result::BINARY_CHARSET);
+        // Binary data stays binary even when it is valid UTF-8
+        let cell = CellValue::from_mysql(Value::Bytes(b"ab".to_vec()), &varbinary);
+        assert_eq!(cell, CellValue::Bytes(b"ab".to_vec()));
+        assert_eq!(cell.format_value(), "0x6162");
+        let cell = CellValue::from_mysql(Value::Bytes(vec![0x00, 0xff, 0x0a]), &varbinary);
+        assert_eq!(cell.format_value(), "0x00ff0a");
+        assert_eq!(
+            CellValue::from_mysql(Value::Bytes(vec![]), &varbinary).format_value(),
+            "0x"
+        );
+
+        // Text columns keep UTF-8 text
+        let text = column(MySqlColumnType::MYSQL_TYPE_BLOB).with_character_set(255);
+        assert_eq!(
+            CellValue::from_mysql(Value::Bytes("héllo".as_bytes().to_vec()), &text),
+            CellValue::Str("héllo".to_string())
+        );
+    }
+
+    #[test]
+    fn test_decode_text_charsets() {
+        const LATIN1_SWEDISH_CI: u16 = 8;
+        const CP1251_GENERAL_CI: u16 = 51;
+        const UTF8MB4_0900_AI_CI: u16 = 255;
+
+        let latin1 =
+            column(MySqlColumnType::MYSQL_TYPE_VAR_STRING).with_character_set(LATIN1_SWEDISH_CI);
+        assert_eq!(
+            CellValue::from_mysql(Value::Bytes(vec![b'c', b'a', b'f', 0xe9]), &latin1),
+            CellValue::Str("café".to_string())
+        );
+        // Windows-1252, like MySQL: 0x80 is the euro sign
+        assert_eq!(
+            CellValue::from_mysql(Value::Bytes(vec![0x80]), &latin1),
+            CellValue::Str("€".to_string())
+        );
+
+        let cp1251 =
+            column(MySqlColumnType::MYSQL_TYPE_VAR_STRING).with_character_set(CP1251_GENERAL_CI);
+        assert_eq!(
+            CellValue::from_mysql(Value::Bytes(vec![0xc4, 0xe0]), &cp1251),
+            CellValue::Str("Да".to_string())
+        );
+
+        // Invalid UTF-8 is decoded lossily and reported
+        let utf8 =
+            column(MySqlColumnType::MYSQL_TYPE_VAR_STRING).with_character_set(UTF8MB4_0900_AI_CI);
+        assert_eq!(
+            CellValue::decode(Value::Bytes(vec![b'c', b'a', b'f', 0xe9]), &utf8),
+            (CellValue::Str("caf\u{fffd}".to_string()), true)
+        );
+        assert!(!CellValue::decode(Value::Bytes(b"caf".to_vec()), &utf8).1);
+    }
+
+    #[test]
+    fn test_decode_temporal_round_trips_display() {
+        let cases = [
+            (MySqlColumnType::MYSQL_TYPE_DATE, "2024-01-15"),
+            (MySqlColumnType::MYSQL_TYPE_DATETIME, "2024-01-15 12:34:56"),
+            (
+                MySqlColumnType::MYSQL_TYPE_DATETIME,
+                "2024-01-15 12:34:56.123456",
+            ),
+            (MySqlColumnType::MYSQL_TYPE_TIME, "-838:59:59"),
+            (MySqlColumnType::MYSQL_TYPE_TIME, "12:00:00.500000"),
+        ];
+        for (t, text) in cases {
+            let cell = CellValue::from_mysql(Value::Bytes(text.as_bytes().to_vec()), &column(t));
+            assert!(!matches!(cell, CellValue::Str(_)), "{} not parsed", text);
+            assert_eq!(cell.format_value(), text);
+        }
+    }
+
+    #[test]
+    fn test_format_value_temporal() {
+        let datetime = CellValue::DateTime {
+            year: 2024,
+            month: 1,
+            day: 15,
+            hour: 12,
+            minute: 34,
+            second: 56,
+            micros: 0,
+        };
+        assert_eq!(datetime.format_value(), "2024-01-15 12:34:56");
+        let with_micros = CellValue::DateTime {
+            year: 2024,
+            month: 1,
+            day: 15,
+            hour: 12,
+            minute: 34,
+            second: 56,
+            micros: 123456,
+        };
+        assert_eq!(with_micros.format_value(), "2024-01-15 12:34:56.123456");
+
+        let time = CellValue::Time {
+            negative: false,
+            days: 0,
+            hours: 12,
+            minutes: 34,
+            seconds: 56,
+            micros: 0,
+        };
+        assert_eq!(time.format_value(), "12:34:56");
+    }
+
+    #[test]
+    fn test_format_value_fractional_and_edge_cases() {
+        // DATETIME(6) keeps leading zeros of the fraction
+        let datetime = column(MySqlColumnType::MYSQL_TYPE_DATETIME);
+        assert_eq!(
+            CellValue::from_mysql(Value::Date(2024, 1, 15, 12, 34, 56, 1), &datetime)
+                .format_value(),
+            "2024-01-15 12:34:56.000001"
+        );
+
+        // Negative values under an hour keep their sign and padded hours
+        let time = column(MySqlColumnType::MYSQL_TYPE_TIME);
+        assert_eq!(
+            CellValue::from_mysql(Value::Time(true, 0, 0, 30, 0, 250000), &time).format_value(),
+            "-00:30:00.250000"
+        );
+        assert_eq!(
+            CellValue::from_mysql(Value::Time(true, 34, 22, 59, 59, 0), &time).format_value(),
+            "-838:59:59"
+        );
+
+        // Zero dates decode and format in both protocols
+        assert_eq!(
+            CellValue::from_mysql(Value::Date(0, 0, 0, 0, 0, 0, 0), &datetime).format_value(),
+            "0000-00-00 00:00:00"
+        );
+        for (t, text) in [
+            (MySqlColumnType::MYSQL_TYPE_DATE, "0000-00-00"),
+            (MySqlColumnType::MYSQL_TYPE_DATETIME, "0000-00-00 00:00:00"),
+            (MySqlColumnType::MYSQL_TYPE_TIME, "-00:00:01.000001"),
+            (MySqlColumnType::MYSQL_TYPE_TIME, "00:00:00"),
+        ] {
+            let cell = CellValue::from_mysql(Value::Bytes(text.as_bytes().to_vec()), &column(t));
+            assert!(!matches!(cell, CellValue::Str(_)), "{} not parsed", text);
+            assert_eq!(cell.format_value(), text);
+        }
+    }
+
+    #[test]
+    fn test_binary_protocol_values() {
+        let datetime = column(MySqlColumnType::MYSQL_TYPE_DATETIME);
+        assert_eq!(
+            CellValue::from_mysql(Value::Date(2024, 1, 15, 12, 34, 56, 0), &datetime)
+                .format_value(),
+            "2024-01-15 12:34:56"
+        );
+        let date = column(MySqlColumnType::MYSQL_TYPE_DATE);
+        assert_eq!(
+            CellValue::from_mysql(Value::Date(2024, 1, 15, 0, 0, 0, 0), &date).format_value(),
+            "2024-01-15"
+        );
+        let time = column(MySqlColumnType::MYSQL_TYPE_TIME);
+        assert_eq!(
+            CellValue::from_mysql(Value::Time(false, 1, 2, 3, 4, 1200), &time).format_value(),
+            "26:03:04.001200"
+        );
+    }
+}
@@ -0,0 +1,919 @@
+//! Cell-by-cell diff of two query results
+//!
+//! `diff_results` pairs up the rows of two results and reports which
+//! values differ, with tolerances for floating point noise. Convert
+//! DataFusion results with `DfQueryResult::to_query_result` first, as
+//! `compare_results` does.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::compare::normalize_temporal;
+use crate::{CellValue, QueryResult};
+
+/// How SQL NULL compares to other cells
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NullEquivalence {
+    /// NULL equals NULL and nothing else
+    Strict,
+    /// NULL also equals the empty string, for sources that lose the
+    /// difference (CSV exports)
+    EmptyString,
+    /// NULL equals nothing, not even NULL, as in SQL `=`
+    NeverEqual,
+}
+
+/// Settings for `diff_results`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffOptions {
+    /// Match rows regardless of their position, for queries without
+    /// `ORDER BY`
+    pub ignore_order: bool,
+    /// Non-integer numbers closer than this are equal
+    pub float_abs_tolerance: f64,
+    /// Non-integer numbers whose difference is at most this fraction of the
+    /// larger magnitude are equal
+    pub float_rel_tolerance: f64,
+    /// How NULL compares with other cells
+    pub null_equivalence: NullEquivalence,
+    /// Match column names ignoring ASCII case (`C_NAME` and `c_name`)
+    pub case_insensitive_columns: bool,
+    /// Most mismatched rows kept in `DiffReport::rows`; all are counted
+    pub max_reported_rows: usize,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            ignore_order: false,
+            float_abs_tolerance: 1e-9,
+            float_rel_tolerance: 1e-9,
+            null_equivalence: NullEquivalence::Strict,
+            case_insensitive_columns: true,
+            max_reported_rows: 10,
+        }
+    }
+}
+
+/// Why a row is in a `DiffReport`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowDiffKind {
+    /// The row of `b` has no counterpart in `a`
+    MissingInA,
+    /// The row of `a` has no counterpart in `b`
+    MissingInB,
+    /// Rows at the same position differ in some columns
+    ValueMismatch,
+}
+
+/// One mismatched row, with the values of both sides in the compared
+/// columns (`DiffReport::columns`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowDiff {
+    pub kind: RowDiffKind,
+    /// Index of the row in `a`, if it has one
+    pub a_index: Option<usize>,
+    /// Index of the row in `b`, if it has one
+    pub b_index: Option<usize>,
+    pub a: Option<Vec<String>>,
+    pub b: Option<Vec<String>>,
+    /// Positions in `DiffReport::columns` whose values differ
+    pub differing_columns: Vec<usize>,
+}
+
+/// Outcome of `diff_results`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffReport {
+    /// Columns present in both results, in the order of `a`
+    pub columns: Vec<String>,
+    /// Columns only `a` has; their values are not compared
+    pub only_in_a_columns: Vec<String>,
+    /// Columns only `b` has; their values are not compared
+    pub only_in_b_columns: Vec<String>,
+    pub a_row_count: usize,
+    pub b_row_count: usize,
+    /// Rows of `b` without a counterpart in `a`
+    pub missing_in_a: usize,
+    /// Rows of `a` without a counterpart in `b`
+    pub missing_in_b: usize,
+    /// Row pairs that differ in at least one value (ordered mode only)
+    pub value_mismatches: usize,
+    /// The first `DiffOptions::max_reported_rows` mismatches
+    pub rows: Vec<RowDiff>,
+}
+
+impl DiffReport {
+    /// Whether both results have the same columns and rows
+    pub fn is_match(&self) -> bool {
+        self.only_in_a_columns.is_empty()
+            && self.only_in_b_columns.is_empty()
+            && self.missing_in_a == 0
+            && self.missing_in_b == 0
+            && self.value_mismatches == 0
+    }
+}
+
+impl fmt::Display for DiffReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Rows: {} vs {}; {} missing in a, {} missing in b, {} value mismatches",
+            self.a_row_count,
+            self.b_row_count,
+            self.missing_in_a,
+            self.missing_in_b,
+            self.value_mismatches
+        )?;
+        if !self.only_in_a_columns.is_empty() {
+            writeln!(
+                f,
+                "Columns only in a: {}",
+                self.only_in_a_columns.join(", ")
+            )?;
+        }
+        if !self.only_in_b_columns.is_empty() {
+            writeln!(
+                f,
+                "Columns only in b: {}",
+                self.only_in_b_columns.join(", ")
+            )?;
+        }
+        for row in &self.rows {
+            let position = |index: Option<usize>| {
+                index.map_or_else(|| "-".to_string(), |i| (i + 1).to_string())
+            };
+            let differing: Vec<&str> = row
+                .differing_columns
+                .iter()
+                .map(|&i| self.columns[i].as_str())
+                .collect();
+            match row.kind {
+                RowDiffKind::MissingInA => {
+                    writeln!(f, "b row {} missing in a", position(row.b_index))?
+                }
+                RowDiffKind::MissingInB => {
+                    writeln!(f, "a row {} missing in b", position(row.a_index))?
+                }
+                RowDiffKind::ValueMismatch => writeln!(
+                    f,
+                    "row {}: {} differ",
+                    position(row.a_index),
+                    differing.join(", ")
+                )?,
+            }
+            for (side, values) in [("a", &row.a), ("b", &row.b)] {
+                if let Some(values) = values {
+                    writeln!(f, "  {}: {}", side, values.join(" | "))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Compare the rows of `a` and `b` in the columns both have
+///
+/// Columns are matched by name. In ordered mode row `i` of `a` is compared
+/// with row `i` of `b`; with `ignore_order` rows are matched regardless of
+/// position and unmatched rows are reported as missing. Integers must be
+/// equal; other numbers within the float tolerances; dates and times are
+/// compared after normalizing `T` separators and trailing fractional
+/// zeros; anything else as text. Strings holding a number count as numbers
+/// unless the result's `column_meta` says the column is text.
+pub fn diff_results(a: &QueryResult, b: &QueryResult, opts: DiffOptions) -> DiffReport {
+    let same_name = |x: &str, y: &str| {
+        if opts.case_insensitive_columns {
+            x.eq_ignore_ascii_case(y)
+        } else {
+            x == y
+        }
+    };
+    // (index in a, index in b) of each shared column
+    let pairs: Vec<(usize, usize)> = a
+        .columns
+        .iter()
+        .enumerate()
+        .filter_map(|(i, name)| {
+            let j = b.columns.iter().position(|other| same_name(name, other))?;
+            Some((i, j))
+        })
+        .collect();
+    let only_in_b_columns = b
+        .columns
+        .iter()
+        .enumerate()
+        .filter(|(j, _)| !pairs.iter().any(|(_, pj)| pj == j))
+        .map(|(_, name)| name.clone())
+        .collect();
+
+    let mut diff = Differ {
+        opts: &opts,
+        pairs: &pairs,
+        parse_text: pairs
+            .iter()
+            .map(|&(i, j)| (parses_text(a, i), parses_text(b, j)))
+            .collect(),
+        report: DiffReport {
+            columns: pairs.iter().map(|&(i, _)| a.columns[i].clone()).collect(),
+            only_in_a_columns: a
+                .columns
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| !pairs.iter().any(|(pi, _)| pi == i))
+                .map(|(_, name)| name.clone())
+                .collect(),
+            only_in_b_columns,
+            a_row_count: a.rows.len(),
+            b_row_count: b.rows.len(),
+            missing_in_a: 0,
+            missing_in_b: 0,
+            value_mismatches: 0,
+            rows: Vec::new(),
+        },
+    };
+    if opts.ignore_order {
+        diff.unordered(a, b);
+    } else {
+        diff.ordered(a, b);
+    }
+    diff.report
+}
+
+/// State of one `diff_results` call
+struct Differ<'a> {
+    opts: &'a DiffOptions,
+    pairs: &'a [(usize, usize)],
+    /// `parses_text` of each side, parallel to `pairs`
+    parse_text: Vec<(bool, bool)>,
+    report: DiffReport,
+}
+
+impl Differ<'_> {
+    /// (index, `parses_text`) of the compared columns of `a`
+    fn columns_a(&self) -> impl Iterator<Item = (usize, bool)> + '_ {
+        self.pairs
+            .iter()
+            .zip(&self.parse_text)
+            .map(|(p, t)| (p.0, t.0))
+    }
+
+    /// (index, `parses_text`) of the compared columns of `b`
+    fn columns_b(&self) -> impl Iterator<Item = (usize, bool)> + '_ {
+        self.pairs
+            .iter()
+            .zip(&self.parse_text)
+            .map(|(p, t)| (p.1, t.1))
+    }
+
+    fn ordered(&mut self, a: &QueryResult, b: &QueryResult) {
+        for i in 0..a.rows.len().max(b.rows.len()) {
+            match (a.rows.get(i), b.rows.get(i)) {
+                (Some(row_a), Some(row_b)) => {
+                    let differing = self.differing_columns(row_a, row_b);
+                    if !differing.is_empty() {
+                        self.report.value_mismatches += 1;
+                        self.record(
+                            RowDiffKind::ValueMismatch,
+                            Some((i, row_a)),
+                            Some((i, row_b)),
+                            differing,
+                        );
+                    }
+                }
+                (Some(row_a), None) => {
+                    self.report.missing_in_b += 1;
+                    self.record(RowDiffKind::MissingInB, Some((i, row_a)), None, vec![]);
+                }
+                (None, Some(row_b)) => {
+                    self.report.missing_in_a += 1;
+                    self.record(RowDiffKind::MissingInA, None, Some((i, row_b)), vec![]);
+                }
+                (None, None) => unreachable!("i is below the longer row count"),
+            }
+        }
+    }
+
+    /// Match rows as multisets
+    ///
+    /// Rows of `a` are counted in a map keyed by their normalized cells;
+    /// rows of `b` are streamed past it, each using up one count of an
+    /// equal row, so a row present 3 times in `a` and twice in `b` leaves
+    /// one behind. The leftovers are then sorted and merged, which pairs up
+    /// values that are equal only within tolerance.
+    fn unordered(&mut self, a: &QueryResult, b: &QueryResult) {
+        let mut counts: HashMap<Vec<RowKey>, Vec<usize>> = HashMap::new();
+        let mut rest_a = Vec::new();
+        for (i, row) in a.rows.iter().enumerate() {
+            match self.row_key(row, self.columns_a()) {
+                Some(key) => counts.entry(key).or_default().push(i),
+                None => rest_a.push(i),
+            }
+        }
+        let mut rest_b = Vec::new();
+        for (j, row) in b.rows.iter().enumerate() {
+            let matched = self
+                .row_key(row, self.columns_b())
+                .and_then(|key| counts.get_mut(&key))
+                .and_then(|indices| indices.pop());
+            if matched.is_none() {
+                rest_b.push(j);
+            }
+        }
+        rest_a.extend(counts.into_values().flatten());
+        self.merge(a, b, rest_a, rest_b);
+    }
+
+    /// Sort the remaining rows of both sides by their compared cells and
+    /// merge them
+    ///
+    /// Values within tolerance sort next to each other, so they meet during
+    /// the merge.
+    fn merge(
+        &mut self,
+        a: &QueryResult,
+        b: &QueryResult,
+        mut sorted_a: Vec<usize>,
+        mut sorted_b: Vec<usize>,
+    ) {
+        sorted_a.sort_by(|&x, &y| {
+            compare_rows(&a.rows[x], &a.rows[y], self.columns_a()).then(x.cmp(&y))
+        });
+        sorted_b.sort_by(|&x, &y| {
+            compare_rows(&b.rows[x], &b.rows[y], self.columns_b()).then(x.cmp(&y))
+        });
+
+        let (mut x, mut y) = (0, 0);
+        while x < sorted_a.len() || y < sorted_b.len() {
+            let row_a = sorted_a.get(x).map(|&i| (i, &a.rows[i]));
+            let row_b = sorted_b.get(y).map(|&j| (j, &b.rows[j]));
+            let order = match (row_a, row_b) {
+                (Some((_, ra)), Some((_, rb))) => {
+                    if self.differing_columns(ra, rb).is_empty() {
+                        Ordering::Equal
+                    } else {
+                        // Rows that sort together without being equal, such
+                        // as NULLs under `NeverEqual`, are not a match
+                        compare_cells_by(ra, rb, self.pairs, &self.parse_text).then(Ordering::Less)
+                    }
+                }
+                (Some(_), None) => Ordering::Less,
+                (None, _) => Ordering::Greater,
+            };
+            match order {
+                Ordering::Equal => {
+                    x += 1;
+                    y += 1;
+                }
+                Ordering::Less => {
+                    self.report.missing_in_b += 1;
+                    self.record(RowDiffKind::MissingInB, row_a, None, vec![]);
+                    x += 1;
+                }
+                Ordering::Greater => {
+                    self.report.missing_in_a += 1;
+                    self.record(RowDiffKind::MissingInA, None, row_b, vec![]);
+                    y += 1;
+                }
+            }
+        }
+    }
+
+    /// The compared cells of `row` in a form where equal keys mean equal
+    /// rows, or `None` if the row can only be matched with `cells_equal`
+    fn row_key(
+        &self,
+        row: &[CellValue],
+        columns: impl Iterator<Item = (usize, bool)>,
+    ) -> Option<Vec<RowKey>> {
+        let null = CellValue::Null;
+        columns
+            .map(|(i, parse_text)| {
+                let cell = row.get(i).unwrap_or(&null);
+                if cell.is_null() {
+                    return match self.opts.null_equivalence {
+                        NullEquivalence::Strict => Some(RowKey::Null),
+                        NullEquivalence::EmptyString => Some(RowKey::Text(String::new())),
+                        NullEquivalence::NeverEqual => None,
+                    };
+                }
+                match as_number(cell, parse_text) {
+                    Some(Number::Int(i)) => Some(RowKey::Int(i)),
+                    Some(Number::Float(f)) if f.is_nan() => None,
+                    // 20.0 must meet "20"
+                    Some(Number::Float(f)) if f.fract() == 0.0 && f.abs() < 1e38 => {
+                        Some(RowKey::Int(f as i128))
+                    }
+                    Some(Number::Float(f)) => Some(RowKey::Float(f.to_bits())),
+                    None => Some(RowKey::Text(normalized_text(cell))),
+                }
+            })
+            .collect()
+    }
+
+    /// Positions in `pairs` where the rows differ
+    fn differing_columns(&self, row_a: &[CellValue], row_b: &[CellValue]) -> Vec<usize> {
+        self.pairs
+            .iter()
+            .zip(&self.parse_text)
+            .enumerate()
+            .filter(|(_, (&(i, j), &parse_text))| {
+                let null = CellValue::Null;
+                let x = row_a.get(i).unwrap_or(&null);
+                let y = row_b.get(j).unwrap_or(&null);
+                !cells_equal(x, y, parse_text, self.opts)
+            })
+            .map(|(k, _)| k)
+            .collect()
+    }
+
+    fn record(
+        &mut self,
+        kind: RowDiffKind,
+        row_a: Option<(usize, &Vec<CellValue>)>,
+        row_b: Option<(usize, &Vec<CellValue>)>,
+        differing_columns: Vec<usize>,
+    ) {
+        if self.report.rows.len() >= self.opts.max_reported_rows {
+            return;
+        }
+        let display = |row: &Vec<CellValue>, side: fn(&(usize, usize)) -> usize| -> Vec<String> {
+            self.pairs
+                .iter()
+                .map(|pair| {
+                    row.get(side(pair))
+                        .map_or_else(|| "NULL".to_string(), CellValue::format_value)
+                })
+                .collect()
+        };
+        self.report.rows.push(RowDiff {
+            kind,
+            a_index: row_a.map(|(i, _)| i),
+            b_index: row_b.map(|(j, _)| j),
+            a: row_a.map(|(_, row)| display(row, |p| p.0)),
+            b: row_b.map(|(_, row)| display(row, |p| p.1)),
+            differing_columns,
+        });
+    }
+}
+
+/// One cell of a row as hashed by `Differ::unordered`
+#[derive(Debug, PartialEq, Eq, Hash)]
+enum RowKey {
+    Null,
+    Int(i128),
+    Float(u64),
+    Text(String),
+}
+
+/// Whether two cells hold the same value under `opts`; `parse_text` is
+/// `parses_text` of each cell's column
+fn cells_equal(x: &CellValue, y: &CellValue, parse_text: (bool, bool), opts: &DiffOptions) -> bool {
+    match (x.is_null(), y.is_null()) {
+        (true, true) => return opts.null_equivalence != NullEquivalence::NeverEqual,
+        (true, false) | (false, true) => {
+            let other = if x.is_null() { y } else { x };
+            return opts.null_equivalence == NullEquivalence::EmptyString
+                && other.format_value().is_empty();
+        }
+        (false, false) => {}
+    }
+    match (as_number(x, parse_text.0), as_number(y, parse_text.1)) {
+        (Some(Number::Int(m)), Some(Number::Int(n))) => m == n,
+        (Some(m), Some(n)) => {
+            let (m, n) = (m.to_f64(), n.to_f64());
+            let delta = (m - n).abs();
+            m == n
+                || delta <= opts.float_abs_tolerance
+                || delta <= opts.float_rel_tolerance * m.abs().max(n.abs())
+        }
+        _ => normalized_text(x) == normalized_text(y),
+    }
+}
+
+/// A cell read as a number
+#[derive(Debug, Clone, Copy)]
+enum Number {
+    Int(i128),
+    Float(f64),
+}
+
+impl Number {
+    fn to_f64(self) -> f64 {
+        match self {
+            Number::Int(i) => i as f64,
+            Number::Float(f) => f,
+        }
+    }
+}
+
+/// Whether strings in column `idx` of `result` may be read as numbers
+///
+/// Only results without metadata, such as converted DataFusion results,
+/// need the guess; a MySQL text column keeps `'007'` apart from `'7'`.
+fn parses_text(result: &QueryResult, idx: usize) -> bool {
+    result
+        .column_meta
+        .get(idx)
+        .map_or(true, |meta| meta.column_type.is_numeric())
+}
+
+/// The numeric value of number cells, and of strings holding a number if
+/// `parse_text` is set
+fn as_number(cell: &CellValue, parse_text: bool) -> Option<Number> {
+    match cell {
+        CellValue::Int(i) => Some(Number::Int(*i as i128)),
+        CellValue::UInt(u) => Some(Number::Int(*u as i128)),
+        CellValue::Float(f) => Some(Number::Float(*f as f64)),
+        CellValue::Double(d) => Some(Number::Float(*d)),
+        CellValue::Str(_) if !parse_text => None,
+        CellValue::Str(s) | CellValue::Decimal(s) => {
+            let s = s.trim();
+            if let Ok(i) = s.parse::<i128>() {
+                Some(Number::Int(i))
+            } else {
+                s.parse::<f64>()
+                    .ok()
+                    .filter(|f| f.is_finite())
+                    .map(Number::Float)
+            }
+        }
+        _ => None,
+    }
+}
+
+fn normalized_text(cell: &CellValue) -> String {
+    let text = cell.format_value();
+    normalize_temporal(&text).unwrap_or(text)
+}
+
+/// Total order on rows for `Differ::unordered`: NULL first, then numbers by
+/// value, then text
+fn compare_rows(
+    x: &[CellValue],
+    y: &[CellValue],
+    columns: impl Iterator<Item = (usize, bool)>,
+) -> Ordering {
+    let null = CellValue::Null;
+    for (i, parse_text) in columns {
+        let order = compare_cells(
+            x.get(i).unwrap_or(&null),
+            y.get(i).unwrap_or(&null),
+            (parse_text, parse_text),
+        );
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+    Ordering::Equal
+}
+
+/// `compare_rows` across the sides, using each side's column positions
+fn compare_cells_by(
+    row_a: &[CellValue],
+    row_b: &[CellValue],
+    pairs: &[(usize, usize)],
+    parse_text: &[(bool, bool)],
+) -> Ordering {
+    let null = CellValue::Null;
+    for (&(i, j), &parse_text) in pairs.iter().zip(parse_text) {
+        let order = compare_cells(
+            row_a.get(i).unwrap_or(&null),
+            row_b.get(j).unwrap_or(&null),
+            parse_text,
+        );
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+    Ordering::Equal
+}
+
+fn compare_cells(x: &CellValue, y: &CellValue, parse_text: (bool, bool)) -> Ordering {
+    match (x.is_null(), y.is_null()) {
+        (true, true) => return Ordering::Equal,
+        (true, false) => return Ordering::Less,
+        (false, true) => return Ordering::Greater,
+        (false, false) => {}
+    }
+    match (as_number(x, parse_text.0), as_number(y, parse_text.1)) {
+        (Some(Number::Int(m)), Some(Number::Int(n))) => m.cmp(&n),
+        (Some(m), Some(n)) => m.to_f64().total_cmp(&n.to_f64()),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => normalized_text(x).cmp(&normalized_text(y)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ColumnMeta, ColumnType};
+
+    fn result(columns: &[&str], rows: Vec<Vec<CellValue>>) -> QueryResult {
+        QueryResult {
+            row_count: rows.len(),
+            rows,
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            column_types: vec![ColumnType::Unknown; columns.len()],
+            ..QueryResult::default()
+        }
+    }
+
+    fn text(s: &str) -> CellValue {
+        CellValue::Str(s.to_string())
+    }
+
+    #[test]
+    fn test_identical_results_match() {
+        let a = result(
+            &["id", "name"],
+            vec![
+                vec![CellValue::Int(1), text("a")],
+                vec![CellValue::Int(2), CellValue::Null],
+            ],
+        );
+        let report = diff_results(&a, &a.clone(), DiffOptions::default());
+        assert!(report.is_match(), "{}", report);
+        assert!(report.rows.is_empty());
+    }
+
+    #[test]
+    fn test_ordered_value_mismatch_and_missing_rows() {
+        let a = result(
+            &["id", "revenue"],
+            vec![
+                vec![CellValue::Int(1), CellValue::Double(10.5)],
+                vec![CellValue::Int(2), CellValue::Double(20.0)],
+                vec![CellValue::Int(3), CellValue::Double(30.0)],
+            ],
+        );
+        let b = result(
+            &["id", "revenue"],
+            vec![
+                vec![CellValue::Int(1), CellValue::Decimal("10.50".to_string())],
+                vec![CellValue::Int(2), CellValue::Decimal("20.01".to_string())],
+            ],
+        );
+        let report = diff_results(&a, &b, DiffOptions::default());
+        assert_eq!(report.value_mismatches, 1);
+        assert_eq!(report.missing_in_b, 1);
+        assert_eq!(report.missing_in_a, 0);
+        assert_eq!(
+            report.rows[0],
+            RowDiff {
+                kind: RowDiffKind::ValueMismatch,
+                a_index: Some(1),
+                b_index: Some(1),
+                a: Some(vec!["2".to_string(), "20".to_string()]),
+                b: Some(vec!["2".to_string(), "20.01".to_string()]),
+                differing_columns: vec![1],
+            }
+        );
+        assert_eq!(report.rows[1].kind, RowDiffKind::MissingInB);
+        assert_eq!(report.rows[1].a_index, Some(2));
+
+        // Tolerance absorbs the difference
+        let loose = DiffOptions {
+            float_abs_tolerance: 0.05,
+            ..DiffOptions::default()
+        };
+        assert_eq!(diff_results(&a, &b, loose).value_mismatches, 0);
+    }
+
+    #[test]
+    fn test_float_tolerances() {
+        let opts = DiffOptions {
+            float_abs_tolerance: 0.0,
+            float_rel_tolerance: 1e-6,
+            ..DiffOptions::default()
+        };
+        let eq = |x: f64, y: f64| {
+            cells_equal(
+                &CellValue::Double(x),
+                &CellValue::Double(y),
+                (true, true),
+                &opts,
+            )
+        };
+        assert!(eq(1_000_000.0, 1_000_000.5));
+        assert!(!eq(1.0, 1.5));
+        assert!(eq(0.1 + 0.2, 0.3));
+        // Integers are never approximated
+        assert!(!cells_equal(
+            &CellValue::Int(9_007_199_254_740_993),
+            &CellValue::UInt(9_007_199_254_740_992),
+            (true, true),
+            &opts
+        ));
+    }
+
+    #[test]
+    fn test_null_equivalence() {
+        let empty = text("");
+        let null = CellValue::Null;
+        let opts = |null_equivalence| DiffOptions {
+            null_equivalence,
+            ..DiffOptions::default()
+        };
+        let strict = opts(NullEquivalence::Strict);
+        assert!(cells_equal(&null, &null, (true, true), &strict));
+        assert!(!cells_equal(&null, &empty, (true, true), &strict));
+        assert!(!cells_equal(&null, &text("NULL"), (true, true), &strict));
+
+        let empty_string = opts(NullEquivalence::EmptyString);
+        assert!(cells_equal(&null, &empty, (true, true), &empty_string));
+        assert!(cells_equal(&empty, &null, (true, true), &empty_string));
+
+        assert!(!cells_equal(
+            &null,
+            &null,
+            (true, true),
+            &opts(NullEquivalence::NeverEqual)
+        ));
+    }
+
+    #[test]
+    fn test_column_matching() {
+        let a = result(
+            &["ID", "Name", "extra"],
+            vec![vec![CellValue::Int(1), text("x"), text("e")]],
+        );
+        let b = result(&["name", "id"], vec![vec![text("x"), CellValue::Int(1)]]);
+
+        let report = diff_results(&a, &b, DiffOptions::default());
+        assert_eq!(report.columns, vec!["ID", "Name"]);
+        assert_eq!(report.only_in_a_columns, vec!["extra"]);
+        assert_eq!(report.value_mismatches, 0);
+        assert!(!report.is_match());
+
+        let exact = DiffOptions {
+            case_insensitive_columns: false,
+            ..DiffOptions::default()
+        };
+        let report = diff_results(&a, &b, exact);
+        assert!(report.columns.is_empty());
+        assert_eq!(report.only_in_b_columns, vec!["name", "id"]);
+    }
+
+    #[test]
+    fn test_unordered() {
+        let a = result(
+            &["id", "ts"],
+            vec![
+                vec![CellValue::Int(2), text("2024-01-02 10:00:00")],
+                vec![CellValue::Int(1), text("2024-01-01 00:00:00.500")],
+                vec![CellValue::Int(3), CellValue::Null],
+            ],
+        );
+        let b = result(
+            &["id", "ts"],
+            vec![
+                vec![CellValue::Int(1), text("2024-01-01T00:00:00.5")],
+                vec![CellValue::Int(4), CellValue::Null],
+                vec![CellValue::Int(2), text("2024-01-02T10:00:00")],
+            ],
+        );
+        let ordered = diff_results(&a, &b, DiffOptions::default());
+        assert_eq!(ordered.value_mismatches, 3);
+
+        let opts = DiffOptions {
+            ignore_order: true,
+            ..DiffOptions::default()
+        };
+        let report = diff_results(&a, &b, opts);
+        assert_eq!(report.missing_in_a, 1);
+        assert_eq!(report.missing_in_b, 1);
+        assert_eq!(report.value_mismatches, 0);
+        assert_eq!(
+            report.rows[0].a,
+            Some(vec!["3".to_string(), "NULL".to_string()])
+        );
+        assert_eq!(
+            report.rows[1].b,
+            Some(vec!["4".to_string(), "NULL".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_unordered_duplicate_rows() {
+        let row = || vec![CellValue::Int(7), text("x")];
+        let a = result(
+            &["id", "v"],
+            vec![row(), row(), vec![CellValue::Int(1), text("y")], row()],
+        );
+        let b = result(
+            &["id", "v"],
+            vec![row(), vec![CellValue::Int(1), text("y")], row()],
+        );
+        let opts = DiffOptions {
+            ignore_order: true,
+            ..DiffOptions::default()
+        };
+
+        let report = diff_results(&a, &b, opts.clone());
+        assert_eq!(report.missing_in_b, 1);
+        assert_eq!(report.missing_in_a, 0);
+        assert_eq!(report.rows.len(), 1);
+        assert_eq!(report.rows[0].kind, RowDiffKind::MissingInB);
+        assert_eq!(
+            report.rows[0].a,
+            Some(vec!["7".to_string(), "x".to_string()])
+        );
+
+        let report = diff_results(&b, &a, opts);
+        assert_eq!(report.missing_in_a, 1);
+        assert_eq!(report.missing_in_b, 0);
+    }
+
+    #[test]
+    fn test_unordered_never_equal_nulls() {
+        let rows = || {
+            vec![
+                vec![CellValue::Int(1), CellValue::Null],
+                vec![CellValue::Int(2), text("x")],
+            ]
+        };
+        let a = result(&["id", "v"], rows());
+        let b = result(&["id", "v"], rows().into_iter().rev().collect());
+        let opts = DiffOptions {
+            ignore_order: true,
+            null_equivalence: NullEquivalence::NeverEqual,
+            ..DiffOptions::default()
+        };
+
+        let report = diff_results(&a, &b, opts);
+        assert_eq!(report.missing_in_a, 1);
+        assert_eq!(report.missing_in_b, 1);
+        assert_eq!(report.rows[0].kind, RowDiffKind::MissingInB);
+        assert_eq!(report.rows[0].a_index, Some(0));
+        assert_eq!(report.rows[1].kind, RowDiffKind::MissingInA);
+        assert_eq!(report.rows[1].b_index, Some(1));
+    }
+
+    #[test]
+    fn test_unordered_matches_within_tolerance() {
+        let a = result(
+            &["n", "x"],
+            vec![
+                vec![CellValue::Int(1), CellValue::Double(0.1 + 0.2)],
+                vec![CellValue::Int(2), CellValue::Double(20.0)],
+            ],
+        );
+        let b = result(
+            &["n", "x"],
+            vec![
+                vec![CellValue::Int(2), CellValue::Decimal("20".to_string())],
+                vec![CellValue::Int(1), CellValue::Decimal("0.3".to_string())],
+            ],
+        );
+        let opts = DiffOptions {
+            ignore_order: true,
+            ..DiffOptions::default()
+        };
+        assert!(diff_results(&a, &b, opts).is_match());
+    }
+
+    #[test]
+    fn test_text_columns_from_metadata() {
+        let a = result(&["code"], vec![vec![text("007")], vec![text("1e3")]]);
+        let b = result(&["code"], vec![vec![text("7")], vec![text("1000")]]);
+        // Without metadata the strings are read as numbers
+        assert!(diff_results(&a, &b, DiffOptions::default()).is_match());
+
+        let mut varchar = a.clone();
+        varchar.column_meta = vec![ColumnMeta {
+            column_type: ColumnType::String,
+            unsigned: false,
+            charset: 255,
+            decimals: 0,
+            length: 40,
+        }];
+        let report = diff_results(&varchar, &b, DiffOptions::default());
+        assert_eq!(report.value_mismatches, 2);
+        let unordered = DiffOptions {
+            ignore_order: true,
+            ..DiffOptions::default()
+        };
+        assert_eq!(diff_results(&varchar, &b, unordered).missing_in_b, 2);
+    }
+
+    #[test]
+    fn test_max_reported_rows_and_display() {
+        let a = result(&["n"], (0..5).map(|i| vec![CellValue::Int(i)]).collect());
+        let b = result(&["n"], vec![]);
+        let opts = DiffOptions {
+            max_reported_rows: 2,
+            ..DiffOptions::default()
+        };
+        let report = diff_results(&a, &b, opts);
+        assert_eq!(report.missing_in_b, 5);
+        assert_eq!(report.rows.len(), 2);
+        assert_eq!(
+            report.to_string(),
+            "Rows: 5 vs 0; 0 missing in a, 5 missing in b, 0 value mismatches\n\
+             a row 1 missing in b\n  a: 0\n\
+             a row 2 missing in b\n  a: 1\n"
+        );
+    }
+}
@@ -0,0 +1,76 @@
+//! The SSB sample data gives the same results on MySQL and DataFusion
+//!
+//! Needs a server (see `MySQLConfig::default`) where the user may create
+//! databases; set FUSIONLAB_TEST_MYSQL to run it:
+//!
+//! ```text
+//! FUSIONLAB_TEST_MYSQL=1 cargo test -p fusionlab-core --test ssb_sample
+//! ```
+
+use fusionlab_core::verify::DiffOptions;
+use fusionlab_core::{compare_results, DataFusionRunner, MySQLConfig, MySQLRunner};
+use std::path::Path;
+
+const DATABASE: &str = "fusionlab_ssb_sample";
+
+/// SSB queries that only use the columns of the sample tables
+const QUERY_FILES: &[&str] = &["q1.1.sql", "q2.1.sql", "q3.1.sql"];
+
+#[tokio::test]
+async fn test_ssb_sample_matches() {
+    if std::env::var("FUSIONLAB_TEST_MYSQL").is_err() {
+        return;
+    }
+
+    let loader = MySQLRunner::new(&MySQLConfig::default()).unwrap();
+    let loaded = loader.load_ssb_sample(DATABASE).await.unwrap();
+    // Loading again replaces the tables instead of duplicating rows
+    assert_eq!(loader.load_ssb_sample(DATABASE).await.unwrap(), loaded);
+    loader.close().await;
+    let counts: Vec<(&str, usize)> = loaded.iter().map(|(t, n)| (t.as_str(), *n)).collect();
+    assert_eq!(
+        counts,
+        vec![
+            ("lineorder", 100),
+            ("customer", 30),
+            ("supplier", 20),
+            ("part", 200),
+            ("date", 2352),
+        ]
+    );
+
+    let mysql = MySQLRunner::new(&MySQLConfig {
+        database: DATABASE.to_string(),
+        ..MySQLConfig::default()
+    })
+    .unwrap();
+    let datafusion = DataFusionRunner::new();
+    datafusion.register_ssb_sample().unwrap();
+
+    let queries_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../data/queries");
+    let mut queries: Vec<(String, String)> = QUERY_FILES
+        .iter()
+        .map(|file| {
+            let sql = std::fs::read_to_string(queries_dir.join(file)).unwrap();
+            (file.to_string(), sql)
+        })
+        .collect();
+    queries.push((
+        "revenue by nation".to_string(),
+        "SELECT c_nation, COUNT(*) AS orders, SUM(lo_revenue) AS revenue \
+         FROM lineorder JOIN customer ON lo_custkey = c_custkey GROUP BY c_nation"
+            .to_string(),
+    ));
+
+    for (name, sql) in &queries {
+        let mysql_result = mysql.run_query(sql).await.unwrap();
+        let df_result = datafusion.run_query_collect(sql).await.unwrap();
+        let opts = DiffOptions {
+            ignore_order: true,
+            ..DiffOptions::default()
+        };
+        let report = compare_results(&mysql_result, &df_result, opts).unwrap();
+        assert!(report.is_match(), "{}:\n{}", name, report);
+    }
+    mysql.close().await;
+}
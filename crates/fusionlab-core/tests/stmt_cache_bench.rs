@@ -0,0 +1,67 @@
+//! Latency of parameterized queries with and without the statement cache
+//!
+//! Needs a server (see `MySQLConfig::default`); set FUSIONLAB_TEST_MYSQL to
+//! run it, and `--nocapture` to see the numbers:
+//!
+//! ```text
+//! FUSIONLAB_TEST_MYSQL=1 cargo test -p fusionlab-core --test stmt_cache_bench -- --nocapture
+//! ```
+
+use fusionlab_core::{MySQLConfig, MySQLRunner, QueryStats};
+
+const TEMPLATES: usize = 30;
+const ROUNDS: usize = 20;
+
+/// Run every template `ROUNDS` times and summarize per-query latency
+async fn replay(stmt_cache_size: usize) -> (QueryStats, MySQLRunner) {
+    let config = MySQLConfig {
+        stmt_cache_size,
+        ..MySQLConfig::default()
+    };
+    let runner = MySQLRunner::new(&config).unwrap();
+    let templates: Vec<String> = (0..TEMPLATES)
+        .map(|i| {
+            format!(
+                "SELECT ? + {i} AS v, CONCAT(?, '-{i}') AS s FROM DUAL \
+                 WHERE ? BETWEEN 0 AND 1000000 ORDER BY v"
+            )
+        })
+        .collect();
+
+    let mut samples = Vec::with_capacity(TEMPLATES * ROUNDS);
+    for round in 0..ROUNDS {
+        for sql in &templates {
+            let result = runner
+                .run_query_params(sql.as_str(), (round, "k", round))
+                .await
+                .unwrap();
+            samples.push(result.duration_ms);
+        }
+    }
+    (QueryStats::from_samples(samples, 0, 1), runner)
+}
+
+#[tokio::test]
+async fn bench_stmt_cache() {
+    if std::env::var("FUSIONLAB_TEST_MYSQL").is_err() {
+        return;
+    }
+
+    let (uncached, runner) = replay(0).await;
+    runner.close().await;
+    let (cached, runner) = replay(TEMPLATES).await;
+    let stats = runner.cache_stats();
+    runner.close().await;
+
+    println!("uncached: {}", uncached);
+    println!("cached:   {}", cached);
+    println!(
+        "cache: {} hits, {} misses, {} evictions",
+        stats.hits, stats.misses, stats.evictions
+    );
+
+    // Each template is prepared once, then reused for every later round
+    assert_eq!(stats.misses, TEMPLATES as u64);
+    assert_eq!(stats.hits, (TEMPLATES * (ROUNDS - 1)) as u64);
+    assert_eq!(stats.evictions, 0);
+}
@@ -165,6 +165,26 @@ extern "C" {
 
     pub fn ibd_read_row(table: IbdTableHandle, row_out: *mut IbdRowHandle) -> c_int;
 
+    // Extension point: a batched read would let `IbdTable::next_rows` fill a
+    // whole batch with one FFI round trip:
+    //
+    //   int ibd_read_rows_batch(IbdTableHandle table, IbdRowHandle *rows_out,
+    //                           uint32_t max_rows, uint32_t *rows_read);
+    //
+    // Returns IBD_SUCCESS with *rows_read <= max_rows, or IBD_END_OF_STREAM
+    // once no rows remain. Until libibd_reader exports it, `next_rows` loops
+    // over `ibd_read_row`.
+    //
+    // Extension point: a filter on the clustered index would let the reader
+    // seek to matching leaf pages instead of scanning the whole tree:
+    //
+    //   int ibd_set_filter(IbdTableHandle table, uint32_t column_index,
+    //                      int op, int64_t low, int64_t high);
+    //
+    // with `op` one of EQ (low only), LT/LE/GT/GE (one bound) or BETWEEN.
+    // Until libibd_reader exports it, `IbdTable::set_filter` skips rows on the
+    // Rust side and only `RowFilter::IntEq` is implemented.
+
     pub fn ibd_row_column_count(row: IbdRowHandle) -> u32;
 
     pub fn ibd_row_get_column(
@@ -173,6 +193,7 @@ extern "C" {
         value: *mut IbdColumnValue,
     ) -> c_int;
 
+    /// Like `snprintf`: returns the full length, `>= buffer_size` if truncated
     pub fn ibd_row_to_string(row: IbdRowHandle, buffer: *mut c_char, buffer_size: size_t)
         -> size_t;
 
@@ -183,35 +204,66 @@ extern "C" {
     pub fn ibd_get_row_count(table: IbdTableHandle) -> u64;
 }
 
+/// # Safety
+///
+/// Always sound; the stand-in does nothing. The linked function must not
+/// run concurrently with other library calls.
 #[cfg(not(ibd_reader_available))]
 pub unsafe fn ibd_init() -> c_int {
     IbdResult::ErrorNotImplemented as c_int
 }
 
+/// # Safety
+///
+/// Always sound; the stand-in does nothing. With the library linked, no
+/// reader, table or row handle may be used afterwards.
 #[cfg(not(ibd_reader_available))]
 pub unsafe fn ibd_cleanup() {}
 
+/// # Safety
+///
+/// Always sound; returns a null pointer, which callers must check before
+/// reading it as a C string.
 #[cfg(not(ibd_reader_available))]
 pub unsafe fn ibd_get_version() -> *const c_char {
     std::ptr::null()
 }
 
+/// # Safety
+///
+/// Always sound; returns a null handle, which callers must check before
+/// passing it to other functions.
 #[cfg(not(ibd_reader_available))]
 pub unsafe fn ibd_reader_create() -> IbdReaderHandle {
     std::ptr::null_mut()
 }
 
+/// # Safety
+///
+/// `reader` must come from `ibd_reader_create` and not be used afterwards.
 #[cfg(not(ibd_reader_available))]
 pub unsafe fn ibd_reader_destroy(_reader: IbdReaderHandle) {}
 
+/// # Safety
+///
+/// `reader` must be a live handle from `ibd_reader_create`. The stand-in
+/// returns a null pointer.
 #[cfg(not(ibd_reader_available))]
 pub unsafe fn ibd_reader_get_error(_reader: IbdReaderHandle) -> *const c_char {
     std::ptr::null()
 }
 
+/// # Safety
+///
+/// `reader` must be a live handle from `ibd_reader_create`.
 #[cfg(not(ibd_reader_available))]
 pub unsafe fn ibd_reader_set_debug(_reader: IbdReaderHandle, _enable: c_int) {}
 
+/// # Safety
+///
+/// `reader` must be a live reader handle, `ibd_path` and `sdi_json_path`
+/// NUL-terminated strings, and `table_out` valid for writes. The stand-in
+/// writes nothing.
 #[cfg(not(ibd_reader_available))]
 pub unsafe fn ibd_open_table(
     _reader: IbdReaderHandle,
@@ -222,6 +274,11 @@ pub unsafe fn ibd_open_table(
     IbdResult::ErrorNotImplemented as c_int
 }
 
+/// # Safety
+///
+/// `table` must be a live table handle, `table_name` valid for
+/// `table_name_size` bytes of writes and `column_count` valid for a write.
+/// The stand-in writes nothing.
 #[cfg(not(ibd_reader_available))]
 pub unsafe fn ibd_get_table_info(
     _table: IbdTableHandle,
@@ -232,6 +289,10 @@ pub unsafe fn ibd_get_table_info(
     IbdResult::ErrorNotImplemented as c_int
 }
 
+/// # Safety
+///
+/// `table` must be a live table handle, `name` valid for `name_size` bytes
+/// of writes and `col_type` valid for a write. The stand-in writes nothing.
 #[cfg(not(ibd_reader_available))]
 pub unsafe fn ibd_get_column_info(
     _table: IbdTableHandle,
@@ -243,16 +304,27 @@ pub unsafe fn ibd_get_column_info(
     IbdResult::ErrorNotImplemented as c_int
 }
 
+/// # Safety
+///
+/// `table` must be a live table handle and `row_out` valid for a write.
+/// The stand-in writes nothing.
 #[cfg(not(ibd_reader_available))]
 pub unsafe fn ibd_read_row(_table: IbdTableHandle, _row_out: *mut IbdRowHandle) -> c_int {
     IbdResult::ErrorNotImplemented as c_int
 }
 
+/// # Safety
+///
+/// `row` must be a live row handle from `ibd_read_row`.
 #[cfg(not(ibd_reader_available))]
 pub unsafe fn ibd_row_column_count(_row: IbdRowHandle) -> u32 {
     0
 }
 
+/// # Safety
+///
+/// `row` must be a live row handle and `value` valid for a write. The
+/// stand-in writes nothing.
 #[cfg(not(ibd_reader_available))]
 pub unsafe fn ibd_row_get_column(
     _row: IbdRowHandle,
@@ -262,6 +334,10 @@ pub unsafe fn ibd_row_get_column(
     IbdResult::ErrorNotImplemented as c_int
 }
 
+/// # Safety
+///
+/// `row` must be a live row handle and `buffer` valid for `buffer_size`
+/// bytes of writes. The stand-in writes nothing.
 #[cfg(not(ibd_reader_available))]
 pub unsafe fn ibd_row_to_string(
     _row: IbdRowHandle,
@@ -271,12 +347,22 @@ pub unsafe fn ibd_row_to_string(
     0
 }
 
+/// # Safety
+///
+/// `row` must come from `ibd_read_row` and not be used afterwards.
 #[cfg(not(ibd_reader_available))]
 pub unsafe fn ibd_free_row(_row: IbdRowHandle) {}
 
+/// # Safety
+///
+/// `table` must come from `ibd_open_table` and not be used afterwards,
+/// nor any row read from it.
 #[cfg(not(ibd_reader_available))]
 pub unsafe fn ibd_close_table(_table: IbdTableHandle) {}
 
+/// # Safety
+///
+/// `table` must be a live table handle from `ibd_open_table`.
 #[cfg(not(ibd_reader_available))]
 pub unsafe fn ibd_get_row_count(_table: IbdTableHandle) -> u64 {
     0
@@ -14,8 +14,10 @@
 //! ```
 
 pub mod ffi;
+mod sdi;
 
 use ffi::{IbdColumnType, IbdResult};
+use std::cmp::Ordering;
 use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
 use std::path::Path;
@@ -41,8 +43,8 @@ pub enum IbdError {
     InvalidFormat(String),
     #[error("Compression error")]
     Compression,
-    #[error("Decompression error")]
-    Decompression,
+    #[error("Decompression error: {0}")]
+    Decompression(String),
     #[error("Encryption error")]
     Encryption,
     #[error("Decryption error")]
@@ -81,7 +83,7 @@ fn ibd_error_from_result(result: IbdResult, message: Option<String>) -> IbdError
         IbdResult::ErrorFileWrite => IbdError::FileWrite(msg),
         IbdResult::ErrorInvalidFormat => IbdError::InvalidFormat(msg),
         IbdResult::ErrorCompression => IbdError::Compression,
-        IbdResult::ErrorDecompression => IbdError::Decompression,
+        IbdResult::ErrorDecompression => IbdError::Decompression(msg),
         IbdResult::ErrorEncryption => IbdError::Encryption,
         IbdResult::ErrorDecryption => IbdError::Decryption,
         IbdResult::ErrorMemory => IbdError::Memory,
@@ -118,6 +120,29 @@ pub struct ColumnInfo {
     pub name: String,
     pub col_type: ColumnType,
     pub index: u32,
+    /// Total digits, for DECIMAL columns
+    pub precision: Option<u8>,
+    /// Digits after the decimal point, for DECIMAL columns
+    pub scale: Option<i8>,
+}
+
+impl ColumnInfo {
+    /// Whether this is a hidden InnoDB column, such as `DB_TRX_ID` or
+    /// `DB_ROLL_PTR`, rather than one of the table's own columns
+    pub fn is_internal(&self) -> bool {
+        self.col_type == ColumnType::Internal
+    }
+}
+
+/// Table name and columns, without the open file handle
+#[derive(Debug, Clone)]
+pub struct TableSchema {
+    pub name: String,
+    /// All columns in storage order, including internal ones
+    pub columns: Vec<ColumnInfo>,
+    /// First column of the clustered index, if the table has an explicit
+    /// primary key
+    pub index_column: Option<String>,
 }
 
 /// Column type enumeration
@@ -191,6 +216,94 @@ impl ColumnValue {
     }
 }
 
+/// Row predicate applied while reading a table
+///
+/// Column indexes are row positions as passed to [`IbdRow::get`]. Only integer
+/// equality exists today; range variants belong here once the reader can seek
+/// (see the `ibd_set_filter` note in `ffi`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowFilter {
+    /// `column = value` on an INT or UINT column
+    IntEq { column: u32, value: i64 },
+}
+
+impl RowFilter {
+    /// Row position of the filtered column
+    pub fn column(&self) -> u32 {
+        match self {
+            RowFilter::IntEq { column, .. } => *column,
+        }
+    }
+
+    /// Check a value of the filtered column; NULL never matches
+    pub fn matches(&self, value: &ColumnValue) -> bool {
+        match (self, value) {
+            (RowFilter::IntEq { value: wanted, .. }, ColumnValue::Int(v)) => v == wanted,
+            (RowFilter::IntEq { value: wanted, .. }, ColumnValue::UInt(v)) => {
+                i64::try_from(*v) == Ok(*wanted)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Min/max of an integer or floating-point column over the rows a table
+/// has returned so far
+#[derive(Debug, Clone)]
+pub struct ColumnStats {
+    /// Row position of the column, as passed to [`IbdRow::get`]
+    pub column: u32,
+    /// Smallest non-NULL value; `None` until one is seen
+    pub min: Option<ColumnValue>,
+    /// Largest non-NULL value; `None` until one is seen
+    pub max: Option<ColumnValue>,
+}
+
+impl ColumnStats {
+    /// Empty stats for the column at row position `column`
+    pub fn new(column: u32) -> Self {
+        Self {
+            column,
+            min: None,
+            max: None,
+        }
+    }
+
+    /// Widen the range to include `value`; NULL and NaN are ignored
+    pub fn update(&mut self, value: &ColumnValue) {
+        let comparable = match *value {
+            ColumnValue::Int(_) | ColumnValue::UInt(_) => true,
+            ColumnValue::Float(v) => !v.is_nan(),
+            _ => false,
+        };
+        if !comparable {
+            return;
+        }
+        if self
+            .min
+            .as_ref()
+            .map_or(true, |min| numeric_cmp(value, min) == Some(Ordering::Less))
+        {
+            self.min = Some(value.clone());
+        }
+        if self.max.as_ref().map_or(true, |max| {
+            numeric_cmp(value, max) == Some(Ordering::Greater)
+        }) {
+            self.max = Some(value.clone());
+        }
+    }
+}
+
+/// Order of two numbers of the same variant
+fn numeric_cmp(a: &ColumnValue, b: &ColumnValue) -> Option<Ordering> {
+    match (a, b) {
+        (ColumnValue::Int(x), ColumnValue::Int(y)) => Some(x.cmp(y)),
+        (ColumnValue::UInt(x), ColumnValue::UInt(y)) => Some(x.cmp(y)),
+        (ColumnValue::Float(x), ColumnValue::Float(y)) => x.partial_cmp(y),
+        _ => None,
+    }
+}
+
 /// A row from an InnoDB table
 pub struct IbdRow {
     handle: ffi::IbdRowHandle,
@@ -256,18 +369,11 @@ impl IbdRow {
     }
 
     /// Get all values as tab-separated string
+    #[allow(clippy::inherent_to_string)]
     pub fn to_string(&self) -> String {
-        unsafe {
-            let mut buffer = vec![0u8; 4096];
-            let len =
-                ffi::ibd_row_to_string(self.handle, buffer.as_mut_ptr() as *mut i8, buffer.len());
-
-            if len > 0 && len < buffer.len() {
-                String::from_utf8_lossy(&buffer[..len]).to_string()
-            } else {
-                String::new()
-            }
-        }
+        read_growing(ROW_STRING_BUFFER, |buffer| unsafe {
+            ffi::ibd_row_to_string(self.handle, buffer.as_mut_ptr() as *mut i8, buffer.len())
+        })
     }
 }
 
@@ -284,6 +390,11 @@ pub struct IbdTable {
     handle: ffi::IbdTableHandle,
     table_name: String,
     columns: Vec<ColumnInfo>,
+    index_column: Option<String>,
+    filter: Option<RowFilter>,
+    column_stats: Vec<ColumnStats>,
+    /// First row, read by `open_table` to validate the schema
+    pending: Option<IbdRow>,
 }
 
 // The table handle is only moved across threads, never shared concurrently.
@@ -308,8 +419,128 @@ impl IbdTable {
             .count()
     }
 
-    /// Read next row
+    /// Name of the first column of the clustered index, if the table has an
+    /// explicit primary key
+    pub fn index_column(&self) -> Option<&str> {
+        self.index_column.as_deref()
+    }
+
+    /// Copy of the table's schema
+    pub fn schema(&self) -> TableSchema {
+        TableSchema {
+            name: self.table_name.clone(),
+            columns: self.columns.clone(),
+            index_column: self.index_column.clone(),
+        }
+    }
+
+    /// Only return rows matching `filter` from `next_row` and `next_rows`
+    ///
+    /// The reader has no seek API yet, so pages are still read in full and
+    /// non-matching rows are skipped before they reach the caller.
+    pub fn set_filter(&mut self, filter: Option<RowFilter>) {
+        self.filter = filter;
+    }
+
+    /// Track min/max of the given columns (row positions, as passed to
+    /// [`IbdRow::get`]) in the rows returned from now on
+    ///
+    /// Only INT, UINT, FLOAT and DOUBLE columns are tracked; other positions
+    /// are ignored, so they cost nothing. Each tracked column is read once
+    /// more per row, so callers that decode the rows anyway should feed
+    /// their values to a [`ColumnStats`] of their own instead. Replaces any
+    /// previous stats; an empty slice stops tracking.
+    pub fn collect_column_stats(&mut self, columns: &[u32]) {
+        let numeric: Vec<u32> = self
+            .columns
+            .iter()
+            .filter(|c| !c.is_internal())
+            .zip(0u32..)
+            .filter(|(c, _)| {
+                matches!(
+                    c.col_type,
+                    ColumnType::Int | ColumnType::UInt | ColumnType::Float | ColumnType::Double
+                )
+            })
+            .map(|(_, position)| position)
+            .collect();
+        self.column_stats = columns
+            .iter()
+            .filter(|column| numeric.contains(column))
+            .map(|&column| ColumnStats::new(column))
+            .collect();
+    }
+
+    /// Min/max of the columns passed to `collect_column_stats`, over the
+    /// rows returned so far
+    pub fn column_stats(&self) -> &[ColumnStats] {
+        &self.column_stats
+    }
+
+    /// Read up to `max` rows; an empty vector means the table is exhausted
+    ///
+    /// Currently one `ibd_read_row` call per row; see the `ibd_read_rows_batch`
+    /// note in `ffi` for the single-call version.
+    pub fn next_rows(&mut self, max: usize) -> Result<Vec<IbdRow>, IbdError> {
+        let mut rows = Vec::with_capacity(max);
+        while rows.len() < max {
+            match self.next_row()? {
+                Some(row) => rows.push(row),
+                None => break,
+            }
+        }
+        Ok(rows)
+    }
+
+    /// Call `f` for every remaining row and return how many rows it saw
+    ///
+    /// Rows are released after each call, so memory stays flat regardless of
+    /// table size. An error from `f` stops iteration and is returned as is.
+    pub fn for_each_row<F>(&mut self, mut f: F) -> Result<u64, IbdError>
+    where
+        F: FnMut(&IbdRow) -> Result<(), IbdError>,
+    {
+        let mut count = 0;
+        while let Some(row) = self.next_row()? {
+            f(&row)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Count the remaining rows without decoding their values
+    ///
+    /// Rows are read and released one at a time like `for_each_row`, but no
+    /// column is converted to a `ColumnValue` unless a filter needs it, so
+    /// this is the cheapest way to get a row count out of a tablespace.
+    pub fn count_rows(&mut self) -> Result<u64, IbdError> {
+        let mut count = 0;
+        while self.next_row()?.is_some() {
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Read next row, skipping rows rejected by the filter
     pub fn next_row(&mut self) -> Result<Option<IbdRow>, IbdError> {
+        while let Some(row) = self.read_row()? {
+            match &self.filter {
+                Some(filter) if !filter.matches(&row.get(filter.column())?) => continue,
+                _ => {
+                    for stats in &mut self.column_stats {
+                        stats.update(&row.get(stats.column)?);
+                    }
+                    return Ok(Some(row));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    fn read_row(&mut self) -> Result<Option<IbdRow>, IbdError> {
+        if let Some(row) = self.pending.take() {
+            return Ok(Some(row));
+        }
         unsafe {
             let mut row_handle: ffi::IbdRowHandle = ptr::null_mut();
             let result = ffi::ibd_read_row(self.handle, &mut row_handle);
@@ -319,10 +550,7 @@ impl IbdTable {
                 return Ok(None); // No more rows
             }
             if ibd_result != IbdResult::Success {
-                return Err(ibd_error_from_result(
-                    ibd_result,
-                    Some("Failed to read row".to_string()),
-                ));
+                return Err(read_row_error(&self.table_name, ibd_result));
             }
 
             if row_handle.is_null() {
@@ -331,16 +559,17 @@ impl IbdTable {
                 ));
             }
 
-            let column_count = ffi::ibd_row_column_count(row_handle);
-
-            Ok(Some(IbdRow {
+            let row = IbdRow {
                 handle: row_handle,
-                column_count,
-            }))
+                column_count: ffi::ibd_row_column_count(row_handle),
+            };
+            // Rows leave out the internal DB_TRX_ID and DB_ROLL_PTR columns
+            check_row_columns(&self.table_name, self.column_count(), row.column_count)?;
+            Ok(Some(row))
         }
     }
 
-    /// Get total rows read so far
+    /// Get total rows read so far, including rows skipped by the filter
     pub fn row_count(&self) -> u64 {
         unsafe { ffi::ibd_get_row_count(self.handle) }
     }
@@ -396,6 +625,9 @@ impl IbdReader {
     }
 
     /// Open a table for reading
+    ///
+    /// Reads the first row to check it against the SDI schema, and fails with
+    /// `IbdError::InvalidFormat` if the column counts disagree.
     pub fn open_table<P: AsRef<Path>, Q: AsRef<Path>>(
         &self,
         ibd_path: P,
@@ -403,6 +635,10 @@ impl IbdReader {
     ) -> Result<IbdTable, IbdError> {
         let ibd_cstr = path_to_cstring(ibd_path.as_ref())?;
         let sdi_cstr = path_to_cstring(sdi_path.as_ref())?;
+        // The C side validates the SDI; this only supplements column metadata
+        let sdi_json = std::fs::read_to_string(sdi_path.as_ref()).unwrap_or_default();
+        let decimal_params = sdi::decimal_params(&sdi_json);
+        let index_column = sdi::first_index_column(&sdi_json);
 
         unsafe {
             let mut table_handle: ffi::IbdTableHandle = ptr::null_mut();
@@ -472,20 +708,74 @@ impl IbdReader {
                     .to_string_lossy()
                     .to_string();
 
+                let col_type = ColumnType::from(IbdColumnType::from(col_type));
+                let (precision, scale) = match decimal_params.get(&col_name) {
+                    Some((p, s)) if col_type == ColumnType::Decimal => (Some(*p), Some(*s)),
+                    _ => (None, None),
+                };
+
                 columns.push(ColumnInfo {
                     name: col_name,
-                    col_type: ColumnType::from(IbdColumnType::from(col_type)),
+                    col_type,
                     index: i,
+                    precision,
+                    scale,
                 });
             }
 
-            Ok(IbdTable {
+            let mut table = IbdTable {
                 handle: table_handle,
                 table_name,
                 columns,
-            })
+                index_column,
+                filter: None,
+                column_stats: Vec::new(),
+                pending: None,
+            };
+            // An SDI from another version of the table only shows in the
+            // rows, so read the first one now rather than fail mid-scan
+            table.pending = table.read_row()?;
+            Ok(table)
         }
     }
+
+    /// Read the schema of a table, the equivalent of `DESCRIBE`
+    ///
+    /// Like `open_table`, this reads the first row to check the SDI against
+    /// the pages.
+    pub fn describe_table<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        ibd_path: P,
+        sdi_path: Q,
+    ) -> Result<TableSchema, IbdError> {
+        Ok(self.open_table(ibd_path, sdi_path)?.schema())
+    }
+}
+
+/// Error for a failed `ibd_read_row` on `table`
+fn read_row_error(table: &str, result: IbdResult) -> IbdError {
+    let message = match result {
+        // libibd_reader does not report which page failed
+        IbdResult::ErrorDecompression => format!("failed to decompress a page of {}", table),
+        _ => "Failed to read row".to_string(),
+    };
+    ibd_error_from_result(result, Some(message))
+}
+
+/// Fail if a row does not have one value per visible schema column
+///
+/// A mismatch means the SDI describes a different version of the table
+/// than the one the pages were written with (a column added or dropped),
+/// so values would land in the wrong columns.
+fn check_row_columns(table: &str, schema_columns: usize, row_columns: u32) -> Result<(), IbdError> {
+    if row_columns as usize == schema_columns {
+        return Ok(());
+    }
+    Err(IbdError::InvalidFormat(format!(
+        "rows of {} have {} columns but its SDI lists {}; the SDI is probably from a \
+         different version of the table",
+        table, row_columns, schema_columns
+    )))
 }
 
 impl Drop for IbdReader {
@@ -508,6 +798,25 @@ pub fn version() -> String {
     }
 }
 
+/// Initial buffer for `IbdRow::to_string`; wider rows trigger a retry
+const ROW_STRING_BUFFER: usize = 4096;
+
+/// Read a string from a `snprintf`-style `fill`, which writes at most
+/// `buffer.len() - 1` bytes plus a terminator and returns the full length
+///
+/// A returned length that does not fit means the text was truncated, so the
+/// buffer is resized to that length and `fill` runs again.
+fn read_growing<F: FnMut(&mut [u8]) -> usize>(initial: usize, mut fill: F) -> String {
+    let mut buffer = vec![0u8; initial.max(1)];
+    loop {
+        let len = fill(&mut buffer);
+        if len < buffer.len() {
+            return String::from_utf8_lossy(&buffer[..len]).to_string();
+        }
+        buffer = vec![0u8; len + 1];
+    }
+}
+
 fn formatted_to_string(formatted: &[c_char]) -> String {
     let len = formatted
         .iter()
@@ -556,6 +865,16 @@ mod tests {
         })
     }
 
+    const TYPES_IBD: &str = "/home/cslog/mysql/percona-parser/tests/types_test.ibd";
+    const TYPES_SDI: &str = "/home/cslog/mysql/percona-parser/tests/types_test_sdi.json";
+
+    /// Paths of the types fixture, or `None` when it or the reader library
+    /// is missing and the test should be skipped
+    fn types_fixture() -> Option<(&'static str, &'static str)> {
+        (ibd_lib_available() && Path::new(TYPES_IBD).exists() && Path::new(TYPES_SDI).exists())
+            .then_some((TYPES_IBD, TYPES_SDI))
+    }
+
     #[test]
     fn test_version() {
         if !ibd_lib_available() {
@@ -573,4 +892,201 @@ mod tests {
         let reader = IbdReader::new();
         assert!(reader.is_ok());
     }
+
+    #[test]
+    fn test_next_rows() {
+        let Some((ibd_path, sdi_path)) = types_fixture() else {
+            return;
+        };
+
+        // The fixture holds two rows
+        let reader = IbdReader::new().unwrap();
+        let mut table = reader.open_table(ibd_path, sdi_path).unwrap();
+        assert_eq!(table.next_rows(1).unwrap().len(), 1);
+        assert_eq!(table.next_rows(10).unwrap().len(), 1);
+        assert!(table.next_rows(10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_describe_table() {
+        let Some((ibd_path, sdi_path)) = types_fixture() else {
+            return;
+        };
+
+        let reader = IbdReader::new().unwrap();
+        let schema = reader.describe_table(ibd_path, sdi_path).unwrap();
+        let table = reader.open_table(ibd_path, sdi_path).unwrap();
+        assert_eq!(schema.name, table.name());
+        assert_eq!(schema.columns.len(), table.columns().len());
+        let visible = schema.columns.iter().filter(|c| !c.is_internal()).count();
+        assert_eq!(visible, table.column_count());
+        for (i, column) in schema.columns.iter().enumerate() {
+            assert_eq!(column.index as usize, i);
+            if column.precision.is_some() {
+                assert_eq!(column.col_type, ColumnType::Decimal);
+            }
+        }
+    }
+
+    #[test]
+    fn test_read_row_error() {
+        let err = read_row_error("orders", IbdResult::ErrorDecompression);
+        assert!(matches!(err, IbdError::Decompression(_)));
+        assert_eq!(
+            err.to_string(),
+            "Decompression error: failed to decompress a page of orders"
+        );
+        assert!(matches!(
+            read_row_error("orders", IbdResult::ErrorFileRead),
+            IbdError::FileRead(ref message) if message == "Failed to read row"
+        ));
+    }
+
+    #[test]
+    fn test_check_row_columns() {
+        assert!(check_row_columns("orders", 5, 5).is_ok());
+        let err = check_row_columns("orders", 5, 4).unwrap_err();
+        assert!(matches!(err, IbdError::InvalidFormat(_)));
+        assert_eq!(
+            err.to_string(),
+            "Invalid file format: rows of orders have 4 columns but its SDI lists 5; \
+             the SDI is probably from a different version of the table"
+        );
+    }
+
+    #[test]
+    fn test_row_filter_matches() {
+        let filter = RowFilter::IntEq {
+            column: 0,
+            value: 42,
+        };
+        assert!(filter.matches(&ColumnValue::Int(42)));
+        assert!(filter.matches(&ColumnValue::UInt(42)));
+        assert!(!filter.matches(&ColumnValue::Int(-42)));
+        assert!(!filter.matches(&ColumnValue::UInt(u64::MAX)));
+        assert!(!filter.matches(&ColumnValue::Null));
+        assert!(!filter.matches(&ColumnValue::String("42".to_string())));
+    }
+
+    #[test]
+    fn test_column_stats_update() {
+        let mut stats = ColumnStats::new(0);
+        for value in [
+            ColumnValue::Int(5),
+            ColumnValue::Null,
+            ColumnValue::Int(-3),
+            ColumnValue::Int(12),
+        ] {
+            stats.update(&value);
+        }
+        assert!(matches!(stats.min, Some(ColumnValue::Int(-3))));
+        assert!(matches!(stats.max, Some(ColumnValue::Int(12))));
+
+        let mut stats = ColumnStats::new(1);
+        stats.update(&ColumnValue::Float(f64::NAN));
+        assert!(stats.min.is_none());
+        stats.update(&ColumnValue::Float(2.5));
+        stats.update(&ColumnValue::Float(-0.5));
+        assert!(matches!(stats.min, Some(ColumnValue::Float(v)) if v == -0.5));
+        assert!(matches!(stats.max, Some(ColumnValue::Float(v)) if v == 2.5));
+    }
+
+    #[test]
+    fn test_collect_column_stats() {
+        let Some((ibd_path, sdi_path)) = types_fixture() else {
+            return;
+        };
+
+        let reader = IbdReader::new().unwrap();
+        let mut table = reader.open_table(ibd_path, sdi_path).unwrap();
+        let positions: Vec<u32> = (0..table.column_count() as u32).collect();
+        table.collect_column_stats(&positions);
+        // `id` is the first column; the fixture's rows have ids 1 and 2
+        assert_eq!(table.column_stats()[0].column, 0);
+        assert!(table.column_stats()[0].min.is_none());
+        table.count_rows().unwrap();
+        let id = &table.column_stats()[0];
+        assert!(matches!(id.min, Some(ColumnValue::Int(1))), "{:?}", id);
+        assert!(matches!(id.max, Some(ColumnValue::Int(2))), "{:?}", id);
+    }
+
+    #[test]
+    fn test_for_each_row() {
+        let Some((ibd_path, sdi_path)) = types_fixture() else {
+            return;
+        };
+
+        let reader = IbdReader::new().unwrap();
+        let mut table = reader.open_table(ibd_path, sdi_path).unwrap();
+        let mut widths = Vec::new();
+        let count = table
+            .for_each_row(|row| {
+                widths.push(row.column_count());
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(widths.len(), 2);
+
+        // A closure error stops after the first row
+        let mut table = reader.open_table(ibd_path, sdi_path).unwrap();
+        let mut calls = 0;
+        let result = table.for_each_row(|_| {
+            calls += 1;
+            Err(IbdError::Library("stop".to_string()))
+        });
+        assert!(matches!(result, Err(IbdError::Library(msg)) if msg == "stop"));
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn test_count_rows() {
+        let Some((ibd_path, sdi_path)) = types_fixture() else {
+            return;
+        };
+
+        let reader = IbdReader::new().unwrap();
+        let mut table = reader.open_table(ibd_path, sdi_path).unwrap();
+        let expected = table.for_each_row(|_| Ok(())).unwrap();
+        let mut table = reader.open_table(ibd_path, sdi_path).unwrap();
+        assert_eq!(table.count_rows().unwrap(), expected);
+        // The cursor is exhausted afterwards
+        assert_eq!(table.count_rows().unwrap(), 0);
+    }
+
+    /// Mimics `snprintf` into `buffer`, returning the untruncated length
+    fn fill_from(text: &str, buffer: &mut [u8]) -> usize {
+        let n = text.len().min(buffer.len() - 1);
+        buffer[..n].copy_from_slice(&text.as_bytes()[..n]);
+        buffer[n] = 0;
+        text.len()
+    }
+
+    #[test]
+    fn test_read_growing_wide_row() {
+        let wide: String = (0..40)
+            .map(|i| format!("{}{}", i, "x".repeat(200)))
+            .collect::<Vec<_>>()
+            .join("\t");
+        assert!(wide.len() > ROW_STRING_BUFFER);
+
+        let mut calls = 0;
+        let text = read_growing(ROW_STRING_BUFFER, |buffer| {
+            calls += 1;
+            fill_from(&wide, buffer)
+        });
+        assert_eq!(text, wide);
+        assert_eq!(calls, 2);
+
+        // Text that exactly fills the buffer but for the terminator fits
+        let exact = "y".repeat(ROW_STRING_BUFFER - 1);
+        let mut calls = 0;
+        let text = read_growing(ROW_STRING_BUFFER, |buffer| {
+            calls += 1;
+            fill_from(&exact, buffer)
+        });
+        assert_eq!(text, exact);
+        assert_eq!(calls, 1);
+        assert_eq!(read_growing(ROW_STRING_BUFFER, |b| fill_from("", b)), "");
+    }
 }
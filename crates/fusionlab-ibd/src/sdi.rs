@@ -0,0 +1,117 @@
+//! Column metadata read directly from the SDI JSON
+//!
+//! The C API reports only a column's name and type class. Attributes such as
+//! DECIMAL precision and scale are taken from the `ibd2sdi` JSON instead.
+
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// DECIMAL `(precision, scale)` per column name
+pub(crate) fn decimal_params(sdi_json: &str) -> HashMap<String, (u8, i8)> {
+    let mut params = HashMap::new();
+    let Ok(root) = serde_json::from_str::<Value>(sdi_json) else {
+        return params;
+    };
+
+    for column in table_columns(&root) {
+        let name = column.get("name").and_then(Value::as_str);
+        let precision = column.get("numeric_precision").and_then(Value::as_u64);
+        let scale = column.get("numeric_scale").and_then(Value::as_u64);
+        if let (Some(name), Some(precision), Some(scale)) = (name, precision, scale) {
+            if let (Ok(precision), Ok(scale)) = (u8::try_from(precision), i8::try_from(scale)) {
+                params.insert(name.to_string(), (precision, scale));
+            }
+        }
+    }
+    params
+}
+
+/// Name of the first key column of the first index (the clustered index)
+///
+/// `None` when the table has no explicit primary key, in which case InnoDB
+/// clusters on the hidden `DB_ROW_ID`.
+pub(crate) fn first_index_column(sdi_json: &str) -> Option<String> {
+    let root = serde_json::from_str::<Value>(sdi_json).ok()?;
+    let table = table_object(&root)?;
+    let element = table
+        .get("indexes")?
+        .as_array()?
+        .first()?
+        .get("elements")?
+        .as_array()?
+        .first()?;
+    let opx = usize::try_from(element.get("column_opx")?.as_u64()?).ok()?;
+    let name = table_columns(&root).get(opx)?.get("name")?.as_str()?;
+    (!name.starts_with("DB_")).then(|| name.to_string())
+}
+
+/// The `columns` array of the first table `dd_object` in the document
+fn table_columns(value: &Value) -> &[Value] {
+    match table_object(value).and_then(|o| o.get("columns")) {
+        Some(Value::Array(columns)) => columns,
+        _ => &[],
+    }
+}
+
+/// The first `dd_object` that has a `columns` array
+fn table_object(value: &Value) -> Option<&Value> {
+    match value {
+        Value::Object(map) => {
+            if let Some(object) = map.get("dd_object").filter(|o| o.get("columns").is_some()) {
+                return Some(object);
+            }
+            map.values().find_map(table_object)
+        }
+        Value::Array(items) => items.iter().find_map(table_object),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decimal_params() {
+        let sdi = r#"[
+          "ibd2sdi",
+          { "type": 1, "id": 1066,
+            "object": { "dd_object": { "name": "types_fixture", "columns": [
+              { "name": "id", "type": 4, "numeric_precision": 10, "numeric_scale": 0 },
+              { "name": "amount", "type": 21, "numeric_precision": 10, "numeric_scale": 2 },
+              { "name": "note", "type": 16, "numeric_precision": 0 }
+            ] } } }
+        ]"#;
+
+        let params = decimal_params(sdi);
+        assert_eq!(params.get("amount"), Some(&(10, 2)));
+        assert_eq!(params.get("note"), None);
+        assert!(decimal_params("not json").is_empty());
+    }
+
+    #[test]
+    fn test_first_index_column() {
+        let sdi = r#"[
+          "ibd2sdi",
+          { "type": 1, "id": 1066,
+            "object": { "dd_object": { "name": "orders",
+              "columns": [
+                { "name": "note", "type": 16 },
+                { "name": "order_id", "type": 4 },
+                { "name": "DB_TRX_ID", "type": 10 }
+              ],
+              "indexes": [
+                { "name": "PRIMARY", "type": 1, "elements": [
+                  { "ordinal_position": 1, "column_opx": 1 },
+                  { "ordinal_position": 2, "column_opx": 2 }
+                ] }
+              ] } } }
+        ]"#;
+        assert_eq!(first_index_column(sdi), Some("order_id".to_string()));
+
+        // Without a primary key the clustered index is on the hidden row id
+        let hidden = sdi.replace(r#""column_opx": 1"#, r#""column_opx": 2"#);
+        assert_eq!(first_index_column(&hidden), None);
+        assert_eq!(first_index_column("not json"), None);
+    }
+}